@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand};
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCompleter;
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, str::FromStr};
 
 #[derive(Parser)]
 #[command(version, about = "Facture is a small customer & invoice database that lets you render invoices to pdf", long_about = None)]
@@ -6,12 +10,83 @@ use clap::{Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+    /// Database to use instead of the one from facture.yaml, ./facture.db or
+    /// $XDG_DATA_HOME/facture/facture.db
+    #[arg(long, global = true, env = "FACTURE_DB")]
+    pub database: Option<PathBuf>,
+    /// Folder to render invoices to instead of the one from facture.yaml or the invoices folder
+    /// next to the database
+    #[arg(long, global = true, env = "FACTURE_INVOICE_FOLDER")]
+    pub invoice_folder: Option<PathBuf>,
+    /// Business profile to use instead of the one from facture.yaml or `default`
+    #[arg(long, global = true, env = "FACTURE_PROFILE")]
+    pub profile: Option<String>,
+    /// Seconds to wait for another facture process to release the database instead of failing
+    /// right away
+    #[arg(
+        long,
+        global = true,
+        env = "FACTURE_WAIT",
+        value_name = "SECONDS",
+        default_value_t = 0
+    )]
+    pub wait: u64,
+    /// Answer yes to every confirmation
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+    /// Format of lists and records. Lists default to a table, single records to YAML
+    #[arg(long, global = true, env = "FACTURE_OUTPUT")]
+    pub output: Option<OutputFormat>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A table for reading in the terminal
+    Table,
+    /// Pretty printed JSON with field names that stay the same between versions
+    Json,
+    /// YAML with the same fields as JSON
+    Yaml,
+    /// Comma separated values with a header row
+    Csv,
 }
 
 /// Subcommands of the application
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    /// Initialize facture
+    #[command(flatten)]
+    Open(Box<OpenCommand>),
+    /// Maintain the database
+    #[command(subcommand)]
+    Db(DatabaseCommand),
+    /// Restore a fresh database from a backup
+    Restore {
+        /// Archive to restore
+        archive: PathBuf,
+    },
+    /// Print the shell completions
+    ///
+    /// Completes commands, flags and the ids of customers and invoices, which are read from the
+    /// database while completing. Load them on shell startup, like `source <(facture completions
+    /// bash)` in ~/.bashrc, `source <(facture completions zsh)` in ~/.zshrc or `facture
+    /// completions fish | source` in ~/.config/fish/config.fish.
+    Completions {
+        /// Shell to print the completions for
+        shell: CompletionShell,
+    },
+}
+
+/// Subcommands that work on the opened database
+#[derive(Subcommand, Debug)]
+pub enum OpenCommand {
+    /// Initialize facture or create the business profile selected with --profile
     Init,
     /// Manipulate customers
     #[command(subcommand)]
@@ -25,50 +100,676 @@ pub enum Commands {
     /// Manipulate configuration
     #[command(subcommand)]
     Config(ConfigCommand),
+    /// Manage business profiles
+    ///
+    /// Every profile has its own business, config and counters. Customers and invoices belong to
+    /// the profile they were created in, customers can also be shared between all profiles. Ids
+    /// are unique across all profiles, so the id templates of a new profile start with its name,
+    /// like `shop-K0001` and `shop-R2025-001`.
+    #[command(subcommand)]
+    Profile(ProfileCommand),
+    /// Export customers or invoices
+    ///
+    /// Customers are exported with the fields `id`, `organization`, `vat_id`, `name`, `surname`,
+    /// `email`, `phone`, `street`, `number`, `postal_code`, `city` and `country`.
+    ///
+    /// Invoices are exported with the fields `id`, `customer` (the customer id), `issuing_date`,
+    /// `delivery_date`, `due_days` and `items`, where every item has a `description`, a `price` and
+    /// an optional `quantity`. In CSV every item is written as its own row and the invoice fields
+    /// are repeated.
+    Export(ExportArgs),
+    /// Import customers or invoices
+    ///
+    /// Uses the same fields as `export`. Records are matched by their `id`: existing records are
+    /// updated, unknown ones are created. Invoices reference their customer by its `id`.
+    Import(ImportArgs),
+    /// Write a backup of all records, their history, the undo journal and rendered invoices
+    ///
+    /// The backup is a tar archive of YAML files that also records the model versions, so it can
+    /// be restored by later versions of facture.
+    Backup {
+        /// Archive to write. Defaults to facture-backup-<timestamp>.tar
+        archive: Option<PathBuf>,
+    },
+    /// List, restore or purge removed customers and invoices
+    ///
+    /// Removed customers and invoices are hidden from lists and prompts but kept in the trash.
+    #[command(subcommand)]
+    Trash(TrashCommand),
+    /// Show who changed what and when
+    ///
+    /// Every create, update and delete is recorded with the changed fields. Without an id the
+    /// recent activity of all records is listed.
+    History {
+        /// Id of a customer or invoice like K0001, a uuid or a profile name
+        #[arg(add = ArgValueCompleter::new(crate::completion::record_ids))]
+        id: Option<String>,
+        /// Only show changes made on or after this date, like 2025-01-31
+        #[arg(long)]
+        since: Option<NaiveDate>,
+    },
+    /// Search customers and invoices for a text
+    ///
+    /// Looks through the ids, names, contact details and addresses of customers and the ids and
+    /// item descriptions of invoices, ignoring case. Every matching field is listed.
+    Search {
+        /// Text to look for
+        term: String,
+    },
+    /// Revert the last create, update or delete
+    ///
+    /// Everything a command wrote is reverted together, like the customer and the invoice counter
+    /// of a new invoice. The last 50 commands can be undone.
+    Undo,
+    /// Apply the last undone command again
+    ///
+    /// Undone commands can not be redone after something else was written.
+    Redo,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum CustomerCommand {
     /// Add a customer
-    Add,
+    ///
+    /// Opens $EDITOR unless the record is read from a file or fields are given as flags.
+    Add {
+        /// Share the customer between all profiles
+        #[arg(long)]
+        shared: bool,
+        #[command(flatten)]
+        fields: CustomerFields,
+        #[command(flatten)]
+        input: InputArgs,
+    },
     /// Edit a customer
-    Edit,
+    ///
+    /// Opens $EDITOR unless the record is read from a file or fields are given as flags.
+    Edit {
+        /// Id of the customer like K0001. Prompts for a customer if omitted
+        #[arg(add = ArgValueCompleter::new(crate::completion::customer_ids))]
+        id: Option<String>,
+        #[command(flatten)]
+        fields: CustomerFields,
+        #[command(flatten)]
+        input: InputArgs,
+    },
     /// List all cusomters
-    List,
-    /// Remove a cusomter
-    Remove,
+    List {
+        /// Order of the customers
+        #[arg(long, default_value = "id")]
+        sort: CustomerSort,
+        /// Only list this many customers
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Move a cusomter and all their invoices to the trash
+    Remove {
+        /// Id of the customer like K0001. Prompts for a customer if omitted
+        #[arg(add = ArgValueCompleter::new(crate::completion::customer_ids))]
+        id: Option<String>,
+    },
     /// Show a customer
-    Show,
+    Show {
+        /// Id of the customer like K0001. Prompts for a customer if omitted
+        #[arg(add = ArgValueCompleter::new(crate::completion::customer_ids))]
+        id: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Create or update customers from vCard files
+    ///
+    /// Maps ORG to the organization, N to the contact name and surname, EMAIL, TEL and ADR to the
+    /// contact and address. Cards carrying an X-FACTURE-ID property update the customer with that
+    /// id. Other cards update the customer with the same email or else the same organization and
+    /// name, and create a new customer if there is none.
+    ImportVcard {
+        /// vCard (.vcf) files to import
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Only show what would be created or changed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export all customers as vCards
+    ///
+    /// The facture id is stored in the X-FACTURE-ID property so the cards can be imported again.
+    ExportVcard {
+        /// File to write to. Writes to stdout if omitted
+        #[arg(short = 'o', long, value_name = "FILE")]
+        to_file: Option<PathBuf>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 pub enum InvoiceCommand {
     /// Add an invoice
-    Add,
+    ///
+    /// Opens $EDITOR unless the record is read from a file or fields are given as flags.
+    Add {
+        #[command(flatten)]
+        fields: InvoiceFields,
+        #[command(flatten)]
+        input: InputArgs,
+    },
     /// Edit an invoice
-    Edit,
-    /// List all invoices
-    List,
-    /// Remove a invoice
-    Remove,
+    ///
+    /// Opens $EDITOR unless the record is read from a file or fields are given as flags.
+    Edit {
+        /// Id of the invoice like R2025-001. Prompts for an invoice if omitted
+        #[arg(add = ArgValueCompleter::new(crate::completion::invoice_ids))]
+        id: Option<String>,
+        #[command(flatten)]
+        fields: InvoiceFields,
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// List the invoices, all of them unless filtered
+    List {
+        #[command(flatten)]
+        filter: InvoiceFilter,
+        /// Order of the invoices
+        #[arg(long, default_value = "date")]
+        sort: InvoiceSort,
+        /// Only list this many invoices
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Move a invoice to the trash
+    Remove {
+        /// Id of the invoice like R2025-001. Prompts for an invoice if omitted
+        #[arg(add = ArgValueCompleter::new(crate::completion::invoice_ids))]
+        id: Option<String>,
+    },
     /// Show a invoice
-    Show,
-    /// Render a invoice to pdf
-    Render,
+    Show {
+        /// Id of the invoice like R2025-001. Prompts for an invoice if omitted
+        #[arg(add = ArgValueCompleter::new(crate::completion::invoice_ids))]
+        id: Option<String>,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Render invoices to pdf
+    Render {
+        /// Ids of the invoices like R2025-001. Prompts for invoices if omitted
+        #[arg(add = ArgValueCompleter::new(crate::completion::invoice_ids))]
+        ids: Vec<String>,
+    },
+    /// Render an invoice and issue it
+    ///
+    /// Issued invoices are numbered per profile and chained by hashes over their content, the
+    /// hash of the invoice issued before them and the hash of their pdf. Apart from their payment
+    /// date they can not be changed or removed afterwards. Run `facture db verify` to check the
+    /// chain.
+    Issue {
+        /// Id of the invoice like R2025-001. Prompts for an unissued invoice if omitted
+        #[arg(add = ArgValueCompleter::new(crate::completion::invoice_ids))]
+        id: Option<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 pub enum BusinessCommand {
     /// Edit business
-    Edit,
+    ///
+    /// Opens $EDITOR unless the record is read from a file or fields are given as flags.
+    Edit {
+        #[command(flatten)]
+        fields: Box<BusinessFields>,
+        #[command(flatten)]
+        input: InputArgs,
+    },
     /// Show business
-    Show,
+    Show {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 pub enum ConfigCommand {
     /// Edit configuration
-    Edit,
+    ///
+    /// Opens $EDITOR unless the record is read from a file or fields are given as flags.
+    Edit {
+        #[command(flatten)]
+        fields: ConfigFields,
+        #[command(flatten)]
+        input: InputArgs,
+    },
     /// Show configuration
-    Show,
+    Show {
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+/// Where to read a record from instead of opening $EDITOR
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Input")]
+pub struct InputArgs {
+    /// Read the record from a YAML or JSON file. Fields missing in the file keep their value and
+    /// field flags are applied on top
+    #[arg(long, value_name = "FILE", conflicts_with = "from_stdin")]
+    pub from_file: Option<PathBuf>,
+    /// Read the record as YAML or JSON from stdin
+    #[arg(long)]
+    pub from_stdin: bool,
+}
+
+/// Filters of `invoice list`. Invoices have to match all given filters
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Filter")]
+pub struct InvoiceFilter {
+    /// Only invoices of the customer with this id
+    #[arg(long, add = ArgValueCompleter::new(crate::completion::customer_ids))]
+    pub customer: Option<String>,
+    /// Only invoices issued on or after this date, like 2025-01-01
+    #[arg(long)]
+    pub from: Option<NaiveDate>,
+    /// Only invoices issued on or before this date, like 2025-12-31
+    #[arg(long)]
+    pub to: Option<NaiveDate>,
+    /// Only invoices issued in this year
+    #[arg(long)]
+    pub year: Option<i32>,
+    /// Only invoices in this state
+    #[arg(long)]
+    pub status: Option<InvoiceStatus>,
+    /// Only invoices with a net amount of at least this much
+    #[arg(long, value_name = "AMOUNT")]
+    pub min_amount: Option<f32>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvoiceStatus {
+    /// Not issued yet
+    Draft,
+    /// Issued and waiting for the payment
+    Open,
+    /// Issued and not paid past the due date
+    Overdue,
+    /// Has a payment date
+    Paid,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum InvoiceSort {
+    /// Issuing date, oldest first
+    Date,
+    /// Invoice id, with numbers in it compared by value like INV-9 before INV-10
+    Number,
+    /// Net amount, smallest first
+    Amount,
+    /// Organization or name of the customer
+    Customer,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CustomerSort {
+    /// Customer id, with numbers in it compared by value
+    Id,
+    /// Organization
+    Organization,
+    /// Surname and name of the contact
+    Name,
+}
+
+#[derive(Args, Debug, Default)]
+pub struct OutputArgs {
+    /// Write the record to a file instead of printing it. Files ending in .json get JSON, all
+    /// others YAML
+    #[arg(long, value_name = "FILE")]
+    pub to_file: Option<PathBuf>,
+}
+
+/// Fields of a customer. Omitted fields keep their value
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Fields")]
+pub struct CustomerFields {
+    /// Id instead of the next one from the customer counter. Renames the customer when editing
+    #[arg(long = "id", id = "new_id", value_name = "ID")]
+    pub id: Option<String>,
+    #[arg(long)]
+    pub organization: Option<String>,
+    #[arg(long)]
+    pub vat_id: Option<String>,
+    /// Name of the contact person
+    #[arg(long)]
+    pub name: Option<String>,
+    /// Surname of the contact person
+    #[arg(long)]
+    pub surname: Option<String>,
+    #[arg(long)]
+    pub email: Option<String>,
+    #[arg(long)]
+    pub phone: Option<String>,
+    #[command(flatten)]
+    pub address: AddressFields,
+}
+
+/// Fields of an invoice. Omitted fields keep their value
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Fields")]
+pub struct InvoiceFields {
+    /// Id instead of the next one from the invoice counter. Renames the invoice when editing
+    #[arg(long = "id", id = "new_id", value_name = "ID")]
+    pub id: Option<String>,
+    /// Id of the customer like K0001. Prompts for a customer if omitted when adding
+    #[arg(long, add = ArgValueCompleter::new(crate::completion::customer_ids))]
+    pub customer: Option<String>,
+    /// Like 2025-01-31
+    #[arg(long)]
+    pub issuing_date: Option<NaiveDate>,
+    /// Like 2025-01-31
+    #[arg(long)]
+    pub delivery_date: Option<NaiveDate>,
+    #[arg(long)]
+    pub due_days: Option<u32>,
+    /// Like 2025-01-31
+    #[arg(long)]
+    pub payment_date: Option<NaiveDate>,
+    /// An item as DESCRIPTION:PRICE or DESCRIPTION:PRICE:QUANTITY. Repeat it for more items.
+    /// Replaces all items when editing
+    #[arg(long = "item", value_name = "ITEM")]
+    pub items: Vec<ItemArg>,
+}
+
+/// Fields of the business. Omitted fields keep their value
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Fields")]
+pub struct BusinessFields {
+    #[arg(long)]
+    pub name: Option<String>,
+    #[arg(long)]
+    pub vat_id: Option<String>,
+    #[arg(long)]
+    pub tax_number: Option<String>,
+    /// VAT rate in percent, like 19
+    #[arg(long, value_parser = parse_percent)]
+    pub vat: Option<f32>,
+    #[arg(long)]
+    pub small_business: Option<bool>,
+    #[arg(long)]
+    pub contact_name: Option<String>,
+    #[arg(long)]
+    pub contact_surname: Option<String>,
+    #[arg(long)]
+    pub email: Option<String>,
+    #[arg(long)]
+    pub phone: Option<String>,
+    #[command(flatten)]
+    pub address: AddressFields,
+    #[arg(long)]
+    pub bank: Option<String>,
+    #[arg(long)]
+    pub iban: Option<String>,
+    #[arg(long)]
+    pub bic: Option<String>,
+}
+
+/// Parses a percentage between 0 and 100
+fn parse_percent(value: &str) -> Result<f32, String> {
+    let percent: f32 = value
+        .parse()
+        .map_err(|_| format!("{value} is not a number"))?;
+    if (0.0..=100.0).contains(&percent) {
+        Ok(percent)
+    } else {
+        Err(format!("{value} is not between 0 and 100"))
+    }
+}
+
+#[derive(Args, Debug, Default)]
+pub struct AddressFields {
+    #[arg(long)]
+    pub street: Option<String>,
+    /// House number
+    #[arg(long)]
+    pub number: Option<String>,
+    #[arg(long)]
+    pub postal_code: Option<String>,
+    #[arg(long)]
+    pub city: Option<String>,
+    #[arg(long)]
+    pub country: Option<String>,
+}
+
+/// Fields of the configuration. Omitted fields keep their value
+#[derive(Args, Debug, Default)]
+#[command(next_help_heading = "Fields")]
+pub struct ConfigFields {
+    #[arg(long)]
+    pub customer_template: Option<String>,
+    #[arg(long)]
+    pub customer_counter: Option<usize>,
+    #[arg(long)]
+    pub invoice_template: Option<String>,
+    #[arg(long)]
+    pub invoice_counter: Option<usize>,
+}
+
+/// An invoice item given as `DESCRIPTION:PRICE[:QUANTITY]`. The description may contain colons
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemArg {
+    pub description: String,
+    pub price: f32,
+    pub quantity: Option<u32>,
+}
+
+impl FromStr for ItemArg {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{value} is not DESCRIPTION:PRICE or DESCRIPTION:PRICE:QUANTITY");
+        let (rest, last) = value.rsplit_once(':').ok_or_else(invalid)?;
+        // With a quantity the price is the second to last part
+        if let Some((description, price)) = rest.rsplit_once(':') {
+            if let (Ok(price), Ok(quantity)) = (price.parse(), last.parse()) {
+                return Ok(Self {
+                    description: description.to_owned(),
+                    price,
+                    quantity: Some(quantity),
+                });
+            }
+        }
+        Ok(Self {
+            description: rest.to_owned(),
+            price: last.parse().map_err(|_| invalid())?,
+            quantity: None,
+        })
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Records to export
+    pub records: ExchangeRecords,
+    /// File to write to. Writes to stdout if omitted
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub to_file: Option<PathBuf>,
+    /// Format of the export. Derived from the file extension if omitted
+    #[arg(short, long)]
+    pub format: Option<ExchangeFormat>,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// Records to import
+    pub records: ExchangeRecords,
+    /// File to read from
+    pub file: PathBuf,
+    /// Format of the import. Derived from the file extension if omitted
+    #[arg(short, long)]
+    pub format: Option<ExchangeFormat>,
+    /// Only show what would be created or changed
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProfileCommand {
+    /// List all profiles
+    List,
+    /// Remove a profile that has no customers or invoices
+    Remove {
+        /// Name of the profile
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrashCommand {
+    /// List the customers and invoices in the trash
+    List,
+    /// Move a customer or invoice out of the trash
+    ///
+    /// Restoring a customer also restores the invoices that were removed together with it.
+    Restore {
+        /// Id of the customer or invoice. Prompts for a record in the trash if omitted
+        id: Option<String>,
+    },
+    /// Delete a customer or invoice in the trash for good
+    ///
+    /// Purging a customer also purges their invoices. Issued invoices have to be kept for 10
+    /// years after the end of the year they are dated in and can not be purged before.
+    Purge {
+        /// Id of the customer or invoice. Prompts for a record in the trash if omitted
+        id: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DatabaseCommand {
+    #[command(flatten)]
+    Open(OpenDatabaseCommand),
+    /// Encrypt the database file with a new passphrase
+    ///
+    /// Encrypted native and SQLite databases are unlocked with the passphrase from
+    /// FACTURE_PASSPHRASE, the output of the command in FACTURE_PASSPHRASE_COMMAND like
+    /// `pass show facture` or a prompt. The command is run with `sh -c` as the current user, so
+    /// only set it in an environment you trust. The new passphrase is read from
+    /// FACTURE_NEW_PASSPHRASE or asked for.
+    ///
+    /// While facture runs, the database is decrypted into a private folder in
+    /// FACTURE_RUNTIME_DIR or XDG_RUNTIME_DIR. Facture refuses to open an encrypted database if
+    /// neither is set, as the fallback temporary folder may be shared or kept on disk.
+    ///
+    /// Backups and exports are not encrypted. The yaml backend is meant to be read by git and can
+    /// not be encrypted.
+    Rekey {
+        /// Store the database unencrypted again
+        #[arg(long)]
+        remove: bool,
+    },
+}
+
+/// Subcommands of `db` that work on the opened database
+#[derive(Subcommand, Debug)]
+pub enum OpenDatabaseCommand {
+    /// Migrate all records to the latest model versions
+    ///
+    /// Facture migrates automatically when it opens an old database. A copy of the database file
+    /// is taken before migrating.
+    Migrate {
+        /// Only show what would be migrated
+        #[arg(long)]
+        dry_run: bool,
+        /// Rename ids that older versions allowed on more than one record with a numbered suffix,
+        /// like R2024-001-2
+        #[arg(long)]
+        rename_duplicates: bool,
+    },
+    /// Check the records for broken references, duplicate ids and outdated counters
+    Check {
+        /// Repair all issues that can be fixed safely
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Accept changes to the files of the yaml backend that were made outside facture
+    ///
+    /// Facture refuses to overwrite files that changed since it last wrote them. Review the
+    /// changes, for example with git, and reindex to continue.
+    Reindex,
+    /// Recompute the hash chain of the issued invoices and compare their pdfs
+    Verify,
+    /// Copy all records into a new database of another backend
+    ///
+    /// The current database is left untouched. Select the new database with the backend and path
+    /// settings in facture.yaml afterwards.
+    Convert {
+        /// Backend of the new database
+        backend: BackendKind,
+        /// Path of the new database. Defaults to facture.db, facture.sqlite or facture-data next
+        /// to the current database
+        #[arg(short = 'o', long)]
+        path: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExchangeRecords {
+    Customers,
+    Invoices,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ExchangeFormat {
+    /// Comma separated values
+    Csv,
+    /// A single JSON array
+    Json,
+    /// One JSON object per line
+    Jsonl,
+}
+
+/// The storage engines a database can be kept in
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Single file native_db database
+    #[default]
+    Native,
+    /// SQLite database with a relational schema that can be queried with SQL
+    Sqlite,
+    /// Directory with one YAML file per record that can be versioned with git
+    Yaml,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(description: &str, price: f32, quantity: Option<u32>) -> ItemArg {
+        ItemArg {
+            description: description.to_owned(),
+            price,
+            quantity,
+        }
+    }
+
+    #[test]
+    fn parses_items_with_and_without_quantity() {
+        assert_eq!("Design:120".parse(), Ok(item("Design", 120.0, None)));
+        assert_eq!("Hosting:9.5:12".parse(), Ok(item("Hosting", 9.5, Some(12))));
+    }
+
+    #[test]
+    fn keeps_colons_in_the_description() {
+        assert_eq!(
+            "Workshop: Rust 101:450".parse(),
+            Ok(item("Workshop: Rust 101", 450.0, None))
+        );
+        assert_eq!(
+            "Support 09:00-17:00:80:3".parse(),
+            Ok(item("Support 09:00-17:00", 80.0, Some(3)))
+        );
+        // A fractional last part can only be the price
+        assert_eq!("Ratio 16:9:2.5".parse(), Ok(item("Ratio 16:9", 2.5, None)));
+    }
+
+    #[test]
+    fn rejects_items_without_price() {
+        assert!("Design".parse::<ItemArg>().is_err());
+        assert!("Design:free".parse::<ItemArg>().is_err());
+    }
 }
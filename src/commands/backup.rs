@@ -0,0 +1,63 @@
+use crate::{
+    cli::BackendKind,
+    database::{self, backup::Backup, FactureDatabase},
+};
+use anyhow::{bail, Result};
+use chrono::Local;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub fn handle_backup_command(
+    archive: &Option<PathBuf>,
+    database: FactureDatabase,
+    invoice_folder: &Path,
+) -> Result<()> {
+    let archive = archive.clone().unwrap_or_else(|| {
+        let timestamp = Local::now().format("%Y%m%d-%H%M%S");
+        PathBuf::from(format!("facture-backup-{timestamp}.tar"))
+    });
+    let backup = Backup::from_database(&database, invoice_folder)?;
+    backup.write(&archive)?;
+    println!(
+        "Wrote {} customers, {} invoices and {} pdfs to {}",
+        backup.customers.len(),
+        backup.invoices.len(),
+        backup.pdfs.len(),
+        archive.display()
+    );
+    Ok(())
+}
+
+pub fn handle_restore_command(
+    archive: &Path,
+    backend: BackendKind,
+    database_path: &Path,
+    invoice_folder: &Path,
+) -> Result<()> {
+    if database_path.exists() {
+        bail!(
+            "{} already exists. Restore only works into a fresh database",
+            database_path.display()
+        );
+    }
+    let backup = Backup::read(archive)?;
+    backup.check_references()?;
+    println!(
+        "Restoring backup from {} (format {}, facture {})...",
+        backup.manifest.created.format("%Y-%m-%d %H:%M:%S"),
+        backup.manifest.format,
+        backup.manifest.facture_version
+    );
+
+    let result = FactureDatabase::open(backend, database_path, Duration::ZERO)
+        .and_then(|database| backup.restore(&database, invoice_folder));
+    if let Err(error) = result {
+        // Do not leave a half restored database behind
+        database::remove(database_path)?;
+        return Err(error);
+    }
+    println!("Backup restored");
+    Ok(())
+}
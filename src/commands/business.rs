@@ -1,27 +1,85 @@
-use super::CRUD;
+use super::{set, CRUD};
 
 use crate::{
-    cli::BusinessCommand,
-    database::{
-        models::{Business, BUSINESS_PRIMARY_KEY},
-        FactureDatabase, YamlAble,
-    },
+    cli::{BusinessCommand, BusinessFields},
+    database::{models::Business, FactureDatabase, YamlAble},
 };
 use anyhow::Result;
 
 impl YamlAble for Business {}
-impl CRUD for Business {}
+impl CRUD for Business {
+    fn insert(database: &FactureDatabase, business: &Self) -> Result<()> {
+        business.validate()?;
+        database.create(business.clone())?;
+        println!("\n{}", business.to_yaml()?);
+        Ok(())
+    }
+
+    fn update(database: &FactureDatabase, business: &Self, key: &str) -> Result<()> {
+        business.validate()?;
+        database.update(key, business.clone())?;
+        println!("\n{}", business.to_yaml()?);
+        Ok(())
+    }
+}
 
 pub fn handle_business_command(command: &BusinessCommand, database: FactureDatabase) -> Result<()> {
     match command {
-        BusinessCommand::Edit => {
-            let business = database.read::<Business>(BUSINESS_PRIMARY_KEY)?;
-            Business::edit(&database, &business, BUSINESS_PRIMARY_KEY)?;
+        BusinessCommand::Edit { fields, input } => {
+            let business = database.business()?;
+            if fields.is_empty() && input.is_empty() {
+                Business::edit(&database, &business, database.profile())?;
+            } else {
+                let mut business = input.read(&business)?;
+                fields.apply(&mut business);
+                Business::update(&database, &business, database.profile())?;
+            }
         }
-        BusinessCommand::Show => {
-            let business = database.read::<Business>(BUSINESS_PRIMARY_KEY)?;
-            Business::show(&business)?;
+        BusinessCommand::Show { output } => {
+            let business = database.business()?;
+            Business::show(&business, output)?;
         }
     }
     Ok(())
 }
+
+impl BusinessFields {
+    fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.vat_id.is_none()
+            && self.tax_number.is_none()
+            && self.vat.is_none()
+            && self.small_business.is_none()
+            && self.contact_name.is_none()
+            && self.contact_surname.is_none()
+            && self.email.is_none()
+            && self.phone.is_none()
+            && self.address.is_empty()
+            && self.bank.is_none()
+            && self.iban.is_none()
+            && self.bic.is_none()
+    }
+
+    fn apply(&self, business: &mut Business) {
+        set(&mut business.name, &self.name);
+        set(&mut business.vat_id, &self.vat_id);
+        set(&mut business.tax_number, &self.tax_number);
+        // The business keeps the VAT as a fraction
+        if let Some(vat) = self.vat {
+            business.vat = vat / 100.0;
+        }
+        set(&mut business.small_business, &self.small_business);
+        set(&mut business.contact.name, &self.contact_name);
+        set(&mut business.contact.surname, &self.contact_surname);
+        if let Some(email) = &self.email {
+            business.contact.email = Some(email.clone());
+        }
+        if let Some(phone) = &self.phone {
+            business.contact.phone = Some(phone.clone());
+        }
+        self.address.apply(&mut business.address);
+        set(&mut business.payment.bank, &self.bank);
+        set(&mut business.payment.iban, &self.iban);
+        set(&mut business.payment.bic, &self.bic);
+    }
+}
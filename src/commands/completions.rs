@@ -0,0 +1,24 @@
+use crate::cli::CompletionShell;
+use anyhow::Result;
+use clap_complete::env::{Bash, EnvCompleter, Fish, Zsh};
+use std::{env, io};
+
+/// Prints the script that registers facture with the shell. The script calls facture with
+/// `COMPLETE=<shell>` while completing, which is answered before any command runs
+pub fn handle_completions_command(shell: CompletionShell) -> Result<()> {
+    let completer: &dyn EnvCompleter = match shell {
+        CompletionShell::Bash => &Bash,
+        CompletionShell::Zsh => &Zsh,
+        CompletionShell::Fish => &Fish,
+    };
+    // The absolute path keeps completions working for binaries outside of $PATH
+    let bin = env::current_exe()?;
+    completer.write_registration(
+        "COMPLETE",
+        "facture",
+        "facture",
+        &bin.to_string_lossy(),
+        &mut io::stdout(),
+    )?;
+    Ok(())
+}
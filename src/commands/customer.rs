@@ -1,30 +1,101 @@
-use super::{render_id_template, ListAble, CRUD};
+use super::{
+    edit_object_in_temp_file,
+    exchange::{export_vcards, import_vcards},
+    natural_cmp, next_customer_id, select_record, set, Selectable, Unchanged, CRUD,
+};
 use crate::{
-    cli::CustomerCommand,
+    cli::{CustomerCommand, CustomerFields, CustomerSort},
     database::{
-        models::{Config, Customer, Invoice, CONFIG_PRIMARY_KEY},
+        models::{Customer, Invoice, Trashable},
         FactureDatabase, YamlAble,
     },
-    ui::prompt,
+    ui::{output, prompt},
 };
 use anyhow::Result;
+use chrono::Local;
 
 impl YamlAble for Customer {}
-impl ListAble for Customer {}
+
+impl Selectable for Customer {
+    /// Like `K0001 · ACME GmbH · Jane Doe · jane@acme.com`
+    fn labels(_database: &FactureDatabase, customers: &[Self]) -> Result<Vec<String>> {
+        Ok(customers
+            .iter()
+            .map(|customer| {
+                let contact = &customer.contact;
+                let name = format!("{} {}", contact.name, contact.surname);
+                [
+                    customer.id.as_str(),
+                    &customer.organization,
+                    name.trim(),
+                    contact.email.as_deref().unwrap_or_default(),
+                ]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" · ")
+            })
+            .collect())
+    }
+}
 
 impl CRUD for Customer {
+    fn create(database: &FactureDatabase, customer: &Self) -> Result<()> {
+        edit_object_in_temp_file(customer, Unchanged::Save, |edited| {
+            // The id in the editor is a preview of the counter
+            let mut edited = edited.clone();
+            if edited.id == customer.id {
+                edited.id.clear();
+            }
+            Self::insert(database, &edited)
+        })
+    }
+
+    /// Creates the customer. An empty id is taken from the counter in the same transaction, so
+    /// no other write can take the same number in between
+    fn insert(database: &FactureDatabase, customer: &Self) -> Result<()> {
+        let customer = database.transaction(|transaction| {
+            let mut config = transaction.config()?;
+            let mut customer = customer.clone();
+            if customer.id.is_empty() {
+                customer.id = next_customer_id(&config)?;
+            }
+            transaction.create(customer.clone())?;
+            config.customer_counter += 1;
+            transaction.update(transaction.profile(), config)?;
+            Ok(customer)
+        })?;
+        let customer_yaml = customer.to_yaml()?;
+        println!("\n{customer_yaml}");
+        Ok(())
+    }
+
     fn remove(database: &FactureDatabase, key: &str) -> Result<()> {
-        let result = prompt::confirm("This will also delete all invoices")?;
+        let result = prompt::confirm("This will also move all invoices to the trash")?;
         if !result {
             println!("Aborted!");
             return Ok(());
         }
-        let customer: Customer = database.read::<Customer>(key)?;
-        database.delete::<Customer>(key)?;
-        for invoice in customer.invoices {
-            database.delete::<Invoice>(&invoice)?;
-        }
-        println!("Customer removed");
+        // Invoices share the timestamp of their customer so they are restored together
+        let deleted_at = Local::now().naive_local();
+        let customer = database.transaction(|transaction| {
+            let mut customer: Customer = transaction.read::<Customer>(key)?;
+            for uuid in &customer.invoices {
+                let mut invoice: Invoice = transaction.read(uuid)?;
+                // Shared customers keep their invoices in the other profiles
+                if invoice.profile == transaction.profile() && !invoice.is_trashed() {
+                    invoice.deleted_at = Some(deleted_at);
+                    transaction.update(uuid, invoice)?;
+                }
+            }
+            customer.deleted_at = Some(deleted_at);
+            transaction.update(key, customer.clone())?;
+            Ok(customer)
+        })?;
+        println!(
+            "Customer moved to the trash. Restore it with `facture trash restore {}`",
+            customer.id
+        );
 
         Ok(())
     }
@@ -34,45 +105,148 @@ pub fn handle_customer_command(command: &CustomerCommand, database: FactureDatab
     let name = "customer";
 
     match command {
-        CustomerCommand::List => Customer::list(database)?,
-        CustomerCommand::Add => {
-            let mut config = database.read::<Config>(CONFIG_PRIMARY_KEY)?;
-            let customer_id = render_id_template(
-                &config.customer_template,
-                &format!("{:04}", config.customer_counter),
-            )?;
-            let customer = Customer::new_with_uuid(customer_id);
-            Customer::create(&database, &customer)?;
-            config.customer_counter += 1;
-            database.update(CONFIG_PRIMARY_KEY, config)?;
+        CustomerCommand::List { sort, limit } => {
+            let mut customers: Vec<Customer> = database.read_visible()?;
+            match sort {
+                CustomerSort::Id => customers.sort_by(|a, b| natural_cmp(&a.id, &b.id)),
+                CustomerSort::Organization => {
+                    customers.sort_by_cached_key(|customer| customer.organization.to_lowercase())
+                }
+                CustomerSort::Name => customers.sort_by_cached_key(|customer| {
+                    (
+                        customer.contact.surname.to_lowercase(),
+                        customer.contact.name.to_lowercase(),
+                    )
+                }),
+            }
+            if let Some(limit) = limit {
+                customers.truncate(*limit);
+            }
+            output::print_records(&database, customers, "Nothing created yet.")?;
         }
-        CustomerCommand::Remove => {
-            let customers: Vec<Customer> = database.read_all()?;
-            if customers.is_empty() {
-                println!("No customers created yet");
-                return Ok(());
+        CustomerCommand::Add {
+            shared,
+            fields,
+            input,
+        } => {
+            let profile = (!shared).then(|| database.profile().to_owned());
+            if fields.is_empty() && input.is_empty() {
+                let customer_id = next_customer_id(&database.config()?)?;
+                let customer = Customer::new_with_uuid(customer_id, profile);
+                Customer::create(&database, &customer)?;
+            } else {
+                let customer = Customer::new_with_uuid(String::new(), profile);
+                let mut customer = input.read(&customer)?;
+                fields.apply(&mut customer);
+                Customer::insert(&database, &customer)?;
             }
-            let customer = prompt::select(&format!("Select a {name} to remove"), customers)?;
+        }
+        CustomerCommand::Remove { id } => {
+            let message = format!("Select a {name} to remove");
+            let Some(customer) = select_record::<Customer>(&database, id.as_deref(), &message)?
+            else {
+                return Ok(());
+            };
             Customer::remove(&database, &customer.uuid)?;
         }
-        CustomerCommand::Edit => {
-            let customers: Vec<Customer> = database.read_all()?;
-            if customers.is_empty() {
-                println!("No customers created yet");
+        CustomerCommand::Edit { id, fields, input } => {
+            let message = format!("Select a {name} to edit");
+            let Some(customer) = select_record::<Customer>(&database, id.as_deref(), &message)?
+            else {
                 return Ok(());
+            };
+            if fields.is_empty() && input.is_empty() {
+                Customer::edit(&database, &customer, &customer.uuid)?;
+            } else {
+                // The key stays the one of the selected customer even if the input changes it
+                let key = customer.uuid.clone();
+                let mut customer = input.read(&customer)?;
+                fields.apply(&mut customer);
+                Customer::update(&database, &customer, &key)?;
             }
-            let customer = prompt::select(&format!("Select a {name} to edit"), customers)?;
-            Customer::edit(&database, &customer, &customer.uuid)?;
         }
-        CustomerCommand::Show => {
-            let customers: Vec<Customer> = database.read_all()?;
-            if customers.is_empty() {
-                println!("No customers created yet");
-                return Ok(());
+        CustomerCommand::Show { id, output } => {
+            let message = format!("Select a {name} to show");
+            if let Some(customer) = select_record::<Customer>(&database, id.as_deref(), &message)? {
+                Customer::show(&customer, output)?;
             }
-            let customer = prompt::select(&format!("Select a {name} to show"), customers)?;
-            Customer::show(&customer)?;
+        }
+        CustomerCommand::ImportVcard { files, dry_run } => {
+            import_vcards(&database, files, *dry_run)?;
+        }
+        CustomerCommand::ExportVcard { to_file } => {
+            export_vcards(&database, to_file.as_deref())?;
         }
     }
     Ok(())
 }
+
+impl CustomerFields {
+    fn is_empty(&self) -> bool {
+        self.id.is_none()
+            && self.organization.is_none()
+            && self.vat_id.is_none()
+            && self.name.is_none()
+            && self.surname.is_none()
+            && self.email.is_none()
+            && self.phone.is_none()
+            && self.address.is_empty()
+    }
+
+    fn apply(&self, customer: &mut Customer) {
+        set(&mut customer.id, &self.id);
+        set(&mut customer.organization, &self.organization);
+        set(&mut customer.vat_id, &self.vat_id);
+        set(&mut customer.contact.name, &self.name);
+        set(&mut customer.contact.surname, &self.surname);
+        if let Some(email) = &self.email {
+            customer.contact.email = Some(email.clone());
+        }
+        if let Some(phone) = &self.phone {
+            customer.contact.phone = Some(phone.clone());
+        }
+        self.address.apply(&mut customer.address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::BackendKind,
+        database::{models::DEFAULT_PROFILE, testing},
+    };
+
+    #[test]
+    fn removing_a_shared_customer_only_trashes_the_invoices_of_the_profile() {
+        prompt::assume_yes();
+        let (_folder, database) = testing::database(BackendKind::Native);
+        let mut customer = Customer::new_with_uuid("K0001".to_owned(), None);
+        let own = Invoice {
+            customer: customer.uuid.clone(),
+            ..Invoice::new_with_uuid("R1".to_owned(), DEFAULT_PROFILE)
+        };
+        let other = Invoice {
+            customer: customer.uuid.clone(),
+            ..Invoice::new_with_uuid("other-R1".to_owned(), "other")
+        };
+        customer.add_invoice(&own.uuid);
+        customer.add_invoice(&other.uuid);
+        database
+            .transaction(|transaction| {
+                transaction.create(customer.clone())?;
+                transaction.create(own.clone())?;
+                transaction.create(other.clone())
+            })
+            .unwrap();
+
+        Customer::remove(&database, &customer.uuid).unwrap();
+
+        assert!(database
+            .read::<Customer>(&customer.uuid)
+            .unwrap()
+            .is_trashed());
+        assert!(database.read::<Invoice>(&own.uuid).unwrap().is_trashed());
+        assert!(!database.read::<Invoice>(&other.uuid).unwrap().is_trashed());
+    }
+}
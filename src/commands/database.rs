@@ -0,0 +1,194 @@
+use crate::{
+    cli::BackendKind,
+    cli::OpenDatabaseCommand,
+    database::{
+        self,
+        backend::encrypted,
+        chain, check,
+        lock::DatabaseLock,
+        models::{Business, Config, Customer, HistoryEntry, Invoice},
+        settings::settings_path,
+        FactureDatabase,
+    },
+};
+use anyhow::{bail, Result};
+use std::{path::Path, time::Duration};
+
+pub fn handle_database_command(
+    command: &OpenDatabaseCommand,
+    database: FactureDatabase,
+    invoice_folder: &Path,
+) -> Result<()> {
+    match command {
+        OpenDatabaseCommand::Migrate {
+            dry_run,
+            rename_duplicates,
+        } => {
+            let migrations = database.pending_migrations()?;
+            if migrations.is_empty() {
+                println!("Database is up to date");
+                return Ok(());
+            }
+            for migration in &migrations {
+                println!("{migration}");
+            }
+            if *dry_run {
+                println!("Dry run, nothing was migrated");
+                return Ok(());
+            }
+            database.migrate(*rename_duplicates)?;
+            println!("Database migrated");
+        }
+        OpenDatabaseCommand::Check { repair } => {
+            let (issues, repaired) = if *repair {
+                database.transaction(|transaction| {
+                    let issues = check::check(transaction)?;
+                    let repaired = check::repair(transaction, &issues)?;
+                    Ok((issues, repaired))
+                })?
+            } else {
+                (database.read_transaction(check::check)?, 0)
+            };
+            if issues.is_empty() {
+                println!("No issues found");
+                return Ok(());
+            }
+
+            for issue in &issues {
+                let hint = if issue.is_repairable() {
+                    "repairable"
+                } else {
+                    "manual"
+                };
+                println!("[{hint}] {issue}");
+            }
+            println!("\n{} issues found", issues.len());
+            if *repair {
+                println!("{repaired} issues repaired");
+            }
+            let manual = issues.iter().filter(|issue| !issue.is_repairable()).count();
+            if manual > 0 {
+                println!("{manual} issues need to be fixed manually");
+            } else if !repair {
+                println!("Run with --repair to fix them");
+            }
+        }
+        OpenDatabaseCommand::Reindex => {
+            let changes = database.reindex()?;
+            if changes.is_empty() {
+                println!("Index is up to date");
+                return Ok(());
+            }
+            for change in &changes {
+                println!("{change}");
+            }
+            println!("\n{} changes accepted", changes.len());
+        }
+        OpenDatabaseCommand::Verify => {
+            let (verified, breaks) = database
+                .read_transaction(|transaction| chain::verify(transaction, invoice_folder))?;
+            if breaks.is_empty() {
+                println!("{verified} issued invoices verified");
+                return Ok(());
+            }
+            for chain_break in &breaks {
+                println!("{chain_break}");
+            }
+            bail!("The chain of issued invoices has {} breaks", breaks.len());
+        }
+        OpenDatabaseCommand::Convert { backend, path } => {
+            let path = path
+                .clone()
+                .unwrap_or_else(|| database.path().with_file_name(backend.file_name()));
+            if path.exists() {
+                bail!(
+                    "{} already exists. Converting only works into a fresh database",
+                    path.display()
+                );
+            }
+            // Broken references can not be stored by the relational schema of SQLite
+            let issues = database.transaction(check::check)?;
+            let broken = issues.iter().filter(|issue| !issue.is_counter()).count();
+            if broken > 0 {
+                bail!("Database has {broken} issues. Run `facture db check` before converting");
+            }
+
+            let target = FactureDatabase::open(*backend, &path, Duration::ZERO)?;
+            if let Err(error) = convert(&database, &target) {
+                drop(target);
+                database::remove(&path)?;
+                return Err(error);
+            }
+            println!("Converted the database to {}", path.display());
+            println!(
+                "Set `backend: {backend}` and `path: {}` in {} to use it",
+                path.display(),
+                settings_path().display()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_rekey_command(
+    backend: BackendKind,
+    path: &Path,
+    remove: bool,
+    wait: Duration,
+) -> Result<()> {
+    if backend == BackendKind::Yaml {
+        bail!("The yaml backend is meant to be read by git and can not be encrypted");
+    }
+    if !path.exists() {
+        bail!("No database found at {}", path.display());
+    }
+    let _lock = DatabaseLock::acquire(path, wait)?;
+    let encrypted = encrypted::is_encrypted(path)?;
+    if remove {
+        if !encrypted {
+            bail!("{} is not encrypted", path.display());
+        }
+        encrypted::rekey(path, false)?;
+        println!("{} is no longer encrypted", path.display());
+        return Ok(());
+    }
+    encrypted::rekey(path, true)?;
+    if encrypted {
+        println!("Changed the passphrase of {}", path.display());
+    } else {
+        println!("Encrypted {}", path.display());
+    }
+    Ok(())
+}
+
+/// Copies all records and their history in a single transaction of the target
+fn convert(source: &FactureDatabase, target: &FactureDatabase) -> Result<()> {
+    let config: Vec<Config> = source.read_all()?;
+    let business: Vec<Business> = source.read_all()?;
+    let customers: Vec<Customer> = source.read_all()?;
+    let invoices: Vec<Invoice> = source.read_all()?;
+    let history: Vec<HistoryEntry> = source.read_all()?;
+    println!(
+        "Copying {} customers and {} invoices...",
+        customers.len(),
+        invoices.len()
+    );
+    target.transaction(|transaction| {
+        for config in config {
+            transaction.copy(config)?;
+        }
+        for business in business {
+            transaction.copy(business)?;
+        }
+        for customer in customers {
+            transaction.copy(customer)?;
+        }
+        for invoice in invoices {
+            transaction.copy(invoice)?;
+        }
+        for entry in history {
+            transaction.copy(entry)?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,400 @@
+use super::next_customer_id;
+use crate::{
+    cli::{ExchangeRecords, ExportArgs, ImportArgs},
+    database::{
+        models::{Customer, Invoice, Scoped, Trashable},
+        FactureDatabase, Transaction,
+    },
+    exchange::{self, changed_fields, vcard, CustomerRecord, InvoiceRecord},
+};
+use anyhow::{anyhow, bail, Result};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Counts the records of an import. The report goes to stderr, so stdout stays free for data
+#[derive(Default)]
+struct Summary {
+    created: usize,
+    updated: usize,
+    unchanged: usize,
+}
+
+impl Summary {
+    fn create(&mut self, id: &str) {
+        eprintln!("Create {id}");
+        self.created += 1;
+    }
+
+    fn update(&mut self, id: &str, fields: &[String]) {
+        eprintln!("Update {id} ({})", fields.join(", "));
+        self.updated += 1;
+    }
+
+    fn unchanged(&mut self, id: &str) {
+        eprintln!("Unchanged {id}");
+        self.unchanged += 1;
+    }
+
+    fn print(&self, dry_run: bool) {
+        eprintln!(
+            "\n{} created, {} updated, {} unchanged",
+            self.created, self.updated, self.unchanged
+        );
+        if dry_run {
+            eprintln!("Dry run, nothing was written");
+        }
+    }
+}
+
+pub fn handle_export_command(args: &ExportArgs, database: FactureDatabase) -> Result<()> {
+    let format = exchange::resolve_format(args.format, args.to_file.as_deref())?;
+    let writer: Box<dyn Write> = match &args.to_file {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    match args.records {
+        ExchangeRecords::Customers => {
+            let customers: Vec<Customer> = database.read_visible()?;
+            let records: Vec<CustomerRecord> = customers.into_iter().map(Into::into).collect();
+            exchange::write(records, format, writer)?;
+        }
+        ExchangeRecords::Invoices => {
+            let customers: Vec<Customer> = database.read_all()?;
+            let customer_ids: HashMap<String, String> = customers
+                .into_iter()
+                .map(|customer| (customer.uuid, customer.id))
+                .collect();
+            let invoices: Vec<Invoice> = database.read_visible()?;
+            let records = invoices
+                .into_iter()
+                .map(|invoice| {
+                    let customer_id = customer_ids.get(&invoice.customer).ok_or_else(|| {
+                        anyhow!("Customer of invoice {} does not exist", invoice.id)
+                    })?;
+                    Ok(InvoiceRecord::new(invoice, customer_id))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            exchange::write(records, format, writer)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_import_command(args: &ImportArgs, database: FactureDatabase) -> Result<()> {
+    let format = exchange::resolve_format(args.format, Some(&args.file))?;
+    let file = File::open(&args.file)?;
+    // Import everything in a single transaction so a failing record does not leave a partial
+    // import behind
+    let summary = match args.records {
+        ExchangeRecords::Customers => {
+            let records = exchange::read(format, file)?;
+            database
+                .transaction(|transaction| import_customers(transaction, records, args.dry_run))?
+        }
+        ExchangeRecords::Invoices => {
+            let records = exchange::read(format, file)?;
+            database
+                .transaction(|transaction| import_invoices(transaction, records, args.dry_run))?
+        }
+    };
+    summary.print(args.dry_run);
+    Ok(())
+}
+
+fn import_customers(
+    transaction: &Transaction,
+    records: Vec<CustomerRecord>,
+    dry_run: bool,
+) -> Result<Summary> {
+    let mut summary = Summary::default();
+
+    for record in records {
+        let id = record.id.clone();
+        match transaction.find_by_id::<Customer>(&record.id)? {
+            Some(mut customer) => {
+                check_profile(transaction, &id, &customer)?;
+                let fields = changed_fields(&CustomerRecord::from(customer.clone()), &record)?;
+                if fields.is_empty() {
+                    summary.unchanged(&id);
+                    continue;
+                }
+                summary.update(&id, &fields);
+                if !dry_run {
+                    record.apply(&mut customer);
+                    transaction.update(&customer.uuid.clone(), customer)?;
+                }
+            }
+            None => {
+                summary.create(&id);
+                if !dry_run {
+                    let profile = Some(transaction.profile().to_owned());
+                    let mut customer = Customer::new_with_uuid(id, profile);
+                    record.apply(&mut customer);
+                    transaction.create(customer)?;
+                }
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn import_invoices(
+    transaction: &Transaction,
+    records: Vec<InvoiceRecord>,
+    dry_run: bool,
+) -> Result<Summary> {
+    let customers: Vec<Customer> = transaction.read_all()?;
+    let mut summary = Summary::default();
+
+    // Resolve all customers upfront to report invalid references before any change
+    let customer_uuids = records
+        .iter()
+        .map(|record| {
+            let customer = transaction
+                .find_by_id::<Customer>(&record.customer)?
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Customer {} of invoice {} does not exist",
+                        record.customer,
+                        record.id
+                    )
+                })?;
+            check_profile(transaction, &record.customer, &customer)?;
+            Ok(customer.uuid)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let customer_ids: HashMap<&str, &str> = customers
+        .iter()
+        .map(|customer| (customer.uuid.as_str(), customer.id.as_str()))
+        .collect();
+
+    for (record, customer_uuid) in records.into_iter().zip(customer_uuids) {
+        let id = record.id.clone();
+        match transaction.find_by_id::<Invoice>(&record.id)? {
+            Some(mut invoice) => {
+                check_profile(transaction, &id, &invoice)?;
+                let customer_id = customer_ids
+                    .get(invoice.customer.as_str())
+                    .copied()
+                    .unwrap_or_default();
+                let fields =
+                    changed_fields(&InvoiceRecord::new(invoice.clone(), customer_id), &record)?;
+                if fields.is_empty() {
+                    summary.unchanged(&id);
+                    continue;
+                }
+                summary.update(&id, &fields);
+                if !dry_run {
+                    let old_customer_uuid = invoice.customer.clone();
+                    record.apply(&mut invoice, &customer_uuid);
+                    transaction.update(&invoice.uuid.clone(), invoice.clone())?;
+                    if old_customer_uuid != customer_uuid {
+                        if transaction.exists::<Customer>(&old_customer_uuid)? {
+                            let mut old_customer: Customer =
+                                transaction.read(&old_customer_uuid)?;
+                            old_customer.remove_invoice(&invoice.uuid);
+                            transaction.update(&old_customer_uuid, old_customer)?;
+                        }
+                        let mut customer: Customer = transaction.read(&customer_uuid)?;
+                        customer.add_invoice(&invoice.uuid);
+                        transaction.update(&customer_uuid, customer)?;
+                    }
+                }
+            }
+            None => {
+                summary.create(&id);
+                if !dry_run {
+                    let mut invoice = Invoice::new_with_uuid(id, transaction.profile());
+                    record.apply(&mut invoice, &customer_uuid);
+                    transaction.create(invoice.clone())?;
+                    let mut customer: Customer = transaction.read(&customer_uuid)?;
+                    customer.add_invoice(&invoice.uuid);
+                    transaction.update(&customer_uuid, customer)?;
+                }
+            }
+        }
+    }
+    Ok(summary)
+}
+
+pub fn import_vcards(database: &FactureDatabase, files: &[PathBuf], dry_run: bool) -> Result<()> {
+    let mut cards = Vec::new();
+    for file in files {
+        let content = fs::read_to_string(file)?;
+        let parsed =
+            vcard::parse(&content).map_err(|error| anyhow!("{}: {error}", file.display()))?;
+        cards.extend(parsed);
+    }
+
+    let summary = database.transaction(|transaction| import_cards(transaction, cards, dry_run))?;
+    summary.print(dry_run);
+    Ok(())
+}
+
+fn import_cards(
+    transaction: &Transaction,
+    cards: Vec<vcard::VCard>,
+    dry_run: bool,
+) -> Result<Summary> {
+    let mut config = transaction.config()?;
+    let mut customers: Vec<Customer> = transaction.read_all()?;
+    let mut summary = Summary::default();
+
+    for card in cards {
+        let existing = match &card.id {
+            Some(id) => customers.iter().position(|customer| customer.id == *id),
+            None => find_contact(&customers, &card, transaction.profile()),
+        };
+        match existing.map(|index| &mut customers[index]) {
+            Some(customer) => {
+                check_profile(transaction, &customer.id, customer)?;
+                // A card matched by its contact keeps the id of the customer
+                let card = vcard::VCard {
+                    id: Some(customer.id.clone()),
+                    ..card
+                };
+                let fields = changed_fields(&vcard::VCard::from(customer.clone()), &card)?;
+                if fields.is_empty() {
+                    summary.unchanged(&customer.id);
+                    continue;
+                }
+                summary.update(&customer.id, &fields);
+                card.apply(customer);
+                if !dry_run {
+                    transaction.update(&customer.uuid.clone(), customer.clone())?;
+                }
+            }
+            None => {
+                let id = match &card.id {
+                    Some(id) => id.clone(),
+                    None => loop {
+                        let id = next_customer_id(&config)?;
+                        config.customer_counter += 1;
+                        // Imported customers may already use ids ahead of the counter
+                        if !customers.iter().any(|customer| customer.id == id) {
+                            break id;
+                        }
+                    },
+                };
+                summary.create(&id);
+                let profile = Some(transaction.profile().to_owned());
+                let mut customer = Customer::new_with_uuid(id, profile);
+                card.apply(&mut customer);
+                if !dry_run {
+                    transaction.create(customer.clone())?;
+                }
+                customers.push(customer);
+            }
+        }
+    }
+
+    if !dry_run {
+        transaction.update(transaction.profile(), config)?;
+    }
+    Ok(summary)
+}
+
+/// Finds the customer of a card without a facture id by its email or else by its organization
+/// and name, so importing an address book again does not duplicate its contacts
+fn find_contact(customers: &[Customer], card: &vcard::VCard, profile: &str) -> Option<usize> {
+    let candidates = || {
+        customers
+            .iter()
+            .enumerate()
+            .filter(|(_, customer)| customer.is_visible_in(profile) && !customer.is_trashed())
+    };
+    let by_email = card.email.as_ref().and_then(|email| {
+        candidates().find(|(_, customer)| {
+            customer
+                .contact
+                .email
+                .as_ref()
+                .is_some_and(|other| other.eq_ignore_ascii_case(email))
+        })
+    });
+    let by_name = || {
+        candidates().find(|(_, customer)| {
+            customer.organization == card.organization
+                && customer.contact.name == card.name
+                && customer.contact.surname == card.surname
+        })
+    };
+    by_email.or_else(by_name).map(|(index, _)| index)
+}
+
+pub fn export_vcards(database: &FactureDatabase, output: Option<&Path>) -> Result<()> {
+    let customers: Vec<Customer> = database.read_visible()?;
+    let vcf: String = customers
+        .into_iter()
+        .map(|customer| vcard::VCard::from(customer).to_vcf())
+        .collect();
+    match output {
+        Some(path) => fs::write(path, vcf)?,
+        None => print!("{vcf}"),
+    }
+    Ok(())
+}
+
+/// Imports must not change records of other profiles or records in the trash
+fn check_profile(
+    transaction: &Transaction,
+    id: &str,
+    record: &(impl Scoped + Trashable),
+) -> Result<()> {
+    if !record.is_visible_in(transaction.profile()) {
+        bail!(
+            "{id} belongs to the profile {}",
+            record.profile().unwrap_or_default()
+        );
+    }
+    if record.is_trashed() {
+        bail!("{id} is in the trash. Restore it with `facture trash restore {id}`");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cli::BackendKind, database::testing};
+
+    fn card(email: Option<&str>, organization: &str) -> vcard::VCard {
+        vcard::VCard {
+            organization: organization.to_owned(),
+            name: "Jane".to_owned(),
+            surname: "Doe".to_owned(),
+            email: email.map(str::to_owned),
+            ..vcard::VCard::default()
+        }
+    }
+
+    fn import(database: &FactureDatabase, cards: Vec<vcard::VCard>) -> Summary {
+        database
+            .transaction(|transaction| import_cards(transaction, cards, false))
+            .unwrap()
+    }
+
+    #[test]
+    fn importing_cards_again_updates_the_matching_customers() {
+        let (_folder, database) = testing::database(BackendKind::Native);
+        let cards = vec![card(Some("jane@acme.test"), "Acme"), card(None, "Initech")];
+        assert_eq!(import(&database, cards.clone()).created, 2);
+
+        let summary = import(&database, cards);
+        assert_eq!((summary.created, summary.unchanged), (0, 2));
+
+        // The email identifies the contact even if its organization changed
+        let summary = import(&database, vec![card(Some("JANE@acme.test"), "Acme Corp")]);
+        assert_eq!((summary.created, summary.updated), (0, 1));
+        let customers: Vec<Customer> = database.read_all().unwrap();
+        assert_eq!(customers.len(), 2);
+        assert!(customers
+            .iter()
+            .any(|customer| customer.organization == "Acme Corp"));
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    cli::OutputFormat,
+    database::{models::HistoryEntry, FactureDatabase},
+    ui::output,
+};
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::collections::HashSet;
+
+pub fn handle_history_command(
+    id: Option<&str>,
+    since: Option<NaiveDate>,
+    database: FactureDatabase,
+) -> Result<()> {
+    // Entries are ordered by their v7 uuid and thereby by time
+    let mut entries: Vec<HistoryEntry> = database.read_all()?;
+    if let Some(since) = since {
+        entries.retain(|entry| entry.timestamp.date() >= since);
+    }
+
+    let Some(id) = id else {
+        return output::print_records(&database, entries, "No changes recorded.");
+    };
+
+    // Also finds records that were deleted or had another id at the time
+    let mut records: HashSet<String> = entries
+        .iter()
+        .filter(|entry| entry.record_id.as_deref() == Some(id))
+        .map(|entry| entry.record.clone())
+        .collect();
+    records.insert(id.to_owned());
+    entries.retain(|entry| records.contains(&entry.record));
+    // The changes of every entry only fit the terminal, other formats get the whole entries
+    if output::format().is_some_and(|format| format != OutputFormat::Table) {
+        return output::print_records(&database, entries, "");
+    }
+    if entries.is_empty() {
+        println!("No changes recorded for {id}.");
+        return Ok(());
+    }
+    for entry in entries {
+        println!(
+            "{} {} {} {}",
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.operation,
+            entry.record_type,
+            entry.record_id.as_deref().unwrap_or(&entry.record)
+        );
+        for change in &entry.changes {
+            println!("  {change}");
+        }
+    }
+    Ok(())
+}
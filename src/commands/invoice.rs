@@ -1,102 +1,518 @@
-use super::{render_id_template, ListAble, CRUD};
+use super::{natural_cmp, next_invoice_id, select_record, select_records, set, Selectable, CRUD};
 use crate::{
-    cli::InvoiceCommand,
-    commands::edit_object_in_temp_file,
+    cli::{InvoiceCommand, InvoiceFields, InvoiceFilter, InvoiceSort, InvoiceStatus},
+    commands::{edit_object_in_temp_file, Unchanged},
     database::{
-        models::{Business, Config, Customer, Invoice, BUSINESS_PRIMARY_KEY, CONFIG_PRIMARY_KEY},
-        FactureDatabase, YamlAble,
+        chain,
+        models::{Business, Customer, Invoice, Item, PaymentStatus},
+        FactureDatabase, Transaction, YamlAble,
     },
     template::{template, Template},
-    ui::prompt,
+    ui::{self, output},
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, Local};
+use std::{
+    fs,
+    path::{Path, PathBuf},
 };
-use anyhow::Result;
 
 impl YamlAble for Invoice {}
-impl ListAble for Invoice {}
+
+impl Selectable for Invoice {
+    /// Like `R2025-001 · ACME GmbH · 2025-03-01 · 1.190,00 € · open` with the amount including
+    /// VAT
+    fn labels(database: &FactureDatabase, invoices: &[Self]) -> Result<Vec<String>> {
+        let customers: Vec<Customer> = database.read_all()?;
+        let businesses: Vec<Business> = database.read_all()?;
+        let today = Local::now().date_naive();
+        Ok(invoices
+            .iter()
+            .map(|invoice| {
+                let customer = customers
+                    .iter()
+                    .find(|customer| customer.uuid == invoice.customer)
+                    .map_or_else(|| "n/a".to_owned(), Customer::display_name);
+                let amount = businesses
+                    .iter()
+                    .find(|business| business.profile == invoice.profile)
+                    .map_or(invoice.amount(), |business| {
+                        business.gross(invoice.amount())
+                    });
+                format!(
+                    "{} · {customer} · {} · {} · {}",
+                    invoice.id,
+                    invoice.issuing_date,
+                    ui::format_amount(amount),
+                    invoice.payment_status(today)
+                )
+            })
+            .collect())
+    }
+}
 
 impl CRUD for Invoice {
     fn create(database: &FactureDatabase, invoice: &Self) -> Result<()> {
-        let customers: Vec<Customer> = database.read_all()?;
-        let mut customer =
-            prompt::select(&format!("Choose an customer to add an invoice"), customers)?;
-        let mut invoice = invoice.clone();
-        invoice.customer = customer.uuid.clone();
-        let invoice = edit_object_in_temp_file(&invoice)?;
-        database.create(invoice.clone())?;
-        let old_customer = customer.clone();
-        customer.add_invoice(&invoice.uuid);
-        database.update(&old_customer.uuid, customer)?;
+        edit_object_in_temp_file(invoice, Unchanged::Save, |edited| {
+            // The id in the editor is a preview of the counter
+            let mut edited = edited.clone();
+            if edited.id == invoice.id {
+                edited.id.clear();
+            }
+            Self::insert(database, &edited)
+        })
+    }
+
+    /// Creates the invoice and adds it to its customer. An empty id is taken from the counter in
+    /// the same transaction, so no other write can take the same number in between
+    fn insert(database: &FactureDatabase, invoice: &Self) -> Result<()> {
+        let invoice = database.transaction(|transaction| {
+            let mut config = transaction.config()?;
+            let mut invoice = invoice.clone();
+            if invoice.id.is_empty() {
+                invoice.id = next_invoice_id(&config)?;
+            }
+            transaction.create(invoice.clone())?;
+            let mut customer = transaction.read::<Customer>(&invoice.customer)?;
+            customer.add_invoice(&invoice.uuid);
+            transaction.update(&invoice.customer, customer)?;
+            config.invoice_counter += 1;
+            transaction.update(transaction.profile(), config)?;
+            Ok(invoice)
+        })?;
+        let invoice_yaml = invoice.to_yaml()?;
+        println!("\n{invoice_yaml}");
+        Ok(())
+    }
+
+    /// Updates the invoice and moves it to its new customer if the customer changed
+    fn update(database: &FactureDatabase, invoice: &Self, key: &str) -> Result<()> {
+        database.transaction(|transaction| {
+            let before: Invoice = transaction.read(key)?;
+            if before.customer != invoice.customer {
+                let mut customer: Customer = transaction.read(&before.customer)?;
+                customer.remove_invoice(key);
+                transaction.update(&before.customer, customer)?;
+                let mut customer: Customer = transaction.read(&invoice.customer)?;
+                customer.add_invoice(key);
+                transaction.update(&invoice.customer, customer)?;
+            }
+            transaction.update(key, invoice.clone())
+        })?;
         let invoice_yaml = invoice.to_yaml()?;
         println!("\n{invoice_yaml}");
         Ok(())
     }
 
     fn remove(database: &FactureDatabase, key: &str) -> Result<()> {
-        let invoice = database.read::<Invoice>(key)?;
-        let mut customer = database.read::<Customer>(&invoice.customer)?;
-        customer.remove_invoice(&invoice.uuid);
-        database.delete::<Invoice>(&invoice.uuid)?;
-        database.update(&customer.uuid, customer.clone())?;
-        println!("Invoice removed");
+        let invoice = database.transaction(|transaction| {
+            let mut invoice = transaction.read::<Invoice>(key)?;
+            invoice.deleted_at = Some(Local::now().naive_local());
+            transaction.update(key, invoice.clone())?;
+            Ok(invoice)
+        })?;
+        println!(
+            "Invoice moved to the trash. Restore it with `facture trash restore {}`",
+            invoice.id
+        );
         Ok(())
     }
 }
 
-pub fn handle_invoice_command(command: &InvoiceCommand, database: FactureDatabase) -> Result<()> {
+pub fn handle_invoice_command(
+    command: &InvoiceCommand,
+    database: FactureDatabase,
+    invoice_folder: &Path,
+) -> Result<()> {
     let name = "invoice";
     match command {
-        InvoiceCommand::List => Invoice::list(database)?,
-        InvoiceCommand::Add => {
-            let mut config = database.read::<Config>(CONFIG_PRIMARY_KEY)?;
-            let invoice_id = render_id_template(
-                &config.invoice_template,
-                &format!("{:03}", config.invoice_counter),
-            )?;
-            let invoice = Invoice::new_with_uuid(invoice_id);
-            Invoice::create(&database, &invoice)?;
-            config.invoice_counter += 1;
-            database.update(CONFIG_PRIMARY_KEY, config)?;
+        InvoiceCommand::List {
+            filter,
+            sort,
+            limit,
+        } => list_invoices(&database, filter, *sort, *limit)?,
+        InvoiceCommand::Add { fields, input } => {
+            let invoice = Invoice::new_with_uuid(String::new(), database.profile());
+            let mut invoice = input.read(&invoice)?;
+            invoice.customer = match (&fields.customer, invoice.customer.as_str()) {
+                (Some(customer), _) => resolve_customer(&database, customer)?,
+                (None, "") => {
+                    let customers: Vec<Customer> = database.read_visible()?;
+                    Customer::choose(&database, "Choose a customer to add an invoice", customers)?
+                        .uuid
+                }
+                (None, customer) => resolve_customer(&database, customer)?,
+            };
+            if fields.is_empty() && input.is_empty() {
+                invoice.id = next_invoice_id(&database.config()?)?;
+                Invoice::create(&database, &invoice)?;
+            } else {
+                fields.apply(&mut invoice);
+                Invoice::insert(&database, &invoice)?;
+            }
         }
-        InvoiceCommand::Remove => {
-            let invoices: Vec<Invoice> = database.read_all()?;
-            if invoices.is_empty() {
-                println!("No invoices created yet");
+        InvoiceCommand::Remove { id } => {
+            let message = format!("Select a {name} to remove");
+            let Some(invoice) = select_record::<Invoice>(&database, id.as_deref(), &message)?
+            else {
                 return Ok(());
-            }
-            let invoice = prompt::select(&format!("Select a {name} to remove"), invoices)?;
+            };
             Invoice::remove(&database, &invoice.uuid)?;
         }
-        InvoiceCommand::Edit => {
-            let invoices: Vec<Invoice> = database.read_all()?;
-            if invoices.is_empty() {
-                println!("No invoices created yet");
+        InvoiceCommand::Edit { id, fields, input } => {
+            let message = format!("Select a {name} to edit");
+            let Some(invoice) = select_record::<Invoice>(&database, id.as_deref(), &message)?
+            else {
                 return Ok(());
+            };
+            if fields.is_empty() && input.is_empty() {
+                Invoice::edit(&database, &invoice, &invoice.uuid)?;
+            } else {
+                // The key stays the one of the selected invoice even if the input changes it
+                let key = invoice.uuid.clone();
+                let mut invoice = input.read(&invoice)?;
+                let customer = fields.customer.as_ref().unwrap_or(&invoice.customer);
+                invoice.customer = resolve_customer(&database, customer)?;
+                fields.apply(&mut invoice);
+                Invoice::update(&database, &invoice, &key)?;
             }
-
-            let invoice = prompt::select(&format!("Select a {name} to edit"), invoices)?;
-            Invoice::edit(&database, &invoice, &invoice.uuid)?;
         }
-        InvoiceCommand::Show => {
-            let invoices: Vec<Invoice> = database.read_all()?;
-            if invoices.is_empty() {
-                println!("No invoices created yet");
-                return Ok(());
+        InvoiceCommand::Show { id, output } => {
+            let message = format!("Select a {name} to show");
+            if let Some(invoice) = select_record::<Invoice>(&database, id.as_deref(), &message)? {
+                Invoice::show(&invoice, output)?;
             }
-            let invoice = prompt::select(&format!("Select a {name} to show"), invoices)?;
-            Invoice::show(&invoice)?;
         }
-        InvoiceCommand::Render => {
-            let business: Business = database.read(BUSINESS_PRIMARY_KEY)?;
-            let invoices: Vec<Invoice> = database.read_all()?;
-            if invoices.is_empty() {
-                println!("No invoices created yet");
-                return Ok(());
+        InvoiceCommand::Render { ids } => {
+            let message = format!("Select the {name}s to render");
+            let invoices = select_records::<Invoice>(&database, ids, &message)?;
+            // Checked upfront so a batch is not left half rendered
+            if let Some(invoice) = invoices.iter().find(|invoice| invoice.issuance.is_some()) {
+                bail!(
+                    "Invoice {} was issued, rendering it again would change its pdf",
+                    invoice.id
+                );
+            }
+            for invoice in invoices {
+                database
+                    .read_transaction(|transaction| render(transaction, invoice, invoice_folder))?;
+            }
+        }
+        InvoiceCommand::Issue { id } => {
+            let invoice: Invoice = match id {
+                Some(id) => database.read_by_id(id)?,
+                None => {
+                    let invoices: Vec<Invoice> = database
+                        .read_visible::<Invoice>()?
+                        .into_iter()
+                        .filter(|invoice| invoice.issuance.is_none())
+                        .collect();
+                    if invoices.is_empty() {
+                        println!("No unissued invoices");
+                        return Ok(());
+                    }
+                    Invoice::choose(&database, &format!("Select a {name} to issue"), invoices)?
+                }
+            };
+            if invoice.issuance.is_some() {
+                bail!("Invoice {} was already issued", invoice.id);
+            }
+            // Rendered in the issuing transaction, so a failure leaves neither a sequence number
+            // nor a pdf behind
+            let mut rendered = None;
+            let issued = database.transaction(|transaction| {
+                let uuid = invoice.uuid.clone();
+                let path = render(transaction, invoice, invoice_folder)?;
+                let pdf = fs::read(&path).map_err(|error| anyhow!("{}: {error}", path.display()));
+                rendered = Some(path);
+                chain::issue(transaction, &uuid, &pdf?)
+            });
+            let invoice = match issued {
+                Ok(invoice) => invoice,
+                Err(error) => {
+                    if let Some(path) = rendered {
+                        // The error of issuing is the one worth reporting
+                        let _ = fs::remove_file(path);
+                    }
+                    return Err(error);
+                }
+            };
+            if let Some(issuance) = invoice.issuance {
+                println!(
+                    "Issued {} as number {} of profile {}",
+                    invoice.id, issuance.sequence, invoice.profile
+                );
+                println!("Hash {}", issuance.hash);
             }
-            let invoice = prompt::select(&format!("Select a {name} to render"), invoices)?;
-            let customer: Customer = database.read(&invoice.customer)?;
-            let template = Template::<template::Invoice>::new(business, customer, invoice)?;
-            template.render()?;
         }
     }
     Ok(())
 }
+
+/// Prints the visible invoices that match the filter in the requested order
+fn list_invoices(
+    database: &FactureDatabase,
+    filter: &InvoiceFilter,
+    sort: InvoiceSort,
+    limit: Option<usize>,
+) -> Result<()> {
+    let invoices = filter_invoices(database, filter, sort, limit)?;
+    output::print_records(database, invoices, "No invoices found")
+}
+
+/// Returns the visible invoices that match the filter in the requested order
+fn filter_invoices(
+    database: &FactureDatabase,
+    filter: &InvoiceFilter,
+    sort: InvoiceSort,
+    limit: Option<usize>,
+) -> Result<Vec<Invoice>> {
+    let customers: Vec<Customer> = database.read_all()?;
+    let customer_name = |uuid: &str| {
+        customers
+            .iter()
+            .find(|customer| customer.uuid == uuid)
+            .map(Customer::display_name)
+            .unwrap_or_default()
+    };
+    let customer = filter
+        .customer
+        .as_deref()
+        .map(|customer| resolve_customer(database, customer))
+        .transpose()?;
+    let today = Local::now().date_naive();
+
+    let mut invoices: Vec<Invoice> = database.read_visible()?;
+    invoices.retain(|invoice| {
+        customer
+            .as_ref()
+            .is_none_or(|uuid| invoice.customer == *uuid)
+            && filter.from.is_none_or(|from| invoice.issuing_date >= from)
+            && filter.to.is_none_or(|to| invoice.issuing_date <= to)
+            && filter
+                .year
+                .is_none_or(|year| invoice.issuing_date.year() == year)
+            && filter.status.is_none_or(|status| {
+                let payment_status = invoice.payment_status(today);
+                match status {
+                    InvoiceStatus::Draft => payment_status == PaymentStatus::Draft,
+                    InvoiceStatus::Open => payment_status == PaymentStatus::Open,
+                    InvoiceStatus::Overdue => payment_status == PaymentStatus::Overdue,
+                    InvoiceStatus::Paid => payment_status == PaymentStatus::Paid,
+                }
+            })
+            && filter
+                .min_amount
+                .is_none_or(|min_amount| invoice.amount() >= min_amount)
+    });
+    match sort {
+        InvoiceSort::Date => invoices.sort_by_key(|invoice| invoice.issuing_date),
+        InvoiceSort::Number => invoices.sort_by(|a, b| natural_cmp(&a.id, &b.id)),
+        InvoiceSort::Amount => invoices.sort_by(|a, b| a.amount().total_cmp(&b.amount())),
+        InvoiceSort::Customer => {
+            invoices.sort_by_cached_key(|invoice| customer_name(&invoice.customer))
+        }
+    }
+    if let Some(limit) = limit {
+        invoices.truncate(limit);
+    }
+    Ok(invoices)
+}
+
+/// Returns the uuid of a customer of the selected profile given by its uuid or by its id like
+/// the exports reference it
+fn resolve_customer(database: &FactureDatabase, customer: &str) -> Result<String> {
+    Ok(database.read_by_key_or_id::<Customer>(customer)?.uuid)
+}
+
+/// Renders the invoice with the business of its profile and returns the path of the pdf
+fn render(transaction: &Transaction, invoice: Invoice, invoice_folder: &Path) -> Result<PathBuf> {
+    let business: Business = transaction.read(&invoice.profile)?;
+    let customer: Customer = transaction.read(&invoice.customer)?;
+    let template = Template::<template::Invoice>::new(invoice_folder, business, customer, invoice)?;
+    template.render()
+}
+
+impl InvoiceFields {
+    fn is_empty(&self) -> bool {
+        self.id.is_none()
+            && self.customer.is_none()
+            && self.issuing_date.is_none()
+            && self.delivery_date.is_none()
+            && self.due_days.is_none()
+            && self.payment_date.is_none()
+            && self.items.is_empty()
+    }
+
+    /// Applies all fields but the customer, which has to be resolved by the caller
+    fn apply(&self, invoice: &mut Invoice) {
+        set(&mut invoice.id, &self.id);
+        set(&mut invoice.issuing_date, &self.issuing_date);
+        set(&mut invoice.delivery_date, &self.delivery_date);
+        set(&mut invoice.due_days, &self.due_days);
+        if let Some(payment_date) = self.payment_date {
+            invoice.payment_date = Some(payment_date);
+        }
+        if !self.items.is_empty() {
+            invoice.items = self
+                .items
+                .iter()
+                .map(|item| Item {
+                    description: item.description.clone(),
+                    price: item.price,
+                    quantity: item.quantity,
+                })
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::{BackendKind, InvoiceStatus},
+        database::{
+            models::{Issuance, DEFAULT_PROFILE},
+            testing,
+        },
+    };
+    use chrono::{Days, NaiveDate};
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn invoice(id: &str, customer: &Customer, issuing_date: NaiveDate, price: f32) -> Invoice {
+        Invoice {
+            customer: customer.uuid.clone(),
+            issuing_date,
+            delivery_date: issuing_date,
+            items: vec![Item {
+                description: "Work".to_owned(),
+                price,
+                quantity: None,
+            }],
+            ..Invoice::new_with_uuid(id.to_owned(), DEFAULT_PROFILE)
+        }
+    }
+
+    fn issued(invoice: Invoice) -> Invoice {
+        Invoice {
+            issuance: Some(Issuance {
+                issued_at: invoice.issuing_date.and_hms_opt(12, 0, 0).unwrap(),
+                sequence: 1,
+                previous_hash: None,
+                pdf_hash: String::new(),
+                hash: String::new(),
+            }),
+            ..invoice
+        }
+    }
+
+    /// Two customers with a draft, an overdue, an open and a paid invoice between them
+    fn invoices() -> (tempfile::TempDir, FactureDatabase) {
+        let (folder, database) = testing::database(BackendKind::Native);
+        let today = Local::now().date_naive();
+        let acme = Customer {
+            organization: "ACME".to_owned(),
+            ..Customer::new_with_uuid("K0001".to_owned(), None)
+        };
+        let beta = Customer {
+            organization: "Beta".to_owned(),
+            ..Customer::new_with_uuid("K0002".to_owned(), None)
+        };
+        let invoices = [
+            invoice("INV-10", &acme, date(2024, 3, 1), 50.0),
+            issued(invoice("INV-9", &beta, date(2024, 11, 1), 200.0)),
+            issued(invoice("INV-100", &acme, today, 120.0)),
+            Invoice {
+                payment_date: Some(today),
+                ..issued(invoice("INV-2", &beta, today - Days::new(40), 80.0))
+            },
+        ];
+        database
+            .transaction(|transaction| {
+                transaction.create(acme.clone())?;
+                transaction.create(beta.clone())?;
+                for invoice in &invoices {
+                    transaction.create(invoice.clone())?;
+                }
+                Ok(())
+            })
+            .unwrap();
+        (folder, database)
+    }
+
+    fn ids(
+        database: &FactureDatabase,
+        filter: InvoiceFilter,
+        sort: InvoiceSort,
+        limit: Option<usize>,
+    ) -> Vec<String> {
+        filter_invoices(database, &filter, sort, limit)
+            .unwrap()
+            .into_iter()
+            .map(|invoice| invoice.id)
+            .collect()
+    }
+
+    #[test]
+    fn sorts_invoices() {
+        let (_folder, database) = invoices();
+        let all = InvoiceFilter::default;
+        assert_eq!(
+            ids(&database, all(), InvoiceSort::Number, None),
+            ["INV-2", "INV-9", "INV-10", "INV-100"]
+        );
+        assert_eq!(
+            ids(&database, all(), InvoiceSort::Amount, None),
+            ["INV-10", "INV-2", "INV-100", "INV-9"]
+        );
+        assert_eq!(
+            ids(&database, all(), InvoiceSort::Date, Some(2)),
+            ["INV-10", "INV-9"]
+        );
+        assert_eq!(
+            ids(&database, all(), InvoiceSort::Customer, None),
+            ["INV-10", "INV-100", "INV-9", "INV-2"]
+        );
+    }
+
+    #[test]
+    fn filters_invoices() {
+        let (_folder, database) = invoices();
+        let filtered = |filter| ids(&database, filter, InvoiceSort::Number, None);
+        let status = |status| InvoiceFilter {
+            status: Some(status),
+            ..Default::default()
+        };
+        assert_eq!(filtered(status(InvoiceStatus::Draft)), ["INV-10"]);
+        assert_eq!(filtered(status(InvoiceStatus::Overdue)), ["INV-9"]);
+        assert_eq!(filtered(status(InvoiceStatus::Open)), ["INV-100"]);
+        assert_eq!(filtered(status(InvoiceStatus::Paid)), ["INV-2"]);
+        assert_eq!(
+            filtered(InvoiceFilter {
+                customer: Some("K0001".to_owned()),
+                ..Default::default()
+            }),
+            ["INV-10", "INV-100"]
+        );
+        assert_eq!(
+            filtered(InvoiceFilter {
+                from: Some(date(2024, 1, 1)),
+                to: Some(date(2024, 12, 31)),
+                min_amount: Some(100.0),
+                ..Default::default()
+            }),
+            ["INV-9"]
+        );
+        assert_eq!(
+            filtered(InvoiceFilter {
+                year: Some(2024),
+                ..Default::default()
+            }),
+            ["INV-9", "INV-10"]
+        );
+    }
+}
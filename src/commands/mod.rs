@@ -1,57 +1,79 @@
+pub mod backup;
 pub mod business;
+pub mod completions;
 pub mod customer;
+pub mod database;
+pub mod exchange;
+pub mod history;
 pub mod invoice;
+pub mod profile;
+pub mod search;
+pub mod trash;
+pub mod undo;
 
 use crate::{
-    cli::ConfigCommand,
+    cli::{AddressFields, ConfigCommand, ConfigFields, InputArgs, OutputArgs},
     database::{
-        models::{Business, Config, BUSINESS_PRIMARY_KEY, CONFIG_PRIMARY_KEY},
+        backend::Record,
+        models::{Address, Business, Config, Identifiable, Scoped, Trashable},
         FactureDatabase, YamlAble,
     },
-    ui::{self, TableAble},
+    ui::{output, prompt},
 };
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use chrono::{Datelike, Local};
 use minijinja::{context, Environment};
-use native_db::ToInput;
-use std::{env, fs, process::Command};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{cmp::Ordering, env, fs, io, path::Path, process::Command};
 use tempfile::Builder;
 
-pub trait ListAble: TableAble + ToInput {
-    fn list(database: FactureDatabase) -> Result<()> {
-        let objects: Vec<Self> = database.read_all()?;
-        if objects.is_empty() {
-            println!("Nothing created yet.");
-            return Ok(());
-        }
-        let header = Self::header();
-        let rows = objects.into_iter().map(|object| object.row()).collect();
-        let table = ui::table(header, rows);
-        println!("{table}");
-        Ok(())
+#[allow(clippy::upper_case_acronyms)]
+pub trait CRUD: Clone + YamlAble + Record {
+    /// Opens the object in $EDITOR and creates it
+    fn create(database: &FactureDatabase, object: &Self) -> Result<()> {
+        edit_object_in_temp_file(object, Unchanged::Save, |new_object| {
+            Self::insert(database, new_object)
+        })
     }
-}
 
-pub trait CRUD: Clone + YamlAble + ToInput {
-    fn create(database: &FactureDatabase, object: &Self) -> Result<()> {
-        let new_object = edit_object_in_temp_file(object)?;
-        database.create(new_object.clone())?;
-        let new_object_yaml = new_object.to_yaml()?;
-        println!("\n{new_object_yaml}");
+    /// Creates the object as is
+    fn insert(database: &FactureDatabase, object: &Self) -> Result<()> {
+        database.create(object.clone())?;
+        let object_yaml = object.to_yaml()?;
+        println!("\n{object_yaml}");
         Ok(())
     }
 
-    fn show(object: &Self) -> Result<()> {
-        let object_yaml = object.to_yaml()?;
-        println!("{object_yaml}");
+    /// Prints the object in the format of `--output` or writes it to the file of `--to-file`
+    fn show(object: &Self, output: &OutputArgs) -> Result<()> {
+        let Some(path) = &output.to_file else {
+            return output::print_record(object);
+        };
+        let content = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            serde_json::to_string_pretty(object)? + "\n"
+        } else {
+            object.to_yaml()?
+        };
+        fs::write(path, content).map_err(|error| anyhow!("{}: {error}", path.display()))?;
         Ok(())
     }
 
+    /// Opens the object in $EDITOR and updates it
     fn edit(database: &FactureDatabase, object: &Self, key: &str) -> Result<()> {
-        let new_object = edit_object_in_temp_file(object)?;
-        database.update(key, new_object.clone())?;
-        let new_object_yaml = new_object.to_yaml()?;
-        println!("\n{new_object_yaml}");
+        edit_object_in_temp_file(object, Unchanged::Skip, |new_object| {
+            Self::update(database, new_object, key)
+        })
+    }
+
+    /// Updates the object as is
+    fn update(database: &FactureDatabase, object: &Self, key: &str) -> Result<()> {
+        database.update(key, object.clone())?;
+        let object_yaml = object.to_yaml()?;
+        println!("\n{object_yaml}");
         Ok(())
     }
 
@@ -61,29 +83,280 @@ pub trait CRUD: Clone + YamlAble + ToInput {
     }
 }
 
-fn edit_object_in_temp_file<T: YamlAble>(object: &T) -> Result<T> {
-    let object_yaml = object.to_yaml()?;
+/// Records that can be picked in selection prompts
+pub trait Selectable: Identifiable + Scoped + Trashable {
+    /// Labels the records are shown and fuzzy searched by, in the order of the records
+    fn labels(database: &FactureDatabase, records: &[Self]) -> Result<Vec<String>>;
+
+    /// Asks for one of the records
+    fn choose(database: &FactureDatabase, message: &str, records: Vec<Self>) -> Result<Self> {
+        let choices = choices(database, records)?;
+        prompt::select(message, choices).map(|choice| choice.value)
+    }
+
+    /// Asks for any number of the records
+    fn choose_many(
+        database: &FactureDatabase,
+        message: &str,
+        records: Vec<Self>,
+    ) -> Result<Vec<Self>> {
+        let choices = choices(database, records)?;
+        let chosen = prompt::multi_select(message, choices)?;
+        Ok(chosen.into_iter().map(|choice| choice.value).collect())
+    }
+}
+
+fn choices<T: Selectable>(
+    database: &FactureDatabase,
+    records: Vec<T>,
+) -> Result<Vec<prompt::Choice<T>>> {
+    let labels = T::labels(database, &records)?;
+    Ok(labels
+        .into_iter()
+        .zip(records)
+        .map(|(label, value)| prompt::Choice { label, value })
+        .collect())
+}
+
+/// Reads the record of the selected profile with the id or prompts for one. Returns `None` if
+/// there is nothing to select
+fn select_record<T: Selectable>(
+    database: &FactureDatabase,
+    id: Option<&str>,
+    message: &str,
+) -> Result<Option<T>> {
+    if let Some(id) = id {
+        return database.read_by_id(id).map(Some);
+    }
+    let records: Vec<T> = database.read_visible()?;
+    if records.is_empty() {
+        println!("No {}s created yet", T::KIND);
+        return Ok(None);
+    }
+    T::choose(database, message, records).map(Some)
+}
+
+/// Reads the records of the selected profile with the ids or prompts for any number of them
+fn select_records<T: Selectable>(
+    database: &FactureDatabase,
+    ids: &[String],
+    message: &str,
+) -> Result<Vec<T>> {
+    if !ids.is_empty() {
+        return ids.iter().map(|id| database.read_by_id(id)).collect();
+    }
+    let records: Vec<T> = database.read_visible()?;
+    if records.is_empty() {
+        println!("No {}s created yet", T::KIND);
+        return Ok(Vec::new());
+    }
+    T::choose_many(database, message, records)
+}
+
+/// Compares ids with the numbers in them by value, so INV-9 comes before INV-10
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(a_first), Some(b_first)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if a_first.is_ascii_digit() && b_first.is_ascii_digit() {
+            let a_end = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let b_end = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            let (a_number, b_number) = (
+                a[..a_end].trim_start_matches('0'),
+                b[..b_end].trim_start_matches('0'),
+            );
+            // Without leading zeros the longer number is the larger one. Equal numbers with
+            // more leading zeros come last so the order stays total
+            let ordering = a_number
+                .len()
+                .cmp(&b_number.len())
+                .then_with(|| a_number.cmp(b_number))
+                .then_with(|| a_end.cmp(&b_end));
+            if ordering.is_ne() {
+                return ordering;
+            }
+            (a, b) = (&a[a_end..], &b[b_end..]);
+        } else {
+            let ordering = a_first.cmp(&b_first);
+            if ordering.is_ne() {
+                return ordering;
+            }
+            (a, b) = (&a[a_first.len_utf8()..], &b[b_first.len_utf8()..]);
+        }
+    }
+}
+
+/// Editors used if neither $EDITOR nor $VISUAL is set, in this order
+const FALLBACK_EDITORS: [&str; 2] = ["vi", "nano"];
+
+/// Returns the editor command from $EDITOR or $VISUAL or the first installed fallback editor
+fn editor() -> Result<String> {
+    for variable in ["EDITOR", "VISUAL"] {
+        if let Some(editor) = env::var(variable)
+            .ok()
+            .filter(|editor| !editor.trim().is_empty())
+        {
+            return Ok(editor);
+        }
+    }
+    let path = env::var_os("PATH").unwrap_or_default();
+    FALLBACK_EDITORS
+        .into_iter()
+        .find(|editor| env::split_paths(&path).any(|folder| folder.join(editor).is_file()))
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("No editor found. Set $EDITOR or pass the record with --from-file"))
+}
+
+/// What the editor does with a file that is saved as it was opened
+enum Unchanged {
+    /// Saves the object as proposed, like the defaults of a new record
+    Save,
+    /// Reports that there are no changes and saves nothing
+    Skip,
+}
+
+/// Opens the object in the editor and saves it. The editor is opened again with the error as
+/// comments at the top of the file until the object parses and saves. Saving an empty file or
+/// the same failing content again aborts with an error
+fn edit_object_in_temp_file<T: YamlAble>(
+    object: &T,
+    unchanged: Unchanged,
+    save: impl Fn(&T) -> Result<()>,
+) -> Result<()> {
+    if !prompt::is_interactive() {
+        bail!("Editing needs a terminal. Pass the record with --from-file or --from-stdin or the fields as flags, see --help");
+    }
+    let editor = editor()?;
+    edit_until_saved(object, unchanged, save, |path| {
+        // Editors like `code --wait` come with arguments
+        let mut command = editor.split_whitespace();
+        let program = command.next().unwrap_or_default();
+        let status = Command::new(program)
+            .args(command)
+            .arg(path)
+            .status()
+            .map_err(|error| anyhow!("Failed to run {editor}: {error}"))?;
+        if !status.success() {
+            bail!("{editor} failed with {status}. Nothing was saved");
+        }
+        Ok(())
+    })
+}
+
+/// The editing loop of [`edit_object_in_temp_file`] with the editor run by `open`
+fn edit_until_saved<T: YamlAble>(
+    object: &T,
+    unchanged: Unchanged,
+    save: impl Fn(&T) -> Result<()>,
+    mut open: impl FnMut(&Path) -> Result<()>,
+) -> Result<()> {
+    let original = object.to_yaml()?;
     let temp_file = Builder::new()
         .prefix("facture_")
         .suffix(".yaml")
         .tempfile()?;
-    fs::write(temp_file.path(), object_yaml)?;
-    let editor = env::var("EDITOR")?;
-    Command::new(editor).arg(temp_file.path()).status()?;
-    let object_yaml = fs::read_to_string(temp_file.path())?;
-    let new_object = T::from_yaml(&object_yaml)?;
-    Ok(new_object)
+    let mut header = String::new();
+    let mut body = original.clone();
+    loop {
+        fs::write(temp_file.path(), format!("{header}{body}"))?;
+        open(temp_file.path())?;
+        let content = fs::read_to_string(temp_file.path())?;
+        let edited = content
+            .strip_prefix(header.as_str())
+            .unwrap_or(&content)
+            .to_owned();
+
+        if edited
+            .lines()
+            .all(|line| line.trim().is_empty() || line.trim_start().starts_with('#'))
+        {
+            bail!("Aborted. Nothing was saved");
+        }
+        // Saving the same error again would open the editor forever
+        if !header.is_empty() && edited == body {
+            bail!("Aborted. Nothing was saved");
+        }
+        if edited == original && matches!(unchanged, Unchanged::Skip) {
+            println!("No changes");
+            return Ok(());
+        }
+
+        let saved = if edited == original {
+            save(object)
+        } else {
+            match serde_yml::from_str::<T>(&edited) {
+                Ok(new_object) => save(&new_object),
+                Err(error) => match error.location() {
+                    // The line is moved down by the comments above the record
+                    Some(location) => Err(anyhow!(
+                        "line {}, column {}: {}",
+                        location.line() + ERROR_HEADER_LINES,
+                        location.column(),
+                        strip_locations(&error.to_string())
+                    )),
+                    None => Err(error.into()),
+                },
+            }
+        };
+        let Err(error) = saved else {
+            return Ok(());
+        };
+        eprintln!("{error}");
+        header = format!(
+            "# Error: {}\n# Fix it and save to try again, or save an empty file to abort\n",
+            error.to_string().replace('\n', " ")
+        );
+        body = edited;
+    }
+}
+
+/// Number of comment lines an error adds at the top of the edited file
+const ERROR_HEADER_LINES: usize = 2;
+
+/// Removes the `at line L column C` of YAML errors, which counts without the error comments
+fn strip_locations(message: &str) -> String {
+    let mut rest = message;
+    let mut stripped = String::new();
+    while let Some((before, after)) = rest.split_once(" at line ") {
+        stripped.push_str(before);
+        let location_end = after
+            .split_once(" column ")
+            .map(|(line, column)| {
+                (
+                    line,
+                    column.trim_start_matches(|c: char| c.is_ascii_digit()),
+                )
+            })
+            .filter(|(line, _)| line.chars().all(|c| c.is_ascii_digit()));
+        rest = match location_end {
+            Some((_, remaining)) => remaining,
+            None => {
+                stripped.push_str(" at line ");
+                after
+            }
+        };
+    }
+    stripped.push_str(rest);
+    stripped
 }
 
 pub fn handle_config_command(command: &ConfigCommand, database: FactureDatabase) -> Result<()> {
     match command {
-        ConfigCommand::Edit => {
-            let config = database.read::<Config>(CONFIG_PRIMARY_KEY)?;
-            Config::edit(&database, &config, CONFIG_PRIMARY_KEY)?;
+        ConfigCommand::Edit { fields, input } => {
+            let config = database.config()?;
+            if fields.is_empty() && input.is_empty() {
+                Config::edit(&database, &config, database.profile())?;
+            } else {
+                let mut config = input.read(&config)?;
+                fields.apply(&mut config);
+                Config::update(&database, &config, database.profile())?;
+            }
         }
-        ConfigCommand::Show => {
-            let config = database.read::<Config>(CONFIG_PRIMARY_KEY)?;
-            Config::show(&config)?;
+        ConfigCommand::Show { output } => {
+            let config = database.config()?;
+            Config::show(&config, output)?;
         }
     }
 
@@ -91,22 +364,34 @@ pub fn handle_config_command(command: &ConfigCommand, database: FactureDatabase)
 }
 
 pub fn handle_init_command(database: FactureDatabase) -> Result<()> {
+    println!("Using database {}", database.path().display());
+    let profile = database.profile();
+    println!("Using profile {profile}");
     // Init config
-    if database.exists::<Config>(CONFIG_PRIMARY_KEY)? {
+    if database.exists::<Config>(profile)? {
         println!("Config already exists... skipping!");
     } else {
         println!("Creating config...");
-        let config = Config::default();
-        Config::create(&database, &config)?;
+        let config = Config::new(profile);
+        // Scripts get the defaults and set the fields with `facture config edit` afterwards
+        if prompt::is_interactive() {
+            Config::create(&database, &config)?;
+        } else {
+            Config::insert(&database, &config)?;
+        }
     };
 
     // Init business
-    if database.exists::<Business>(BUSINESS_PRIMARY_KEY)? {
+    if database.exists::<Business>(profile)? {
         println!("Business already exists... skipping!");
     } else {
         println!("Creating Business...");
-        let business = Business::default();
-        Business::create(&database, &business)?;
+        let business = Business::new(profile);
+        if prompt::is_interactive() {
+            Business::create(&database, &business)?;
+        } else {
+            Business::insert(&database, &business)?;
+        }
     }
 
     Ok(())
@@ -119,3 +404,229 @@ pub fn render_id_template(template: &str, counter: &str) -> Result<String> {
         context!(year => date.year().to_string(), month => date.month().to_string(), day => date.month().to_string(), counter => counter))?;
     Ok(id)
 }
+
+/// Renders the id of the next customer from the counter of the config
+pub fn next_customer_id(config: &Config) -> Result<String> {
+    render_id_template(
+        &config.customer_template,
+        &format!("{:04}", config.customer_counter),
+    )
+}
+
+/// Renders the id of the next invoice from the counter of the config
+pub fn next_invoice_id(config: &Config) -> Result<String> {
+    render_id_template(
+        &config.invoice_template,
+        &format!("{:03}", config.invoice_counter),
+    )
+}
+
+impl ConfigFields {
+    fn is_empty(&self) -> bool {
+        self.customer_template.is_none()
+            && self.customer_counter.is_none()
+            && self.invoice_template.is_none()
+            && self.invoice_counter.is_none()
+    }
+
+    fn apply(&self, config: &mut Config) {
+        set(&mut config.customer_template, &self.customer_template);
+        set(&mut config.customer_counter, &self.customer_counter);
+        set(&mut config.invoice_template, &self.invoice_template);
+        set(&mut config.invoice_counter, &self.invoice_counter);
+    }
+}
+
+impl AddressFields {
+    fn is_empty(&self) -> bool {
+        self.street.is_none()
+            && self.number.is_none()
+            && self.postal_code.is_none()
+            && self.city.is_none()
+            && self.country.is_none()
+    }
+
+    fn apply(&self, address: &mut Address) {
+        set(&mut address.street, &self.street);
+        set(&mut address.number, &self.number);
+        set(&mut address.postal_code, &self.postal_code);
+        set(&mut address.city, &self.city);
+        set(&mut address.country, &self.country);
+    }
+}
+
+/// Overwrites the field if a value was given
+fn set<T: Clone>(field: &mut T, value: &Option<T>) {
+    if let Some(value) = value {
+        *field = value.clone();
+    }
+}
+
+impl InputArgs {
+    fn is_empty(&self) -> bool {
+        self.from_file.is_none() && !self.from_stdin
+    }
+
+    /// Reads the record from the file or stdin and merges it over the object. Returns the object
+    /// as is without input
+    fn read<T: Serialize + DeserializeOwned + Clone>(&self, object: &T) -> Result<T> {
+        let (source, content) = match (&self.from_file, self.from_stdin) {
+            (Some(path), _) => (
+                path.display().to_string(),
+                fs::read_to_string(path).map_err(|error| anyhow!("{}: {error}", path.display()))?,
+            ),
+            (None, true) => ("stdin".to_owned(), io::read_to_string(io::stdin())?),
+            (None, false) => return Ok(object.clone()),
+        };
+        // JSON is tried first as YAML parsers are picky about some valid JSON like tabs
+        let patch: Value = serde_json::from_str(&content)
+            .or_else(|_| serde_yml::from_str(&content))
+            .map_err(|error| anyhow!("{source}: {error}"))?;
+        if !patch.is_object() {
+            bail!("{source}: Expected the fields of a record");
+        }
+        let mut value = serde_json::to_value(object)?;
+        merge(&mut value, patch);
+        serde_json::from_value(value).map_err(|error| anyhow!("{source}: {error}"))
+    }
+}
+
+/// Merges the patch into the value. Nested mappings are merged field by field, everything else
+/// is replaced
+fn merge(value: &mut Value, patch: Value) {
+    match (value, patch) {
+        (Value::Object(value), Value::Object(patch)) => {
+            for (key, patch) in patch {
+                match value.get_mut(&key) {
+                    Some(value) => merge(value, patch),
+                    None => {
+                        value.insert(key, patch);
+                    }
+                }
+            }
+        }
+        (value, patch) => *value = patch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::Customer;
+    use std::cell::RefCell;
+
+    type Edit = fn(&str) -> String;
+
+    /// Runs the editing loop with the edits in place of the editor. Returns the result, the
+    /// files the editor was opened with and the ids of the saved customers
+    fn edit(
+        unchanged: Unchanged,
+        fails: usize,
+        edits: &[Edit],
+    ) -> (Result<()>, Vec<String>, Vec<String>) {
+        let customer = Customer::new_with_uuid("C1".to_owned(), None);
+        let saved = RefCell::new(Vec::new());
+        let mut opened = Vec::new();
+        let mut edits = edits.iter();
+        let result = edit_until_saved(
+            &customer,
+            unchanged,
+            |customer: &Customer| {
+                saved.borrow_mut().push(customer.id.clone());
+                if saved.borrow().len() <= fails {
+                    bail!("Customer {} already exists", customer.id);
+                }
+                Ok(())
+            },
+            |path| {
+                let content = fs::read_to_string(path)?;
+                let edit = edits.next().expect("the editor was opened too often");
+                fs::write(path, edit(&content))?;
+                opened.push(content);
+                Ok(())
+            },
+        );
+        (result, opened, saved.into_inner())
+    }
+
+    fn unchanged(content: &str) -> String {
+        content.to_owned()
+    }
+
+    fn invalid(content: &str) -> String {
+        content.replacen("id: C1", "id: [C1", 1)
+    }
+
+    fn fixed(content: &str) -> String {
+        content
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| {
+                line.replacen("id: [C1", "id: C2", 1)
+                    .replacen("id: C1", "id: C2", 1)
+                    + "\n"
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compares_numbers_in_ids_by_value() {
+        let mut ids = [
+            "INV-10", "INV-9", "K0002", "INV-009", "INV-9a", "K10", "INV-", "INV-09",
+        ];
+        ids.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            ids,
+            ["INV-", "INV-9", "INV-9a", "INV-09", "INV-009", "INV-10", "K0002", "K10"]
+        );
+    }
+
+    #[test]
+    fn reopens_the_editor_with_the_error_until_the_record_saves() {
+        let (result, opened, saved) = edit(Unchanged::Skip, 0, &[invalid, fixed]);
+        result.unwrap();
+        assert_eq!(saved, ["C2"]);
+        assert_eq!(opened.len(), 2);
+        assert!(
+            opened[1].starts_with("# Error: line 4, column 5: id: invalid type"),
+            "{}",
+            opened[1]
+        );
+        assert!(opened[1].contains("id: [C1"));
+    }
+
+    #[test]
+    fn saving_the_same_error_again_aborts() {
+        let (result, opened, saved) = edit(Unchanged::Skip, 0, &[invalid, unchanged]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Aborted. Nothing was saved"
+        );
+        assert_eq!(opened.len(), 2);
+        assert!(saved.is_empty());
+
+        let (result, _, saved) = edit(Unchanged::Skip, 0, &[|_| "# Nothing\n".to_owned()]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Aborted. Nothing was saved"
+        );
+        assert!(saved.is_empty());
+    }
+
+    #[test]
+    fn unchanged_records_are_saved_or_skipped() {
+        let (result, _, saved) = edit(Unchanged::Skip, 0, &[unchanged]);
+        result.unwrap();
+        assert!(saved.is_empty());
+
+        let (result, _, saved) = edit(Unchanged::Save, 0, &[unchanged]);
+        result.unwrap();
+        assert_eq!(saved, ["C1"]);
+
+        // A failed save of the unchanged record opens the editor again with the error
+        let (result, opened, saved) = edit(Unchanged::Save, 1, &[unchanged, fixed]);
+        result.unwrap();
+        assert_eq!(saved, ["C1", "C2"]);
+        assert!(opened[1].starts_with("# Error: Customer C1 already exists\n"));
+    }
+}
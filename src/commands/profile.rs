@@ -0,0 +1,96 @@
+use crate::{
+    cli::ProfileCommand,
+    database::{
+        models::{Business, Config, Customer, Invoice},
+        FactureDatabase,
+    },
+    ui::{output, prompt},
+};
+use anyhow::{bail, Result};
+
+pub fn handle_profile_command(command: &ProfileCommand, database: FactureDatabase) -> Result<()> {
+    match command {
+        ProfileCommand::List => {
+            let (configs, businesses, customers, invoices) =
+                database.transaction(|transaction| {
+                    Ok((
+                        transaction.read_all::<Config>()?,
+                        transaction.read_all::<Business>()?,
+                        transaction.read_all::<Customer>()?,
+                        transaction.read_all::<Invoice>()?,
+                    ))
+                })?;
+            let header = ["Profile", "Business", "Customers", "Invoices", "Active"]
+                .map(str::to_owned)
+                .to_vec();
+            let rows = configs
+                .into_iter()
+                .map(|config| {
+                    let profile = config.profile;
+                    let business = businesses
+                        .iter()
+                        .find(|business| business.profile == profile)
+                        .map_or_else(|| "n/a".to_owned(), |business| business.name.clone());
+                    let customers = customers
+                        .iter()
+                        .filter(|customer| customer.profile.as_ref() == Some(&profile))
+                        .count();
+                    let invoices = invoices
+                        .iter()
+                        .filter(|invoice| invoice.profile == profile)
+                        .count();
+                    let active = if profile == database.profile() {
+                        "*".to_owned()
+                    } else {
+                        String::new()
+                    };
+                    vec![
+                        profile,
+                        business,
+                        customers.to_string(),
+                        invoices.to_string(),
+                        active,
+                    ]
+                })
+                .collect();
+            output::print_rows(header, rows, "Nothing created yet.")?;
+        }
+        ProfileCommand::Remove { name } => {
+            if !database.exists::<Config>(name)? && !database.exists::<Business>(name)? {
+                bail!("Profile {name} does not exist");
+            }
+            // Customers and invoices are never deleted together with their profile
+            let customers: Vec<Customer> = database.read_all()?;
+            let invoices: Vec<Invoice> = database.read_all()?;
+            let customers = customers
+                .iter()
+                .filter(|customer| customer.profile.as_ref() == Some(name))
+                .count();
+            let invoices = invoices
+                .iter()
+                .filter(|invoice| invoice.profile == *name)
+                .count();
+            if customers > 0 || invoices > 0 {
+                bail!("Profile {name} still has {customers} customers and {invoices} invoices");
+            }
+            let result = prompt::confirm(&format!(
+                "This will delete the business and config of profile {name}"
+            ))?;
+            if !result {
+                println!("Aborted!");
+                return Ok(());
+            }
+            database.transaction(|transaction| {
+                if transaction.exists::<Config>(name)? {
+                    transaction.delete::<Config>(name)?;
+                }
+                if transaction.exists::<Business>(name)? {
+                    transaction.delete::<Business>(name)?;
+                }
+                Ok(())
+            })?;
+            println!("Profile removed");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use crate::{
+    database::{
+        models::{Customer, Invoice},
+        FactureDatabase,
+    },
+    ui::output,
+};
+use anyhow::Result;
+
+pub fn handle_search_command(term: &str, database: FactureDatabase) -> Result<()> {
+    let term = term.to_lowercase();
+    let customers: Vec<Customer> = database.read_visible()?;
+    let invoices: Vec<Invoice> = database.read_visible()?;
+
+    let mut rows = Vec::new();
+    let mut add_matches = |kind: &str, id: &str, fields: Vec<(String, &str)>| {
+        for (field, text) in fields {
+            if text.to_lowercase().contains(&term) {
+                rows.push(vec![kind.to_owned(), id.to_owned(), field, text.to_owned()]);
+            }
+        }
+    };
+    for customer in &customers {
+        let contact = &customer.contact;
+        let address = &customer.address;
+        let fields = vec![
+            ("id".to_owned(), customer.id.as_str()),
+            ("organization".to_owned(), &customer.organization),
+            ("vat_id".to_owned(), &customer.vat_id),
+            ("name".to_owned(), &contact.name),
+            ("surname".to_owned(), &contact.surname),
+            (
+                "email".to_owned(),
+                contact.email.as_deref().unwrap_or_default(),
+            ),
+            (
+                "phone".to_owned(),
+                contact.phone.as_deref().unwrap_or_default(),
+            ),
+            ("street".to_owned(), &address.street),
+            ("postal_code".to_owned(), &address.postal_code),
+            ("city".to_owned(), &address.city),
+            ("country".to_owned(), &address.country),
+        ];
+        add_matches("customer", &customer.id, fields);
+    }
+    for invoice in &invoices {
+        let mut fields = vec![("id".to_owned(), invoice.id.as_str())];
+        fields.extend(invoice.items.iter().enumerate().map(|(index, item)| {
+            (
+                format!("items.{index}.description"),
+                item.description.as_str(),
+            )
+        }));
+        add_matches("invoice", &invoice.id, fields);
+    }
+
+    let header = ["Type", "Id", "Field", "Text"].map(str::to_owned).to_vec();
+    output::print_rows(header, rows, "Nothing found")
+}
@@ -0,0 +1,351 @@
+use crate::{
+    cli::TrashCommand,
+    database::{
+        models::{Customer, Invoice, Trashable},
+        FactureDatabase,
+    },
+    ui::{output, prompt},
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::{Local, NaiveDateTime};
+use std::fmt::Display;
+
+/// A customer or invoice in the trash
+enum Trashed {
+    Customer(Customer),
+    Invoice(Invoice),
+}
+
+impl Trashed {
+    fn id(&self) -> &str {
+        match self {
+            Trashed::Customer(customer) => &customer.id,
+            Trashed::Invoice(invoice) => &invoice.id,
+        }
+    }
+
+    fn deleted_at(&self) -> Option<NaiveDateTime> {
+        match self {
+            Trashed::Customer(customer) => customer.deleted_at,
+            Trashed::Invoice(invoice) => invoice.deleted_at,
+        }
+    }
+}
+
+impl Display for Trashed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trashed::Customer(customer) => write!(f, "customer {customer}"),
+            Trashed::Invoice(invoice) => write!(f, "invoice {invoice}"),
+        }
+    }
+}
+
+/// Reads the trash of the selected profile, most recently removed first
+fn read_trash(database: &FactureDatabase) -> Result<Vec<Trashed>> {
+    let customers: Vec<Customer> = database.read_trashed()?;
+    let invoices: Vec<Invoice> = database.read_trashed()?;
+    let mut trash: Vec<Trashed> = customers
+        .into_iter()
+        .map(Trashed::Customer)
+        .chain(invoices.into_iter().map(Trashed::Invoice))
+        .collect();
+    trash.sort_by_key(|trashed| std::cmp::Reverse(trashed.deleted_at()));
+    Ok(trash)
+}
+
+/// Finds the record by its id or prompts for one. Returns `None` if the trash is empty
+fn select(database: &FactureDatabase, id: Option<&str>, action: &str) -> Result<Option<Trashed>> {
+    let trash = read_trash(database)?;
+    if let Some(id) = id {
+        return trash
+            .into_iter()
+            .find(|trashed| trashed.id() == id)
+            .map(Some)
+            .ok_or_else(|| anyhow!("{id} is not in the trash of profile {}", database.profile()));
+    }
+    if trash.is_empty() {
+        println!("The trash is empty");
+        return Ok(None);
+    }
+    prompt::select(&format!("Select a record to {action}"), trash).map(Some)
+}
+
+/// Refuses to purge issued invoices that are still inside their retention period
+fn check_retention(invoice: &Invoice) -> Result<()> {
+    let today = Local::now().date_naive();
+    if let Some(until) = invoice.retained_until().filter(|until| *until >= today) {
+        bail!(
+            "Invoice {} was issued and has to be kept until {until}",
+            invoice.id
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_trash_command(command: &TrashCommand, database: FactureDatabase) -> Result<()> {
+    match command {
+        TrashCommand::List => {
+            let trash = read_trash(&database)?;
+            let customers: Vec<Customer> = database.read_all()?;
+            let organization = |uuid: &str| {
+                customers
+                    .iter()
+                    .find(|customer| customer.uuid == uuid)
+                    .map_or_else(
+                        || "n/a".to_owned(),
+                        |customer| customer.organization.clone(),
+                    )
+            };
+            let header = ["Type", "Id", "Customer", "Removed", "Kept until"]
+                .map(str::to_owned)
+                .to_vec();
+            let rows = trash
+                .iter()
+                .map(|trashed| {
+                    let (kind, customer, kept_until) = match trashed {
+                        Trashed::Customer(customer) => {
+                            ("customer", customer.organization.clone(), None)
+                        }
+                        Trashed::Invoice(invoice) => (
+                            "invoice",
+                            organization(&invoice.customer),
+                            invoice.retained_until(),
+                        ),
+                    };
+                    vec![
+                        kind.to_owned(),
+                        trashed.id().to_owned(),
+                        customer,
+                        trashed
+                            .deleted_at()
+                            .map(|deleted_at| deleted_at.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or_default(),
+                        kept_until
+                            .map(|until| until.to_string())
+                            .unwrap_or_default(),
+                    ]
+                })
+                .collect();
+            output::print_rows(header, rows, "The trash is empty")?;
+        }
+        TrashCommand::Restore { id } => {
+            let Some(trashed) = select(&database, id.as_deref(), "restore")? else {
+                return Ok(());
+            };
+            match trashed {
+                Trashed::Customer(customer) => {
+                    let restored = database.transaction(|transaction| {
+                        let mut restored = 0;
+                        for uuid in &customer.invoices {
+                            let mut invoice: Invoice = transaction.read(uuid)?;
+                            if invoice.deleted_at == customer.deleted_at {
+                                invoice.deleted_at = None;
+                                transaction.update(uuid, invoice)?;
+                                restored += 1;
+                            }
+                        }
+                        let mut customer = customer.clone();
+                        customer.deleted_at = None;
+                        transaction.update(&customer.uuid.clone(), customer)?;
+                        Ok(restored)
+                    })?;
+                    println!("Restored customer {} and {restored} invoices", customer.id);
+                }
+                Trashed::Invoice(mut invoice) => {
+                    let customer: Customer = database.read(&invoice.customer)?;
+                    if customer.is_trashed() {
+                        bail!(
+                            "The customer {} of invoice {} is in the trash. Restore the customer first",
+                            customer.id,
+                            invoice.id
+                        );
+                    }
+                    invoice.deleted_at = None;
+                    database.update(&invoice.uuid.clone(), invoice.clone())?;
+                    println!("Restored invoice {}", invoice.id);
+                }
+            }
+        }
+        TrashCommand::Purge { id } => {
+            let Some(trashed) = select(&database, id.as_deref(), "purge")? else {
+                return Ok(());
+            };
+            let invoices: Vec<Invoice> = match &trashed {
+                Trashed::Customer(customer) => customer
+                    .invoices
+                    .iter()
+                    .map(|uuid| database.read(uuid))
+                    .collect::<Result<_>>()?,
+                Trashed::Invoice(invoice) => vec![invoice.clone()],
+            };
+            if let Some(invoice) = invoices.iter().find(|invoice| !invoice.is_trashed()) {
+                bail!(
+                    "Invoice {} of the customer is not in the trash. Remove it first",
+                    invoice.id
+                );
+            }
+            for invoice in &invoices {
+                check_retention(invoice)?;
+            }
+            let message = match &trashed {
+                Trashed::Customer(_) => format!(
+                    "This will delete {trashed} and {} invoices for good",
+                    invoices.len()
+                ),
+                Trashed::Invoice(_) => format!("This will delete {trashed} for good"),
+            };
+            let result = prompt::confirm(&message)?;
+            if !result {
+                println!("Aborted!");
+                return Ok(());
+            }
+            database.transaction(|transaction| {
+                for invoice in &invoices {
+                    transaction.delete::<Invoice>(&invoice.uuid)?;
+                }
+                match &trashed {
+                    Trashed::Customer(customer) => transaction.delete::<Customer>(&customer.uuid),
+                    Trashed::Invoice(invoice) => {
+                        let mut customer: Customer = transaction.read(&invoice.customer)?;
+                        customer.remove_invoice(&invoice.uuid);
+                        transaction.update(&invoice.customer, customer)
+                    }
+                }
+            })?;
+            println!("Purged {trashed}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::BackendKind,
+        database::{
+            models::{Issuance, DEFAULT_PROFILE},
+            testing,
+        },
+    };
+    use chrono::{Datelike, NaiveDate, NaiveDateTime};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Runs the command and opens the database again, as the command consumes it
+    fn run(database: FactureDatabase, command: TrashCommand) -> (Result<()>, FactureDatabase) {
+        let path = database.path().to_path_buf();
+        let result = handle_trash_command(&command, database);
+        let database = FactureDatabase::open(BackendKind::Native, path, Duration::ZERO).unwrap();
+        (result, database)
+    }
+
+    fn removed_at(day: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2025, 3, day)?.and_hms_opt(12, 0, 0)
+    }
+
+    /// A customer in the trash together with an invoice removed with it and one removed before
+    fn trashed_customer() -> (TempDir, FactureDatabase, Customer, Invoice, Invoice) {
+        let (folder, database) = testing::database(BackendKind::Native);
+        let mut customer = Customer {
+            deleted_at: removed_at(2),
+            ..Customer::new_with_uuid("K0001".to_owned(), None)
+        };
+        let with_customer = Invoice {
+            customer: customer.uuid.clone(),
+            deleted_at: removed_at(2),
+            ..Invoice::new_with_uuid("R1".to_owned(), DEFAULT_PROFILE)
+        };
+        let before = Invoice {
+            customer: customer.uuid.clone(),
+            deleted_at: removed_at(1),
+            ..Invoice::new_with_uuid("R2".to_owned(), DEFAULT_PROFILE)
+        };
+        customer.add_invoice(&with_customer.uuid);
+        customer.add_invoice(&before.uuid);
+        database
+            .transaction(|transaction| {
+                transaction.create(customer.clone())?;
+                transaction.create(with_customer.clone())?;
+                transaction.create(before.clone())
+            })
+            .unwrap();
+        (folder, database, customer, with_customer, before)
+    }
+
+    #[test]
+    fn restoring_a_customer_restores_the_invoices_removed_with_it() {
+        let (_folder, database, customer, with_customer, before) = trashed_customer();
+
+        let (result, database) = run(
+            database,
+            TrashCommand::Restore {
+                id: Some("R2".to_owned()),
+            },
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The customer K0001 of invoice R2 is in the trash. Restore the customer first"
+        );
+
+        let (result, database) = run(
+            database,
+            TrashCommand::Restore {
+                id: Some("K0001".to_owned()),
+            },
+        );
+        result.unwrap();
+        assert!(!database
+            .read::<Customer>(&customer.uuid)
+            .unwrap()
+            .is_trashed());
+        assert!(!database
+            .read::<Invoice>(&with_customer.uuid)
+            .unwrap()
+            .is_trashed());
+        assert!(database.read::<Invoice>(&before.uuid).unwrap().is_trashed());
+    }
+
+    #[test]
+    fn purging_keeps_issued_invoices_until_their_retention_ended() {
+        prompt::assume_yes();
+        let (_folder, database, customer, with_customer, before) = trashed_customer();
+        let issued = Invoice {
+            issuance: Some(Issuance {
+                issued_at: Local::now().naive_local(),
+                sequence: 1,
+                previous_hash: None,
+                pdf_hash: String::new(),
+                hash: String::new(),
+            }),
+            ..with_customer.clone()
+        };
+        database.update(&with_customer.uuid, issued).unwrap();
+
+        let (result, database) = run(
+            database,
+            TrashCommand::Purge {
+                id: Some("K0001".to_owned()),
+            },
+        );
+        let until =
+            NaiveDate::from_ymd_opt(with_customer.issuing_date.year() + 10, 12, 31).unwrap();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("Invoice R1 was issued and has to be kept until {until}")
+        );
+        assert!(database.exists::<Customer>(&customer.uuid).unwrap());
+
+        let (result, database) = run(
+            database,
+            TrashCommand::Purge {
+                id: Some("R2".to_owned()),
+            },
+        );
+        result.unwrap();
+        assert!(!database.exists::<Invoice>(&before.uuid).unwrap());
+        let customer: Customer = database.read(&customer.uuid).unwrap();
+        assert_eq!(customer.invoices, [with_customer.uuid]);
+    }
+}
@@ -0,0 +1,79 @@
+use crate::{
+    database::{
+        journal::Direction,
+        models::{HistoryEntry, JournalChange, Operation},
+        FactureDatabase,
+    },
+    ui::prompt,
+};
+use anyhow::Result;
+use serde_json::Value;
+
+/// Describes what undoing or redoing the change does to the record, like the history does
+fn describe(change: &JournalChange, direction: Direction) -> Result<HistoryEntry> {
+    let parse = |json: &Option<String>| -> Result<Option<Value>> {
+        json.as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(Into::into)
+    };
+    let (from, to) = match direction {
+        Direction::Undo => (parse(&change.after)?, parse(&change.before)?),
+        Direction::Redo => (parse(&change.before)?, parse(&change.after)?),
+    };
+    Ok(HistoryEntry::new(
+        change.kind.to_string(),
+        change.key.clone(),
+        change.id.clone(),
+        from.as_ref(),
+        to.as_ref(),
+    ))
+}
+
+pub fn handle_undo_command(direction: Direction, database: FactureDatabase) -> Result<()> {
+    let action = match direction {
+        Direction::Undo => "undo",
+        Direction::Redo => "redo",
+    };
+    let Some(entry) = database.next_journal_entry(direction)? else {
+        println!("Nothing to {action}");
+        return Ok(());
+    };
+
+    let timestamp = entry.timestamp.format("%Y-%m-%d %H:%M:%S");
+    match direction {
+        Direction::Undo => println!("Reverting the changes made at {timestamp}:"),
+        Direction::Redo => println!("Applying the changes made at {timestamp} again:"),
+    }
+    let mut changes: Vec<&JournalChange> = entry.changes.iter().collect();
+    if direction == Direction::Undo {
+        changes.reverse();
+    }
+    for change in changes {
+        let described = describe(change, direction)?;
+        println!(
+            "{} {} {}",
+            described.operation,
+            described.record_type,
+            described.record_id.as_deref().unwrap_or(&described.record)
+        );
+        // Created and deleted records are named only, their fields would drown the updates
+        if described.operation == Operation::Update {
+            for change in &described.changes {
+                println!("  {change}");
+            }
+        }
+    }
+
+    let result = prompt::confirm(&format!("Do you want to {action} these changes?"))?;
+    if !result {
+        println!("Aborted!");
+        return Ok(());
+    }
+    database.apply_journal_entry(&entry.uuid, direction)?;
+    match direction {
+        Direction::Undo => println!("Undone. Run `facture redo` to apply the changes again"),
+        Direction::Redo => println!("Redone"),
+    }
+    Ok(())
+}
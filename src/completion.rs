@@ -0,0 +1,160 @@
+use crate::database::{
+    backend::encrypted,
+    models::{Customer, Invoice},
+    settings::Settings,
+    FactureDatabase,
+};
+use anyhow::Result;
+use clap_complete::engine::CompletionCandidate;
+use std::{env, ffi::OsStr, path::PathBuf, time::Duration};
+
+/// Ids of records with the help shown next to them
+type Records = Vec<(String, String)>;
+
+/// Completes the ids of the customers of the selected profile
+pub fn customer_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    complete(current, customers)
+}
+
+/// Completes the ids of the invoices of the selected profile
+pub fn invoice_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    complete(current, invoices)
+}
+
+/// Completes the ids of customers and invoices
+pub fn record_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    let mut candidates = customer_ids(current);
+    candidates.extend(invoice_ids(current));
+    candidates
+}
+
+fn customers(database: &FactureDatabase) -> Result<Records> {
+    let customers: Vec<Customer> = database.read_visible()?;
+    Ok(customers
+        .into_iter()
+        .map(|customer| (customer.id.clone(), customer.display_name()))
+        .collect())
+}
+
+fn invoices(database: &FactureDatabase) -> Result<Records> {
+    let customers: Vec<Customer> = database.read_all()?;
+    let invoices: Vec<Invoice> = database.read_visible()?;
+    Ok(invoices
+        .into_iter()
+        .map(|invoice| {
+            let customer = customers
+                .iter()
+                .find(|customer| customer.uuid == invoice.customer)
+                .map(Customer::display_name)
+                .unwrap_or_default();
+            let help = format!("{customer} · {}", invoice.issuing_date);
+            (invoice.id, help)
+        })
+        .collect())
+}
+
+/// Offers the ids starting with the current value, described by their help. Completions stay
+/// empty if the database can not be read
+fn complete(
+    current: &OsStr,
+    records: impl Fn(&FactureDatabase) -> Result<Records>,
+) -> Vec<CompletionCandidate> {
+    let records = open()
+        .and_then(|database| database.map(|database| records(&database)).transpose())
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    candidates(current, records)
+}
+
+fn candidates(current: &OsStr, records: Records) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    records
+        .into_iter()
+        .filter(|(id, _)| id.starts_with(current.as_ref()))
+        .map(|(id, help)| CompletionCandidate::new(id).help(Some(help.into())))
+        .collect()
+}
+
+/// Opens the database like a command does, but never asks for a passphrase, waits for a lock or
+/// migrates, as the shell is waiting. `--database` and `--profile` on the command line being
+/// completed are not seen, only FACTURE_DB and FACTURE_PROFILE
+fn open() -> Result<Option<FactureDatabase>> {
+    let settings = Settings::load()?;
+    let explicit = env::var_os("FACTURE_DB").map(PathBuf::from);
+    let path = settings.database_path(explicit.as_deref());
+    if !path.exists() || encrypted::is_encrypted(&path)? && !encrypted::has_passphrase() {
+        return Ok(None);
+    }
+    let database = FactureDatabase::open_unmigrated(settings.backend, &path, Duration::ZERO)?;
+    if !database.pending_migrations()?.is_empty() {
+        return Ok(None);
+    }
+    let profile = env::var("FACTURE_PROFILE").ok();
+    Ok(Some(
+        database.with_profile(settings.profile(profile.as_deref())),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::BackendKind,
+        database::{models::DEFAULT_PROFILE, testing},
+    };
+    use chrono::NaiveDate;
+
+    fn completed(candidates: Vec<CompletionCandidate>) -> Records {
+        candidates
+            .into_iter()
+            .map(|candidate| {
+                let help = candidate.get_help().map(ToString::to_string);
+                (
+                    candidate.get_value().to_string_lossy().into_owned(),
+                    help.unwrap_or_default(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn completes_the_visible_records_of_the_profile() {
+        let (_folder, database) = testing::database(BackendKind::Native);
+        let acme = Customer {
+            organization: "ACME".to_owned(),
+            ..Customer::new_with_uuid("K0001".to_owned(), None)
+        };
+        let other = Customer::new_with_uuid("K0002".to_owned(), Some("other".to_owned()));
+        let invoice = Invoice {
+            customer: acme.uuid.clone(),
+            issuing_date: NaiveDate::from_ymd_opt(2025, 1, 31).unwrap(),
+            ..Invoice::new_with_uuid("R1".to_owned(), DEFAULT_PROFILE)
+        };
+        let trashed = Invoice {
+            deleted_at: Some(invoice.issuing_date.and_hms_opt(0, 0, 0).unwrap()),
+            ..Invoice::new_with_uuid("R2".to_owned(), DEFAULT_PROFILE)
+        };
+        database
+            .transaction(|transaction| {
+                transaction.create(acme.clone())?;
+                transaction.create(other.clone())?;
+                transaction.create(invoice.clone())?;
+                transaction.create(trashed.clone())
+            })
+            .unwrap();
+
+        let complete = |current: &str, records: fn(&FactureDatabase) -> Result<Records>| {
+            completed(candidates(OsStr::new(current), records(&database).unwrap()))
+        };
+        assert_eq!(
+            complete("K", customers),
+            [("K0001".to_owned(), "ACME".to_owned())]
+        );
+        assert_eq!(
+            complete("", invoices),
+            [("R1".to_owned(), "ACME · 2025-01-31".to_owned())]
+        );
+        assert!(complete("X", customers).is_empty());
+    }
+}
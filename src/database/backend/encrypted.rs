@@ -0,0 +1,411 @@
+use super::{Backend, BackendTransaction};
+use crate::{cli::BackendKind, database::migration::Migration, ui::prompt};
+use anyhow::{anyhow, bail, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    env,
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Command},
+    thread,
+};
+use tempfile::{NamedTempFile, TempDir};
+
+/// Passphrase of encrypted databases
+const PASSPHRASE_ENV: &str = "FACTURE_PASSPHRASE";
+/// Shell command that prints the passphrase, like `pass show facture`. It is run with `sh -c`
+/// and the rights of the user, so it is as trusted as the environment facture runs in
+const PASSPHRASE_COMMAND_ENV: &str = "FACTURE_PASSPHRASE_COMMAND";
+/// New passphrase for `facture db rekey`
+const NEW_PASSPHRASE_ENV: &str = "FACTURE_NEW_PASSPHRASE";
+/// Private folder for the decrypted copy. Takes precedence over `XDG_RUNTIME_DIR`
+const RUNTIME_DIR_ENV: &str = "FACTURE_RUNTIME_DIR";
+
+const MAGIC: &[u8] = b"facture-encrypted-v1\n";
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+/// Magic, the three Argon2 cost parameters and the salt
+const HEADER_LENGTH: usize = MAGIC.len() + 3 * 4 + SALT_LENGTH;
+
+/// The key of an encrypted file together with everything needed to derive it again
+struct Sealer {
+    cipher: XChaCha20Poly1305,
+    params: Params,
+    salt: [u8; SALT_LENGTH],
+}
+
+impl Sealer {
+    /// Derives a key with a fresh salt
+    fn new(passphrase: &str) -> Result<Self> {
+        let mut salt = [0; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(passphrase, Params::default(), salt)
+    }
+
+    fn derive(passphrase: &str, params: Params, salt: [u8; SALT_LENGTH]) -> Result<Self> {
+        let mut key = [0; KEY_LENGTH];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|error| anyhow!("Failed to derive the key: {error}"))?;
+        Ok(Self {
+            cipher: XChaCha20Poly1305::new(&key.into()),
+            params,
+            salt,
+        })
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut header = MAGIC.to_vec();
+        for cost in [
+            self.params.m_cost(),
+            self.params.t_cost(),
+            self.params.p_cost(),
+        ] {
+            header.extend(cost.to_le_bytes());
+        }
+        header.extend(self.salt);
+        header
+    }
+
+    /// Encrypts the content into `header | nonce | ciphertext`. The header is authenticated too
+    fn seal(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        let header = self.header();
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plain,
+                    aad: &header,
+                },
+            )
+            .map_err(|error| anyhow!("Failed to encrypt: {error}"))?;
+        Ok([header, nonce.to_vec(), ciphertext].concat())
+    }
+
+    /// Derives the key from the header of the encrypted content and decrypts it
+    fn unseal(passphrase: &str, content: &[u8], path: &Path) -> Result<(Self, Vec<u8>)> {
+        if content.len() < HEADER_LENGTH + NONCE_LENGTH || !content.starts_with(MAGIC) {
+            bail!("{} is not an encrypted facture database", path.display());
+        }
+        let (header, rest) = content.split_at(HEADER_LENGTH);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+        let cost = |index: usize| {
+            let start = MAGIC.len() + index * 4;
+            u32::from_le_bytes([
+                header[start],
+                header[start + 1],
+                header[start + 2],
+                header[start + 3],
+            ])
+        };
+        let params = Params::new(cost(0), cost(1), cost(2), Some(KEY_LENGTH))
+            .map_err(|error| anyhow!("{}: {error}", path.display()))?;
+        let mut salt = [0; SALT_LENGTH];
+        salt.copy_from_slice(&header[HEADER_LENGTH - SALT_LENGTH..]);
+        let sealer = Self::derive(passphrase, params, salt)?;
+        let plain = sealer
+            .cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| {
+                anyhow!(
+                    "Wrong passphrase for {} or the file is damaged",
+                    path.display()
+                )
+            })?;
+        Ok((sealer, plain))
+    }
+}
+
+/// Returns whether the path is a database file that was encrypted by facture
+pub fn is_encrypted(path: &Path) -> Result<bool> {
+    if !path.is_file() {
+        return Ok(false);
+    }
+    let mut start = vec![0; MAGIC.len()];
+    let read = File::open(path)?.read(&mut start)?;
+    Ok(read == MAGIC.len() && start == MAGIC)
+}
+
+/// Returns whether the passphrase is given by the environment, so opening never asks for it
+pub fn has_passphrase() -> bool {
+    env::var_os(PASSPHRASE_ENV).is_some() || env::var_os(PASSPHRASE_COMMAND_ENV).is_some()
+}
+
+/// Reads the passphrase from [`PASSPHRASE_ENV`], the output of [`PASSPHRASE_COMMAND_ENV`] or
+/// asks for it
+fn passphrase(path: &Path) -> Result<String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if let Ok(command) = env::var(PASSPHRASE_COMMAND_ENV) {
+        let output = Command::new("sh").args(["-c", &command]).output()?;
+        if !output.status.success() {
+            bail!("{PASSPHRASE_COMMAND_ENV} failed with {}", output.status);
+        }
+        let passphrase = String::from_utf8(output.stdout)?;
+        return Ok(passphrase.trim_end_matches(['\r', '\n']).to_owned());
+    }
+    prompt::password(&format!("Passphrase for {}", path.display()))
+}
+
+/// Reads the new passphrase from [`NEW_PASSPHRASE_ENV`] or asks for it twice
+fn new_passphrase() -> Result<String> {
+    let passphrase = match env::var(NEW_PASSPHRASE_ENV) {
+        Ok(passphrase) => passphrase,
+        Err(_) => prompt::new_password("New passphrase")?,
+    };
+    if passphrase.is_empty() {
+        bail!("The passphrase can not be empty");
+    }
+    Ok(passphrase)
+}
+
+/// Encrypts the database file with a new passphrase or stores it unencrypted. An encrypted file
+/// is unlocked with its current passphrase first.
+pub fn rekey(path: &Path, encrypt: bool) -> Result<()> {
+    let content = fs::read(path)?;
+    let plain = if is_encrypted(path)? {
+        Sealer::unseal(&passphrase(path)?, &content, path)?.1
+    } else {
+        content
+    };
+    let content = if encrypt {
+        Sealer::new(&new_passphrase()?)?.seal(&plain)?
+    } else {
+        plain
+    };
+    write_atomic(path, &content)
+}
+
+/// Replaces the file in one step so an interrupted write never leaves half a database behind
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let folder = path
+        .parent()
+        .filter(|folder| !folder.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let file = NamedTempFile::new_in(folder)?;
+    fs::write(file.path(), content)?;
+    file.persist(path)?;
+    Ok(())
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Keeps the file of a native or SQLite database encrypted with a key derived from a passphrase
+/// (Argon2id and XChaCha20-Poly1305).
+///
+/// The inner backends only work on files, so the database is decrypted into a private folder
+/// (mode 0700, the file 0600) in `$FACTURE_RUNTIME_DIR` or `$XDG_RUNTIME_DIR`. After every
+/// transaction that writes, the copy is encrypted again and replaces the database file. The
+/// folder is removed when facture exits, panics or is interrupted. Folders left behind by a
+/// killed process are removed the next time the database is opened.
+pub struct EncryptedBackend {
+    inner: Box<dyn Backend>,
+    path: PathBuf,
+    plain: NamedTempFile,
+    sealer: Sealer,
+    /// Hash of the content that was encrypted last, so unchanged databases are not rewritten
+    sealed: RefCell<String>,
+    /// Dropped after the inner backend closed the decrypted copy
+    _folder: TempDir,
+}
+
+impl EncryptedBackend {
+    pub fn open(kind: BackendKind, path: &Path) -> Result<Self> {
+        let content = fs::read(path)?;
+        let (sealer, plain) = Sealer::unseal(&passphrase(path)?, &content, path)?;
+        let runtime = runtime_dir()?;
+        // The database is locked, so folders of the same database are left over from a crash
+        let prefix = format!(
+            "facture-{}-",
+            &sha256(path.canonicalize()?.as_os_str().as_encoded_bytes())[..16]
+        );
+        remove_leftovers(&runtime, &prefix)?;
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(&prefix);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            builder.permissions(fs::Permissions::from_mode(0o700));
+        }
+        let folder = builder.tempdir_in(runtime)?;
+        remove_on_signal(folder.path().to_path_buf())?;
+        let mut file = tempfile::Builder::new()
+            .suffix(kind.file_name())
+            .tempfile_in(folder.path())?;
+        file.write_all(&plain)?;
+        Ok(Self {
+            inner: kind.open_plain(file.path())?,
+            path: path.to_path_buf(),
+            plain: file,
+            sealer,
+            sealed: RefCell::new(sha256(&plain)),
+            _folder: folder,
+        })
+    }
+
+    fn seal(&self) -> Result<()> {
+        let plain = fs::read(self.plain.path())?;
+        let hash = sha256(&plain);
+        if *self.sealed.borrow() == hash {
+            return Ok(());
+        }
+        write_atomic(&self.path, &self.sealer.seal(&plain)?)?;
+        self.sealed.replace(hash);
+        Ok(())
+    }
+}
+
+/// Returns the folder for the decrypted copy. The temporary folder is no fallback, as it may be
+/// shared with other users or kept on disk
+fn runtime_dir() -> Result<PathBuf> {
+    let folder = env::var_os(RUNTIME_DIR_ENV)
+        .or_else(|| env::var_os("XDG_RUNTIME_DIR"))
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            anyhow!(
+                "Neither {RUNTIME_DIR_ENV} nor XDG_RUNTIME_DIR is set, so there is no private folder to decrypt the database into. Set {RUNTIME_DIR_ENV} to a folder only you can read"
+            )
+        })?;
+    if !folder.is_dir() {
+        bail!("{} does not exist", folder.display());
+    }
+    Ok(folder)
+}
+
+/// Removes the folders with decrypted copies of the database that start with the prefix
+fn remove_leftovers(runtime: &Path, prefix: &str) -> Result<()> {
+    for entry in fs::read_dir(runtime)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(prefix) {
+            fs::remove_dir_all(entry.path())
+                .map_err(|error| anyhow!("{}: {error}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes the folder of the decrypted copy and exits when facture is interrupted or
+/// terminated, which would skip the cleanup on drop
+#[cfg(unix)]
+fn remove_on_signal(folder: PathBuf) -> Result<()> {
+    use signal_hook::{
+        consts::{SIGHUP, SIGINT, SIGTERM},
+        iterator::Signals,
+    };
+    let mut signals = Signals::new([SIGHUP, SIGINT, SIGTERM])?;
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            // The process ends either way
+            let _ = fs::remove_dir_all(&folder);
+            process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn remove_on_signal(_folder: PathBuf) -> Result<()> {
+    Ok(())
+}
+
+impl Backend for EncryptedBackend {
+    fn transaction(&self, f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>) -> Result<()> {
+        self.inner.transaction(f)?;
+        self.seal()
+    }
+
+    fn read_transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>,
+    ) -> Result<()> {
+        self.inner.read_transaction(f)
+    }
+
+    fn pending_migrations(&self) -> Result<Vec<Migration>> {
+        self.inner.pending_migrations()
+    }
+
+    fn migrate(&self) -> Result<()> {
+        self.inner.migrate()?;
+        self.seal()
+    }
+
+    fn duplicate_ids(&self) -> Result<Vec<String>> {
+        self.inner.duplicate_ids()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{models::Customer, testing, FactureDatabase};
+    use std::time::Duration;
+
+    fn open(path: &Path) -> Result<FactureDatabase> {
+        FactureDatabase::open(BackendKind::Native, path, Duration::ZERO)
+    }
+
+    /// The only test that sets the passphrase variables, as they are shared by all tests
+    #[test]
+    fn encrypts_rekeys_and_decrypts_a_database() {
+        let runtime = tempfile::tempdir().unwrap();
+        env::set_var(RUNTIME_DIR_ENV, runtime.path());
+        let (_folder, database) = testing::database(BackendKind::Native);
+        let path = database.path().to_path_buf();
+        let first = Customer::new_with_uuid("K0001".to_owned(), None);
+        database.create(first.clone()).unwrap();
+        drop(database);
+
+        env::set_var(NEW_PASSPHRASE_ENV, "first");
+        rekey(&path, true).unwrap();
+        assert!(is_encrypted(&path).unwrap());
+        assert!(!fs::read(&path)
+            .unwrap()
+            .windows(first.uuid.len())
+            .any(|window| window == first.uuid.as_bytes()));
+
+        env::set_var(PASSPHRASE_ENV, "wrong");
+        let error = open(&path).err().unwrap().to_string();
+        assert!(error.starts_with("Wrong passphrase"), "{error}");
+
+        env::set_var(PASSPHRASE_ENV, "first");
+        let database = open(&path).unwrap();
+        database.read::<Customer>(&first.uuid).unwrap();
+        let second = Customer::new_with_uuid("K0002".to_owned(), None);
+        database.create(second.clone()).unwrap();
+        drop(database);
+        // The decrypted copy is gone once the database is closed
+        assert_eq!(fs::read_dir(runtime.path()).unwrap().count(), 0);
+
+        env::set_var(NEW_PASSPHRASE_ENV, "second");
+        rekey(&path, true).unwrap();
+        env::set_var(PASSPHRASE_ENV, "second");
+        let database = open(&path).unwrap();
+        database.read::<Customer>(&second.uuid).unwrap();
+        drop(database);
+
+        rekey(&path, false).unwrap();
+        assert!(!is_encrypted(&path).unwrap());
+        let database = open(&path).unwrap();
+        assert_eq!(database.read_all::<Customer>().unwrap().len(), 2);
+    }
+}
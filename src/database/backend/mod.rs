@@ -0,0 +1,253 @@
+pub mod encrypted;
+pub mod native;
+pub mod sqlite;
+pub mod yaml;
+
+use crate::cli::BackendKind;
+
+use super::{
+    migration::Migration,
+    models::{Business, Config, Customer, HistoryEntry, Invoice, JournalEntry, Upgrade},
+};
+use anyhow::{anyhow, bail, Result};
+use native_model::Model;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, path::Path};
+
+impl BackendKind {
+    /// Opens the database and unlocks it first if it is encrypted
+    pub fn open(self, path: &Path) -> Result<Box<dyn Backend>> {
+        if encrypted::is_encrypted(path)? {
+            return Ok(Box::new(encrypted::EncryptedBackend::open(self, path)?));
+        }
+        self.open_plain(path)
+    }
+
+    fn open_plain(self, path: &Path) -> Result<Box<dyn Backend>> {
+        let backend: Box<dyn Backend> = match self {
+            BackendKind::Native => Box::new(native::NativeBackend::open(path)?),
+            BackendKind::Sqlite => Box::new(sqlite::SqliteBackend::open(path)?),
+            BackendKind::Yaml => Box::new(yaml::YamlBackend::open(path)?),
+        };
+        Ok(backend)
+    }
+
+    /// Name of the database file or folder if no path is configured
+    pub fn file_name(self) -> &'static str {
+        match self {
+            BackendKind::Native => "facture.db",
+            BackendKind::Sqlite => "facture.sqlite",
+            BackendKind::Yaml => "facture-data",
+        }
+    }
+}
+
+impl Display for BackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BackendKind::Native => f.write_str("native"),
+            BackendKind::Sqlite => f.write_str("sqlite"),
+            BackendKind::Yaml => f.write_str("yaml"),
+        }
+    }
+}
+
+/// A storage engine for the records of facture.
+///
+/// All reads and writes happen inside [`Backend::transaction`]. The records are passed as
+/// [`Entity`] so the trait stays object safe.
+pub trait Backend {
+    /// Runs the closure in a single transaction and commits it if the closure succeeds
+    fn transaction(&self, f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>) -> Result<()>;
+
+    /// Runs the closure in a transaction that only reads. Writes fail and nothing is committed
+    fn read_transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>,
+    ) -> Result<()>;
+
+    fn pending_migrations(&self) -> Result<Vec<Migration>>;
+
+    fn migrate(&self) -> Result<()>;
+
+    /// Returns the customer and invoice ids that are used by more than one record. Only
+    /// databases of older versions can have them, and migrating them renames the duplicates
+    fn duplicate_ids(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    /// Accepts changes made outside facture and returns a description of every change
+    fn reindex(&self) -> Result<Vec<String>> {
+        bail!("Only the yaml backend keeps an index")
+    }
+}
+
+pub trait BackendTransaction {
+    fn create(&self, entity: Entity) -> Result<()>;
+
+    /// Replaces the record stored under the key
+    fn update(&self, key: &str, entity: Entity) -> Result<()>;
+
+    fn read(&self, kind: Kind, key: &str) -> Result<Option<Entity>>;
+
+    /// Returns all records of a kind ordered by their key
+    fn read_all(&self, kind: Kind) -> Result<Vec<Entity>>;
+
+    /// Looks up a customer or invoice by its human readable id. Ids are unique across all
+    /// profiles
+    fn find_by_id(&self, kind: Kind, id: &str) -> Result<Option<Entity>>;
+
+    fn delete(&self, kind: Kind, key: &str) -> Result<()>;
+
+    fn exists(&self, kind: Kind, key: &str) -> Result<bool> {
+        Ok(self.read(kind, key)?.is_some())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Customer,
+    Invoice,
+    Business,
+    Config,
+    History,
+    Journal,
+}
+
+impl Kind {
+    /// Current model version of the records of this kind
+    pub fn version(self) -> u32 {
+        match self {
+            Kind::Customer => Customer::native_model_version(),
+            Kind::Invoice => Invoice::native_model_version(),
+            Kind::Business => Business::native_model_version(),
+            Kind::Config => Config::native_model_version(),
+            Kind::History => HistoryEntry::native_model_version(),
+            Kind::Journal => JournalEntry::native_model_version(),
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Customer => f.write_str("customer"),
+            Kind::Invoice => f.write_str("invoice"),
+            Kind::Business => f.write_str("business"),
+            Kind::Config => f.write_str("config"),
+            Kind::History => f.write_str("history"),
+            Kind::Journal => f.write_str("journal"),
+        }
+    }
+}
+
+/// A record of any model
+#[derive(Debug, Clone)]
+pub enum Entity {
+    Customer(Customer),
+    Invoice(Invoice),
+    Business(Business),
+    Config(Config),
+    History(HistoryEntry),
+    Journal(JournalEntry),
+}
+
+impl Entity {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Entity::Customer(_) => Kind::Customer,
+            Entity::Invoice(_) => Kind::Invoice,
+            Entity::Business(_) => Kind::Business,
+            Entity::Config(_) => Kind::Config,
+            Entity::History(_) => Kind::History,
+            Entity::Journal(_) => Kind::Journal,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            Entity::Customer(customer) => &customer.uuid,
+            Entity::Invoice(invoice) => &invoice.uuid,
+            Entity::Business(business) => &business.profile,
+            Entity::Config(config) => &config.profile,
+            Entity::History(entry) => &entry.uuid,
+            Entity::Journal(entry) => &entry.uuid,
+        }
+    }
+
+    /// Human readable id of customers and invoices
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Entity::Customer(customer) => Some(&customer.id),
+            Entity::Invoice(invoice) => Some(&invoice.id),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<serde_json::Value> {
+        let value = match self {
+            Entity::Customer(customer) => serde_json::to_value(customer)?,
+            Entity::Invoice(invoice) => serde_json::to_value(invoice)?,
+            Entity::Business(business) => serde_json::to_value(business)?,
+            Entity::Config(config) => serde_json::to_value(config)?,
+            Entity::History(entry) => serde_json::to_value(entry)?,
+            Entity::Journal(entry) => serde_json::to_value(entry)?,
+        };
+        Ok(value)
+    }
+
+    /// Reads a record that was serialized with the given model version and upgrades it
+    pub fn upgrade(kind: Kind, version: u32, yaml: &str) -> Result<Self> {
+        let entity = match kind {
+            Kind::Customer => Entity::Customer(Customer::upgrade(version, yaml)?),
+            Kind::Invoice => Entity::Invoice(Invoice::upgrade(version, yaml)?),
+            Kind::Business => Entity::Business(Business::upgrade(version, yaml)?),
+            Kind::Config => Entity::Config(Config::upgrade(version, yaml)?),
+            Kind::History => Entity::History(HistoryEntry::upgrade(version, yaml)?),
+            Kind::Journal => Entity::Journal(JournalEntry::upgrade(version, yaml)?),
+        };
+        Ok(entity)
+    }
+}
+
+/// A model that can be stored by every backend
+pub trait Record: Sized {
+    const KIND: Kind;
+
+    fn into_entity(self) -> Entity;
+    fn from_entity(entity: Entity) -> Result<Self>;
+}
+
+macro_rules! record {
+    ($model:ident) => {
+        record!($model => $model);
+    };
+    ($model:ident => $variant:ident) => {
+        impl Record for $model {
+            const KIND: Kind = Kind::$variant;
+
+            fn into_entity(self) -> Entity {
+                Entity::$variant(self)
+            }
+
+            fn from_entity(entity: Entity) -> Result<Self> {
+                match entity {
+                    Entity::$variant(record) => Ok(record),
+                    entity => Err(anyhow!(
+                        "Expected a {} but got a {}",
+                        Self::KIND,
+                        entity.kind()
+                    )),
+                }
+            }
+        }
+    };
+}
+
+record!(Customer);
+record!(Invoice);
+record!(Business);
+record!(Config);
+record!(HistoryEntry => History);
+record!(JournalEntry => Journal);
@@ -0,0 +1,227 @@
+use super::{Backend, BackendTransaction, Entity, Kind, Record};
+use crate::database::{
+    migration::{self, Migration},
+    models::{
+        v1, v2, Business, Config, Customer, HistoryEntry, Identifiable, Invoice, JournalEntry,
+    },
+};
+use anyhow::{anyhow, bail, Result};
+use native_db::{
+    db_type,
+    transaction::{RTransaction, RwTransaction},
+    Builder, Database, Models, ToInput,
+};
+use std::{path::Path, sync::LazyLock};
+
+pub static MODELS: LazyLock<Models> = LazyLock::new(|| {
+    let mut models = Models::new();
+    // It's a good practice to define the models by specifying the version
+    models.define::<v1::Customer>().unwrap();
+    models.define::<v2::Customer>().unwrap();
+    models.define::<v1::Invoice>().unwrap();
+    models.define::<v2::Invoice>().unwrap();
+    models.define::<v1::Business>().unwrap();
+    models.define::<v2::Business>().unwrap();
+    models.define::<v1::Config>().unwrap();
+    models.define::<v2::Config>().unwrap();
+    models.define::<v1::HistoryEntry>().unwrap();
+    models.define::<v1::JournalEntry>().unwrap();
+    models
+});
+
+/// Stores all records in a single native_db file
+pub struct NativeBackend {
+    _builder: Builder,
+    database: Database<'static>,
+}
+
+impl NativeBackend {
+    pub fn open(path: &Path) -> Result<Self> {
+        let builder = Builder::new();
+        let database = builder.create(&MODELS, path)?;
+        Ok(Self {
+            _builder: builder,
+            database,
+        })
+    }
+}
+
+impl Backend for NativeBackend {
+    fn transaction(&self, f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>) -> Result<()> {
+        let transaction = NativeTransaction::Write(Box::new(self.database.rw_transaction()?));
+        f(&transaction)?;
+        if let NativeTransaction::Write(rw) = transaction {
+            (*rw).commit()?;
+        }
+        Ok(())
+    }
+
+    fn read_transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>,
+    ) -> Result<()> {
+        f(&NativeTransaction::Read(self.database.r_transaction()?))
+    }
+
+    fn pending_migrations(&self) -> Result<Vec<Migration>> {
+        migration::pending(&self.database)
+    }
+
+    fn migrate(&self) -> Result<()> {
+        migration::migrate(&self.database)
+    }
+
+    fn duplicate_ids(&self) -> Result<Vec<String>> {
+        migration::duplicate_ids(&self.database)
+    }
+}
+
+enum NativeTransaction<'db> {
+    Read(RTransaction<'db>),
+    Write(Box<RwTransaction<'db>>),
+}
+
+impl NativeTransaction<'_> {
+    fn rw(&self) -> Result<&RwTransaction<'_>> {
+        match self {
+            NativeTransaction::Write(rw) => Ok(rw.as_ref()),
+            NativeTransaction::Read(_) => bail!("Can not write in a read-only transaction"),
+        }
+    }
+
+    fn insert<T: ToInput>(&self, item: T) -> Result<()> {
+        self.rw()?.insert(item).map_err(unique_error)?;
+        Ok(())
+    }
+
+    fn replace<T: ToInput>(&self, key: &str, item: T) -> Result<()> {
+        let rw = self.rw()?;
+        let old: T = rw
+            .get()
+            .primary(key)?
+            .ok_or_else(|| anyhow!("{key} not found"))?;
+        rw.update(old, item).map_err(unique_error)?;
+        Ok(())
+    }
+
+    fn get<T: ToInput + Record>(&self, key: &str) -> Result<Option<Entity>> {
+        let result: Option<T> = match self {
+            NativeTransaction::Read(r) => r.get().primary(key)?,
+            NativeTransaction::Write(rw) => rw.get().primary(key)?,
+        };
+        Ok(result.map(T::into_entity))
+    }
+
+    fn scan<T: ToInput + Record>(&self) -> Result<Vec<Entity>> {
+        let result = match self {
+            NativeTransaction::Read(r) => r
+                .scan()
+                .primary()?
+                .all()?
+                .filter_map(Result::ok)
+                .map(T::into_entity)
+                .collect(),
+            NativeTransaction::Write(rw) => rw
+                .scan()
+                .primary()?
+                .all()?
+                .filter_map(Result::ok)
+                .map(T::into_entity)
+                .collect(),
+        };
+        Ok(result)
+    }
+
+    fn find<T: Identifiable>(&self, id: &str) -> Result<Option<Entity>> {
+        let result: Option<T> = match self {
+            NativeTransaction::Read(r) => r.get().secondary(T::id_key(), id)?,
+            NativeTransaction::Write(rw) => rw.get().secondary(T::id_key(), id)?,
+        };
+        Ok(result.map(T::into_entity))
+    }
+
+    fn remove<T: ToInput>(&self, key: &str) -> Result<()> {
+        let rw = self.rw()?;
+        let item: T = rw
+            .get()
+            .primary(key)?
+            .ok_or_else(|| anyhow!("{key} not found"))?;
+        rw.remove(item)?;
+        Ok(())
+    }
+}
+
+impl BackendTransaction for NativeTransaction<'_> {
+    fn create(&self, entity: Entity) -> Result<()> {
+        match entity {
+            Entity::Customer(customer) => self.insert(customer),
+            Entity::Invoice(invoice) => self.insert(invoice),
+            Entity::Business(business) => self.insert(business),
+            Entity::Config(config) => self.insert(config),
+            Entity::History(entry) => self.insert(entry),
+            Entity::Journal(entry) => self.insert(entry),
+        }
+    }
+
+    fn update(&self, key: &str, entity: Entity) -> Result<()> {
+        match entity {
+            Entity::Customer(customer) => self.replace(key, customer),
+            Entity::Invoice(invoice) => self.replace(key, invoice),
+            Entity::Business(business) => self.replace(key, business),
+            Entity::Config(config) => self.replace(key, config),
+            Entity::History(entry) => self.replace(key, entry),
+            Entity::Journal(entry) => self.replace(key, entry),
+        }
+    }
+
+    fn read(&self, kind: Kind, key: &str) -> Result<Option<Entity>> {
+        match kind {
+            Kind::Customer => self.get::<Customer>(key),
+            Kind::Invoice => self.get::<Invoice>(key),
+            Kind::Business => self.get::<Business>(key),
+            Kind::Config => self.get::<Config>(key),
+            Kind::History => self.get::<HistoryEntry>(key),
+            Kind::Journal => self.get::<JournalEntry>(key),
+        }
+    }
+
+    fn read_all(&self, kind: Kind) -> Result<Vec<Entity>> {
+        match kind {
+            Kind::Customer => self.scan::<Customer>(),
+            Kind::Invoice => self.scan::<Invoice>(),
+            Kind::Business => self.scan::<Business>(),
+            Kind::Config => self.scan::<Config>(),
+            Kind::History => self.scan::<HistoryEntry>(),
+            Kind::Journal => self.scan::<JournalEntry>(),
+        }
+    }
+
+    fn find_by_id(&self, kind: Kind, id: &str) -> Result<Option<Entity>> {
+        match kind {
+            Kind::Customer => self.find::<Customer>(id),
+            Kind::Invoice => self.find::<Invoice>(id),
+            Kind::Business | Kind::Config | Kind::History | Kind::Journal => {
+                bail!("A {kind} has no id")
+            }
+        }
+    }
+
+    fn delete(&self, kind: Kind, key: &str) -> Result<()> {
+        match kind {
+            Kind::Customer => self.remove::<Customer>(key),
+            Kind::Invoice => self.remove::<Invoice>(key),
+            Kind::Business => self.remove::<Business>(key),
+            Kind::Config => self.remove::<Config>(key),
+            Kind::History => self.remove::<HistoryEntry>(key),
+            Kind::Journal => self.remove::<JournalEntry>(key),
+        }
+    }
+}
+
+/// Turns a violated unique key into a readable error
+fn unique_error(error: db_type::Error) -> anyhow::Error {
+    match error {
+        db_type::Error::DuplicateKey { .. } => anyhow!("The id is already used by another record"),
+        error => error.into(),
+    }
+}
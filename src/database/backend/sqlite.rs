@@ -0,0 +1,829 @@
+use super::{Backend, BackendTransaction, Entity, Kind};
+use crate::database::{
+    migration::Migration,
+    models::{
+        v1::Payment, Address, Business, Config, Contact, Customer, HistoryEntry, Invoice, Issuance,
+        Item, JournalEntry,
+    },
+};
+use anyhow::{anyhow, bail, Result};
+use rusqlite::{
+    params, params_from_iter,
+    types::{ToSqlOutput, Value},
+    Connection, ErrorCode, Row, ToSql,
+};
+use std::path::Path;
+
+/// Every entry moves the schema one version forward. The schema version is stored in
+/// `PRAGMA user_version`, so entries must never be changed once released.
+const SCHEMA: &[&str] = &[
+    r#"
+CREATE TABLE customers (
+    uuid TEXT PRIMARY KEY NOT NULL,
+    id TEXT NOT NULL UNIQUE,
+    organization TEXT NOT NULL,
+    vat_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    surname TEXT NOT NULL,
+    email TEXT,
+    phone TEXT,
+    country TEXT NOT NULL,
+    city TEXT NOT NULL,
+    postal_code TEXT NOT NULL,
+    street TEXT NOT NULL,
+    number TEXT NOT NULL
+);
+
+CREATE TABLE invoices (
+    uuid TEXT PRIMARY KEY NOT NULL,
+    id TEXT NOT NULL UNIQUE,
+    customer_uuid TEXT NOT NULL
+        REFERENCES customers (uuid) DEFERRABLE INITIALLY DEFERRED,
+    issuing_date TEXT NOT NULL,
+    delivery_date TEXT NOT NULL,
+    due_days INTEGER NOT NULL,
+    payment_date TEXT
+);
+
+CREATE INDEX invoices_customer_uuid ON invoices (customer_uuid);
+
+CREATE TABLE customer_invoices (
+    customer_uuid TEXT NOT NULL
+        REFERENCES customers (uuid) ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED,
+    position INTEGER NOT NULL,
+    invoice_uuid TEXT NOT NULL,
+    PRIMARY KEY (customer_uuid, position)
+);
+
+CREATE TABLE items (
+    invoice_uuid TEXT NOT NULL
+        REFERENCES invoices (uuid) ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED,
+    position INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    price REAL NOT NULL,
+    quantity INTEGER,
+    PRIMARY KEY (invoice_uuid, position)
+);
+
+CREATE TABLE business (
+    key TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    vat_id TEXT NOT NULL,
+    tax_number TEXT NOT NULL,
+    vat REAL NOT NULL,
+    small_business INTEGER NOT NULL,
+    contact_name TEXT NOT NULL,
+    contact_surname TEXT NOT NULL,
+    email TEXT,
+    phone TEXT,
+    country TEXT NOT NULL,
+    city TEXT NOT NULL,
+    postal_code TEXT NOT NULL,
+    street TEXT NOT NULL,
+    number TEXT NOT NULL,
+    bank TEXT NOT NULL,
+    iban TEXT NOT NULL,
+    bic TEXT NOT NULL
+);
+
+CREATE TABLE config (
+    key TEXT PRIMARY KEY NOT NULL,
+    customer_template TEXT NOT NULL,
+    customer_counter INTEGER NOT NULL,
+    invoice_template TEXT NOT NULL,
+    invoice_counter INTEGER NOT NULL
+);
+"#,
+    // Business profiles
+    r#"
+ALTER TABLE customers ADD COLUMN profile TEXT;
+UPDATE customers SET profile = 'default';
+
+ALTER TABLE invoices ADD COLUMN profile TEXT NOT NULL DEFAULT 'default';
+
+ALTER TABLE business RENAME COLUMN key TO profile;
+UPDATE business SET profile = 'default';
+
+ALTER TABLE config RENAME COLUMN key TO profile;
+UPDATE config SET profile = 'default';
+"#,
+    // History
+    r#"
+CREATE TABLE history (
+    uuid TEXT PRIMARY KEY NOT NULL,
+    timestamp TEXT NOT NULL,
+    record_type TEXT NOT NULL,
+    record TEXT NOT NULL,
+    record_id TEXT,
+    operation TEXT NOT NULL,
+    changes TEXT NOT NULL
+);
+
+CREATE INDEX history_record ON history (record);
+"#,
+    // Issued invoices
+    r#"
+ALTER TABLE invoices ADD COLUMN issued_at TEXT;
+ALTER TABLE invoices ADD COLUMN sequence INTEGER;
+ALTER TABLE invoices ADD COLUMN previous_hash TEXT;
+ALTER TABLE invoices ADD COLUMN pdf_hash TEXT;
+ALTER TABLE invoices ADD COLUMN hash TEXT;
+"#,
+    // Trash
+    r#"
+ALTER TABLE customers ADD COLUMN deleted_at TEXT;
+ALTER TABLE invoices ADD COLUMN deleted_at TEXT;
+"#,
+    // Journal for undo and redo
+    r#"
+CREATE TABLE journal (
+    uuid TEXT PRIMARY KEY NOT NULL,
+    timestamp TEXT NOT NULL,
+    undone INTEGER NOT NULL,
+    changes TEXT NOT NULL
+);
+"#,
+];
+
+/// Stores the records in a relational SQLite schema.
+///
+/// The items of an invoice and the invoices listed by a customer are stored in their own tables
+/// `items` and `customer_invoices`, ordered by `position`.
+pub struct SqliteBackend {
+    connection: Connection,
+}
+
+impl SqliteBackend {
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)?;
+        connection.pragma_update(None, "foreign_keys", true)?;
+        let backend = Self { connection };
+        // A new database gets the latest schema right away
+        if backend.schema_version()? == 0 {
+            backend.migrate()?;
+        }
+        Ok(backend)
+    }
+
+    fn schema_version(&self) -> Result<u32> {
+        let version = self
+            .connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))?;
+        Ok(version)
+    }
+}
+
+impl Backend for SqliteBackend {
+    fn transaction(&self, f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>) -> Result<()> {
+        let transaction = SqliteTransaction {
+            connection: self.connection.unchecked_transaction()?,
+        };
+        f(&transaction)?;
+        transaction.connection.commit().map_err(sql_error)?;
+        Ok(())
+    }
+
+    fn read_transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>,
+    ) -> Result<()> {
+        // SQLite rejects every write while the connection is query only
+        self.connection.pragma_update(None, "query_only", true)?;
+        let result = self
+            .connection
+            .unchecked_transaction()
+            .map_err(anyhow::Error::from)
+            .and_then(|connection| f(&SqliteTransaction { connection }));
+        self.connection.pragma_update(None, "query_only", false)?;
+        result
+    }
+
+    fn pending_migrations(&self) -> Result<Vec<Migration>> {
+        let version = self.schema_version()? as usize;
+        if version > SCHEMA.len() {
+            bail!("Database schema {version} is newer than this facture. Please update facture");
+        }
+        let migrations = (version..SCHEMA.len())
+            .map(|from| Migration {
+                model: "schema",
+                from: from as u32,
+                to: from as u32 + 1,
+                records: None,
+            })
+            .collect();
+        Ok(migrations)
+    }
+
+    fn migrate(&self) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        let version = self.schema_version()? as usize;
+        for (index, statements) in SCHEMA.iter().enumerate().skip(version) {
+            transaction.execute_batch(statements)?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+struct SqliteTransaction<'c> {
+    connection: rusqlite::Transaction<'c>,
+}
+
+impl SqliteTransaction<'_> {
+    fn table(kind: Kind) -> &'static str {
+        match kind {
+            Kind::Customer => "customers",
+            Kind::Invoice => "invoices",
+            Kind::Business => "business",
+            Kind::Config => "config",
+            Kind::History => "history",
+            Kind::Journal => "journal",
+        }
+    }
+
+    fn key_column(kind: Kind) -> &'static str {
+        match kind {
+            Kind::Customer | Kind::Invoice | Kind::History | Kind::Journal => "uuid",
+            Kind::Business | Kind::Config => "profile",
+        }
+    }
+
+    fn customer(&self, row: &Row) -> Result<Customer> {
+        let uuid: String = row.get("uuid")?;
+        let invoices = self
+            .connection
+            .prepare_cached(
+                "SELECT invoice_uuid FROM customer_invoices
+                 WHERE customer_uuid = ?1 ORDER BY position",
+            )?
+            .query_map([&uuid], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(Customer {
+            uuid,
+            id: row.get("id")?,
+            profile: row.get("profile")?,
+            organization: row.get("organization")?,
+            vat_id: row.get("vat_id")?,
+            contact: Contact {
+                name: row.get("name")?,
+                surname: row.get("surname")?,
+                email: row.get("email")?,
+                phone: row.get("phone")?,
+            },
+            address: address(row)?,
+            invoices,
+            deleted_at: row.get("deleted_at")?,
+        })
+    }
+
+    fn invoice(&self, row: &Row) -> Result<Invoice> {
+        let uuid: String = row.get("uuid")?;
+        let items = self
+            .connection
+            .prepare_cached(
+                "SELECT description, price, quantity FROM items
+                 WHERE invoice_uuid = ?1 ORDER BY position",
+            )?
+            .query_map([&uuid], |row| {
+                Ok(Item {
+                    description: row.get(0)?,
+                    price: row.get(1)?,
+                    quantity: row.get(2)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(Invoice {
+            uuid,
+            id: row.get("id")?,
+            profile: row.get("profile")?,
+            issuing_date: row.get("issuing_date")?,
+            delivery_date: row.get("delivery_date")?,
+            due_days: row.get("due_days")?,
+            payment_date: row.get("payment_date")?,
+            customer: row.get("customer_uuid")?,
+            items,
+            issuance: issuance(row)?,
+            deleted_at: row.get("deleted_at")?,
+        })
+    }
+
+    fn entity(&self, kind: Kind, row: &Row) -> Result<Entity> {
+        let entity = match kind {
+            Kind::Customer => Entity::Customer(self.customer(row)?),
+            Kind::Invoice => Entity::Invoice(self.invoice(row)?),
+            Kind::Business => Entity::Business(Business {
+                profile: row.get("profile")?,
+                name: row.get("name")?,
+                vat_id: row.get("vat_id")?,
+                tax_number: row.get("tax_number")?,
+                vat: row.get("vat")?,
+                small_business: row.get("small_business")?,
+                contact: Contact {
+                    name: row.get("contact_name")?,
+                    surname: row.get("contact_surname")?,
+                    email: row.get("email")?,
+                    phone: row.get("phone")?,
+                },
+                address: address(row)?,
+                payment: Payment {
+                    bank: row.get("bank")?,
+                    iban: row.get("iban")?,
+                    bic: row.get("bic")?,
+                },
+            }),
+            Kind::Config => Entity::Config(Config {
+                profile: row.get("profile")?,
+                customer_template: row.get("customer_template")?,
+                customer_counter: row.get("customer_counter")?,
+                invoice_template: row.get("invoice_template")?,
+                invoice_counter: row.get("invoice_counter")?,
+            }),
+            Kind::History => {
+                let operation: String = row.get("operation")?;
+                let changes: String = row.get("changes")?;
+                Entity::History(HistoryEntry {
+                    uuid: row.get("uuid")?,
+                    timestamp: row.get("timestamp")?,
+                    record_type: row.get("record_type")?,
+                    record: row.get("record")?,
+                    record_id: row.get("record_id")?,
+                    operation: serde_json::from_value(operation.into())?,
+                    changes: serde_json::from_str(&changes)?,
+                })
+            }
+            Kind::Journal => {
+                let changes: String = row.get("changes")?;
+                Entity::Journal(JournalEntry {
+                    uuid: row.get("uuid")?,
+                    timestamp: row.get("timestamp")?,
+                    undone: row.get("undone")?,
+                    changes: serde_json::from_str(&changes)?,
+                })
+            }
+        };
+        Ok(entity)
+    }
+
+    /// Replaces the items of an invoice or the invoices listed by a customer
+    fn write_children(&self, entity: &Entity) -> Result<()> {
+        match entity {
+            Entity::Customer(customer) => {
+                self.connection
+                    .prepare_cached("DELETE FROM customer_invoices WHERE customer_uuid = ?1")?
+                    .execute([&customer.uuid])?;
+                let mut statement = self.connection.prepare_cached(
+                    "INSERT INTO customer_invoices (customer_uuid, position, invoice_uuid)
+                     VALUES (?1, ?2, ?3)",
+                )?;
+                for (position, invoice) in customer.invoices.iter().enumerate() {
+                    statement.execute(params![customer.uuid, position, invoice])?;
+                }
+            }
+            Entity::Invoice(invoice) => {
+                self.connection
+                    .prepare_cached("DELETE FROM items WHERE invoice_uuid = ?1")?
+                    .execute([&invoice.uuid])?;
+                let mut statement = self.connection.prepare_cached(
+                    "INSERT INTO items (invoice_uuid, position, description, price, quantity)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?;
+                for (position, item) in invoice.items.iter().enumerate() {
+                    statement.execute(params![
+                        invoice.uuid,
+                        position,
+                        item.description,
+                        item.price,
+                        item.quantity,
+                    ])?;
+                }
+            }
+            Entity::Business(_) | Entity::Config(_) | Entity::History(_) | Entity::Journal(_) => {}
+        }
+        Ok(())
+    }
+
+    fn query(&self, kind: Kind, filter: &str, value: Option<&str>) -> Result<Vec<Entity>> {
+        let sql = format!(
+            "SELECT * FROM {} {filter} ORDER BY {}",
+            Self::table(kind),
+            Self::key_column(kind)
+        );
+        let mut statement = self.connection.prepare_cached(&sql)?;
+        let mut rows = match value {
+            Some(value) => statement.query([value])?,
+            None => statement.query([])?,
+        };
+        let mut entities = Vec::new();
+        while let Some(row) = rows.next()? {
+            entities.push(self.entity(kind, row)?);
+        }
+        Ok(entities)
+    }
+
+    fn query_one(&self, kind: Kind, column: &str, value: &str) -> Result<Option<Entity>> {
+        let filter = format!("WHERE {column} = ?1");
+        Ok(self.query(kind, &filter, Some(value))?.into_iter().next())
+    }
+}
+
+impl BackendTransaction for SqliteTransaction<'_> {
+    fn create(&self, entity: Entity) -> Result<()> {
+        let columns = columns(&entity)?;
+        let names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+        let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{i}")).collect();
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            Self::table(entity.kind()),
+            names.join(", "),
+            placeholders.join(", ")
+        );
+        self.connection
+            .prepare_cached(&sql)?
+            .execute(params_from_iter(
+                columns.into_iter().map(|(_, value)| value),
+            ))
+            .map_err(sql_error)?;
+        self.write_children(&entity)
+    }
+
+    fn update(&self, key: &str, entity: Entity) -> Result<()> {
+        let kind = entity.kind();
+        let columns = columns(&entity)?;
+        let assignments: Vec<String> = columns
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _))| format!("{name} = ?{}", index + 1))
+            .collect();
+        let sql = format!(
+            "UPDATE {} SET {} WHERE {} = ?{}",
+            Self::table(kind),
+            assignments.join(", "),
+            Self::key_column(kind),
+            columns.len() + 1
+        );
+        let values = columns
+            .into_iter()
+            .map(|(_, value)| value)
+            .chain([Value::Text(key.to_owned())]);
+        let updated = self
+            .connection
+            .prepare_cached(&sql)?
+            .execute(params_from_iter(values))
+            .map_err(sql_error)?;
+        if updated == 0 {
+            bail!("{key} not found");
+        }
+        self.write_children(&entity)
+    }
+
+    fn read(&self, kind: Kind, key: &str) -> Result<Option<Entity>> {
+        self.query_one(kind, Self::key_column(kind), key)
+    }
+
+    fn read_all(&self, kind: Kind) -> Result<Vec<Entity>> {
+        self.query(kind, "", None)
+    }
+
+    fn find_by_id(&self, kind: Kind, id: &str) -> Result<Option<Entity>> {
+        match kind {
+            Kind::Customer | Kind::Invoice => self.query_one(kind, "id", id),
+            Kind::Business | Kind::Config | Kind::History | Kind::Journal => {
+                bail!("A {kind} has no id")
+            }
+        }
+    }
+
+    fn delete(&self, kind: Kind, key: &str) -> Result<()> {
+        let sql = format!(
+            "DELETE FROM {} WHERE {} = ?1",
+            Self::table(kind),
+            Self::key_column(kind)
+        );
+        let deleted = self.connection.execute(&sql, [key])?;
+        if deleted == 0 {
+            bail!("{key} not found");
+        }
+        Ok(())
+    }
+}
+
+/// Columns of the row of a record. Items and listed invoices are stored in their own tables
+fn columns(entity: &Entity) -> Result<Vec<(&'static str, Value)>> {
+    let columns = match entity {
+        Entity::Customer(customer) => {
+            let address = &customer.address;
+            vec![
+                ("uuid", value(&customer.uuid)?),
+                ("id", value(&customer.id)?),
+                ("profile", value(&customer.profile)?),
+                ("organization", value(&customer.organization)?),
+                ("vat_id", value(&customer.vat_id)?),
+                ("name", value(&customer.contact.name)?),
+                ("surname", value(&customer.contact.surname)?),
+                ("email", value(&customer.contact.email)?),
+                ("phone", value(&customer.contact.phone)?),
+                ("country", value(&address.country)?),
+                ("city", value(&address.city)?),
+                ("postal_code", value(&address.postal_code)?),
+                ("street", value(&address.street)?),
+                ("number", value(&address.number)?),
+                ("deleted_at", value(&customer.deleted_at)?),
+            ]
+        }
+        Entity::Invoice(invoice) => {
+            let issuance = invoice.issuance.as_ref();
+            vec![
+                ("uuid", value(&invoice.uuid)?),
+                ("id", value(&invoice.id)?),
+                ("profile", value(&invoice.profile)?),
+                ("customer_uuid", value(&invoice.customer)?),
+                ("issuing_date", value(&invoice.issuing_date)?),
+                ("delivery_date", value(&invoice.delivery_date)?),
+                ("due_days", value(&invoice.due_days)?),
+                ("payment_date", value(&invoice.payment_date)?),
+                (
+                    "issued_at",
+                    value(&issuance.map(|issuance| issuance.issued_at))?,
+                ),
+                (
+                    "sequence",
+                    value(&issuance.map(|issuance| issuance.sequence))?,
+                ),
+                (
+                    "previous_hash",
+                    value(&issuance.and_then(|issuance| issuance.previous_hash.as_ref()))?,
+                ),
+                (
+                    "pdf_hash",
+                    value(&issuance.map(|issuance| &issuance.pdf_hash))?,
+                ),
+                ("hash", value(&issuance.map(|issuance| &issuance.hash))?),
+                ("deleted_at", value(&invoice.deleted_at)?),
+            ]
+        }
+        Entity::Business(business) => {
+            let address = &business.address;
+            vec![
+                ("profile", value(&business.profile)?),
+                ("name", value(&business.name)?),
+                ("vat_id", value(&business.vat_id)?),
+                ("tax_number", value(&business.tax_number)?),
+                ("vat", value(&business.vat)?),
+                ("small_business", value(&business.small_business)?),
+                ("contact_name", value(&business.contact.name)?),
+                ("contact_surname", value(&business.contact.surname)?),
+                ("email", value(&business.contact.email)?),
+                ("phone", value(&business.contact.phone)?),
+                ("country", value(&address.country)?),
+                ("city", value(&address.city)?),
+                ("postal_code", value(&address.postal_code)?),
+                ("street", value(&address.street)?),
+                ("number", value(&address.number)?),
+                ("bank", value(&business.payment.bank)?),
+                ("iban", value(&business.payment.iban)?),
+                ("bic", value(&business.payment.bic)?),
+            ]
+        }
+        Entity::Config(config) => vec![
+            ("profile", value(&config.profile)?),
+            ("customer_template", value(&config.customer_template)?),
+            ("customer_counter", value(&config.customer_counter)?),
+            ("invoice_template", value(&config.invoice_template)?),
+            ("invoice_counter", value(&config.invoice_counter)?),
+        ],
+        Entity::History(entry) => vec![
+            ("uuid", value(&entry.uuid)?),
+            ("timestamp", value(&entry.timestamp)?),
+            ("record_type", value(&entry.record_type)?),
+            ("record", value(&entry.record)?),
+            ("record_id", value(&entry.record_id)?),
+            ("operation", value(&entry.operation.to_string())?),
+            ("changes", value(&serde_json::to_string(&entry.changes)?)?),
+        ],
+        Entity::Journal(entry) => vec![
+            ("uuid", value(&entry.uuid)?),
+            ("timestamp", value(&entry.timestamp)?),
+            ("undone", value(&entry.undone)?),
+            ("changes", value(&serde_json::to_string(&entry.changes)?)?),
+        ],
+    };
+    Ok(columns)
+}
+
+fn value(value: &dyn ToSql) -> Result<Value> {
+    match value.to_sql()? {
+        ToSqlOutput::Owned(value) => Ok(value),
+        ToSqlOutput::Borrowed(value) => Ok(value.into()),
+        _ => bail!("Unsupported SQL value"),
+    }
+}
+
+fn address(row: &Row) -> rusqlite::Result<Address> {
+    Ok(Address {
+        country: row.get("country")?,
+        city: row.get("city")?,
+        postal_code: row.get("postal_code")?,
+        street: row.get("street")?,
+        number: row.get("number")?,
+    })
+}
+
+/// The columns of an issuance are either all set or all empty
+fn issuance(row: &Row) -> rusqlite::Result<Option<Issuance>> {
+    let Some(hash) = row.get("hash")? else {
+        return Ok(None);
+    };
+    Ok(Some(Issuance {
+        issued_at: row.get("issued_at")?,
+        sequence: row.get("sequence")?,
+        previous_hash: row.get("previous_hash")?,
+        pdf_hash: row.get("pdf_hash")?,
+        hash,
+    }))
+}
+
+/// Turns violated constraints into readable errors
+fn sql_error(error: rusqlite::Error) -> anyhow::Error {
+    match error.sqlite_error() {
+        Some(failure) if failure.code == ErrorCode::ConstraintViolation => {
+            match failure.extended_code {
+                rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE
+                | rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY => {
+                    anyhow!("The id is already used by another record")
+                }
+                rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY => {
+                    anyhow!("A record references a customer or invoice that does not exist")
+                }
+                _ => error.into(),
+            }
+        }
+        _ => error.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::DEFAULT_PROFILE;
+
+    fn open() -> (tempfile::TempDir, SqliteBackend) {
+        let folder = tempfile::tempdir().unwrap();
+        let backend = SqliteBackend::open(&folder.path().join("facture.sqlite")).unwrap();
+        (folder, backend)
+    }
+
+    fn write(
+        backend: &SqliteBackend,
+        f: impl Fn(&dyn BackendTransaction) -> Result<()>,
+    ) -> Result<()> {
+        backend.transaction(&mut |transaction| f(transaction))
+    }
+
+    fn read(backend: &SqliteBackend, kind: Kind, key: &str) -> Option<Entity> {
+        let mut entity = None;
+        backend
+            .read_transaction(&mut |transaction| {
+                entity = transaction.read(kind, key)?;
+                Ok(())
+            })
+            .unwrap();
+        entity
+    }
+
+    fn stored_customer(backend: &SqliteBackend, key: &str) -> Customer {
+        match read(backend, Kind::Customer, key) {
+            Some(Entity::Customer(customer)) => customer,
+            entity => panic!("Expected a customer, got {entity:?}"),
+        }
+    }
+
+    fn stored_invoice(backend: &SqliteBackend, key: &str) -> Invoice {
+        match read(backend, Kind::Invoice, key) {
+            Some(Entity::Invoice(invoice)) => invoice,
+            entity => panic!("Expected an invoice, got {entity:?}"),
+        }
+    }
+
+    #[test]
+    fn stores_customers_with_their_listed_invoices() {
+        let (_folder, backend) = open();
+        let mut customer = Customer::new_with_uuid("K0001".to_owned(), None);
+        customer.add_invoice("b");
+        customer.add_invoice("a");
+        write(&backend, |transaction| {
+            transaction.create(Entity::Customer(customer.clone()))
+        })
+        .unwrap();
+        assert_eq!(
+            stored_customer(&backend, &customer.uuid).invoices,
+            ["b", "a"]
+        );
+
+        customer.organization = "ACME".to_owned();
+        customer.remove_invoice("b");
+        write(&backend, |transaction| {
+            transaction.update(&customer.uuid, Entity::Customer(customer.clone()))
+        })
+        .unwrap();
+        let stored = stored_customer(&backend, &customer.uuid);
+        assert_eq!(stored.organization, "ACME");
+        assert_eq!(stored.invoices, ["a"]);
+
+        write(&backend, |transaction| {
+            transaction.delete(Kind::Customer, &customer.uuid)
+        })
+        .unwrap();
+        assert!(read(&backend, Kind::Customer, &customer.uuid).is_none());
+        let listed: u32 = backend
+            .connection
+            .query_row("SELECT COUNT(*) FROM customer_invoices", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(listed, 0);
+    }
+
+    #[test]
+    fn updates_invoices_in_place() {
+        let (_folder, backend) = open();
+        let customer = Customer::new_with_uuid("K0001".to_owned(), None);
+        let mut invoice = Invoice {
+            customer: customer.uuid.clone(),
+            items: vec![Item {
+                description: "Consulting".to_owned(),
+                price: 100.0,
+                quantity: Some(2),
+            }],
+            ..Invoice::new_with_uuid("R1".to_owned(), DEFAULT_PROFILE)
+        };
+        write(&backend, |transaction| {
+            transaction.create(Entity::Customer(customer.clone()))?;
+            transaction.create(Entity::Invoice(invoice.clone()))
+        })
+        .unwrap();
+
+        invoice.due_days = 14;
+        invoice.items[0].price = 120.0;
+        invoice.items.push(Item {
+            description: "Travel".to_owned(),
+            price: 30.0,
+            quantity: None,
+        });
+        write(&backend, |transaction| {
+            transaction.update(&invoice.uuid, Entity::Invoice(invoice.clone()))
+        })
+        .unwrap();
+        let stored = stored_invoice(&backend, &invoice.uuid);
+        assert_eq!(stored.due_days, 14);
+        let items: Vec<(String, f32)> = stored
+            .items
+            .into_iter()
+            .map(|item| (item.description, item.price))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("Consulting".to_owned(), 120.0),
+                ("Travel".to_owned(), 30.0)
+            ]
+        );
+
+        let missing = write(&backend, |transaction| {
+            transaction.update("missing", Entity::Invoice(invoice.clone()))
+        });
+        assert_eq!(missing.unwrap_err().to_string(), "missing not found");
+    }
+
+    #[test]
+    fn rejects_duplicate_ids_and_missing_customers() {
+        let (_folder, backend) = open();
+        let customer = Customer::new_with_uuid("K0001".to_owned(), None);
+        write(&backend, |transaction| {
+            transaction.create(Entity::Customer(customer.clone()))
+        })
+        .unwrap();
+
+        let duplicate = Customer::new_with_uuid("K0001".to_owned(), None);
+        let result = write(&backend, |transaction| {
+            transaction.create(Entity::Customer(duplicate.clone()))
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The id is already used by another record"
+        );
+
+        let orphan = Invoice {
+            customer: "missing".to_owned(),
+            ..Invoice::new_with_uuid("R1".to_owned(), DEFAULT_PROFILE)
+        };
+        let result = write(&backend, |transaction| {
+            transaction.create(Entity::Invoice(orphan.clone()))
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "A record references a customer or invoice that does not exist"
+        );
+        assert!(read(&backend, Kind::Invoice, &orphan.uuid).is_none());
+    }
+}
@@ -0,0 +1,756 @@
+use super::{Backend, BackendTransaction, Entity, Kind};
+use crate::database::{
+    backup::ModelVersions,
+    migration::Migration,
+    models::{Business, Config, Customer, Invoice},
+    YamlAble,
+};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+const INDEX_NAME: &str = "index.yaml";
+/// Lists the files of a commit that is being moved into place
+const COMMIT_NAME: &str = "commit.yaml";
+const CONFIG_FOLDER: &str = "config";
+const BUSINESS_FOLDER: &str = "business";
+const CUSTOMERS_FOLDER: &str = "customers";
+const INVOICES_FOLDER: &str = "invoices";
+const HISTORY_FOLDER: &str = "history";
+const JOURNAL_FOLDER: &str = "journal";
+const FOLDERS: [&str; 6] = [
+    CONFIG_FOLDER,
+    BUSINESS_FOLDER,
+    CUSTOMERS_FOLDER,
+    INVOICES_FOLDER,
+    HISTORY_FOLDER,
+    JOURNAL_FOLDER,
+];
+/// Files of the config and business before profiles were introduced. They are moved into their
+/// folders by the migration
+const LEGACY_CONFIG_NAME: &str = "config.yaml";
+const LEGACY_BUSINESS_NAME: &str = "business.yaml";
+
+/// Lookup table of all files written by facture
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Index {
+    /// Model versions the files were written with
+    models: ModelVersions,
+    /// Relative path of every file
+    records: BTreeMap<String, IndexEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct IndexEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    /// SHA-256 of the file content as last written by facture
+    hash: String,
+}
+
+/// A commit whose files were written next to their destination but not moved into place yet.
+/// An interrupted commit is completed the next time the database is opened
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct PendingCommit {
+    written: Vec<String>,
+    removed: Vec<String>,
+    index: Index,
+}
+
+impl PendingCommit {
+    /// Moves the written files into place, removes the deleted ones and writes the index last.
+    /// Applying a commit again finishes what an interrupted run left over
+    fn apply(&self, root: &Path) -> Result<()> {
+        for path in &self.written {
+            let file = root.join(path);
+            let temporary = temporary_path(&file);
+            if temporary.exists() {
+                fs::rename(&temporary, &file)?;
+            }
+        }
+        for path in &self.removed {
+            let file = root.join(path);
+            if file.exists() {
+                fs::remove_file(file)?;
+            }
+        }
+        write_file(&root.join(INDEX_NAME), &serde_yml::to_string(&self.index)?)?;
+        fs::remove_file(root.join(COMMIT_NAME))?;
+        Ok(())
+    }
+}
+
+/// Reads only the id of a record, independent of its model version
+#[derive(Deserialize)]
+struct IdOnly {
+    id: Option<String>,
+}
+
+/// Stores every record as its own YAML file so the records can be versioned with git.
+///
+/// ```text
+/// index.yaml
+/// config/<profile>.yaml
+/// business/<profile>.yaml
+/// customers/<uuid>.yaml
+/// invoices/<uuid>.yaml
+/// history/<uuid>.yaml
+/// journal/<uuid>.yaml
+/// ```
+///
+/// `index.yaml` maps the ids to the files and keeps the hash of every file facture wrote. A file
+/// whose hash differs was edited outside facture and is not overwritten until the change is
+/// accepted with `facture db reindex`.
+///
+/// A commit writes every changed file next to its destination first and lists them in
+/// `commit.yaml`. Only then are the files moved into place and the index written. If facture is
+/// interrupted in between, the commit is completed when the database is opened again.
+pub struct YamlBackend {
+    root: PathBuf,
+}
+
+impl YamlBackend {
+    pub fn open(path: &Path) -> Result<Self> {
+        for folder in FOLDERS {
+            fs::create_dir_all(path.join(folder))?;
+        }
+        let backend = Self {
+            root: path.to_path_buf(),
+        };
+        let pending = backend.root.join(COMMIT_NAME);
+        if pending.exists() {
+            let commit: PendingCommit = serde_yml::from_str(&fs::read_to_string(&pending)?)
+                .map_err(|error| anyhow!("{COMMIT_NAME} is invalid: {error}"))?;
+            commit.apply(&backend.root)?;
+        }
+        // Files of a commit that was interrupted before it was listed
+        for folder in FOLDERS {
+            remove_temporary_files(&backend.root.join(folder))?;
+        }
+        if !backend.root.join(INDEX_NAME).exists() {
+            backend.reindex()?;
+        }
+        Ok(backend)
+    }
+
+    fn load_index(&self) -> Result<Index> {
+        let yaml = fs::read_to_string(self.root.join(INDEX_NAME))?;
+        serde_yml::from_str(&yaml).map_err(|error| {
+            anyhow!("{INDEX_NAME} is invalid: {error}. Run `facture db reindex` to rebuild it")
+        })
+    }
+
+    fn write_index(&self, index: &Index) -> Result<()> {
+        write_file(&self.root.join(INDEX_NAME), &serde_yml::to_string(index)?)
+    }
+
+    /// Relative paths of all record files on disk
+    fn files(&self) -> Result<BTreeSet<String>> {
+        let mut files = BTreeSet::new();
+        for name in [LEGACY_CONFIG_NAME, LEGACY_BUSINESS_NAME] {
+            if self.root.join(name).exists() {
+                files.insert(name.to_owned());
+            }
+        }
+        for folder in FOLDERS {
+            for key in list_folder(&self.root.join(folder))? {
+                files.insert(format!("{folder}/{key}.yaml"));
+            }
+        }
+        Ok(files)
+    }
+}
+
+impl Backend for YamlBackend {
+    fn transaction(&self, f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>) -> Result<()> {
+        let transaction = YamlTransaction {
+            root: &self.root,
+            index: self.load_index()?,
+            read_only: false,
+            changes: RefCell::default(),
+        };
+        f(&transaction)?;
+        if transaction.changes.borrow().is_empty() {
+            return Ok(());
+        }
+        transaction.commit()
+    }
+
+    fn read_transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn BackendTransaction) -> Result<()>,
+    ) -> Result<()> {
+        f(&YamlTransaction {
+            root: &self.root,
+            index: self.load_index()?,
+            read_only: true,
+            changes: RefCell::default(),
+        })
+    }
+
+    fn pending_migrations(&self) -> Result<Vec<Migration>> {
+        let index = self.load_index()?;
+        let (old, new) = (&index.models, ModelVersions::default());
+        let count = |prefix: &str| {
+            index
+                .records
+                .keys()
+                .filter(|path| path.starts_with(prefix))
+                .count() as u64
+        };
+        let migrations = [
+            ("customer", old.customer, new.customer, CUSTOMERS_FOLDER),
+            ("invoice", old.invoice, new.invoice, INVOICES_FOLDER),
+            ("business", old.business, new.business, BUSINESS_FOLDER),
+            ("config", old.config, new.config, CONFIG_FOLDER),
+            ("history", old.history, new.history, HISTORY_FOLDER),
+            ("journal", old.journal, new.journal, JOURNAL_FOLDER),
+        ]
+        .into_iter()
+        .filter(|(_, from, to, _)| from < to)
+        .map(|(model, from, to, prefix)| Migration {
+            model,
+            from,
+            to,
+            records: Some(count(prefix)),
+        })
+        .collect();
+        Ok(migrations)
+    }
+
+    fn migrate(&self) -> Result<()> {
+        let index = self.load_index()?;
+        let versions = &index.models;
+        let transaction = YamlTransaction {
+            root: &self.root,
+            index: Index::default(),
+            read_only: false,
+            changes: RefCell::default(),
+        };
+        for path in self.files()? {
+            let yaml = fs::read_to_string(self.root.join(&path))?;
+            let kind = Kind::of_path(&path);
+            let entity = Entity::upgrade(kind, versions.of(kind), &yaml)
+                .map_err(|error| anyhow!("{path}: {error}"))?;
+            // Records that changed their key move to a new file
+            let target = YamlTransaction::path(entity.kind(), entity.key());
+            if target != path {
+                transaction.stage(path, None)?;
+            }
+            transaction.stage(target, Some(entity))?;
+        }
+        // The new index starts with the current model versions
+        transaction.commit()
+    }
+
+    fn reindex(&self) -> Result<Vec<String>> {
+        let old = self.load_index().unwrap_or_default();
+        let mut index = Index {
+            models: old.models,
+            records: BTreeMap::new(),
+        };
+        let mut changes = Vec::new();
+        for path in self.files()? {
+            let yaml = fs::read_to_string(self.root.join(&path))?;
+            let IdOnly { id } =
+                serde_yml::from_str(&yaml).map_err(|error| anyhow!("{path}: {error}"))?;
+            let entry = IndexEntry {
+                id,
+                hash: hash(&yaml),
+            };
+            match old.records.get(&path) {
+                None => changes.push(format!("Added {path}")),
+                Some(old) if *old != entry => changes.push(format!("Changed {path}")),
+                Some(_) => {}
+            }
+            index.records.insert(path, entry);
+        }
+        for path in old.records.keys() {
+            if !index.records.contains_key(path) {
+                changes.push(format!("Removed {path}"));
+            }
+        }
+        self.write_index(&index)?;
+        Ok(changes)
+    }
+}
+
+impl Kind {
+    fn of_path(path: &str) -> Kind {
+        match path.split_once('/') {
+            Some((CUSTOMERS_FOLDER, _)) => Kind::Customer,
+            Some((INVOICES_FOLDER, _)) => Kind::Invoice,
+            Some((BUSINESS_FOLDER, _)) => Kind::Business,
+            Some((HISTORY_FOLDER, _)) => Kind::History,
+            Some((JOURNAL_FOLDER, _)) => Kind::Journal,
+            _ if path == LEGACY_BUSINESS_NAME => Kind::Business,
+            _ => Kind::Config,
+        }
+    }
+}
+
+struct YamlTransaction<'b> {
+    root: &'b Path,
+    index: Index,
+    read_only: bool,
+    /// Files written by the transaction. `None` marks a deleted file
+    changes: RefCell<BTreeMap<String, Option<StagedFile>>>,
+}
+
+struct StagedFile {
+    id: Option<String>,
+    yaml: String,
+}
+
+impl YamlTransaction<'_> {
+    fn folder(kind: Kind) -> &'static str {
+        match kind {
+            Kind::Customer => CUSTOMERS_FOLDER,
+            Kind::Invoice => INVOICES_FOLDER,
+            Kind::Business => BUSINESS_FOLDER,
+            Kind::Config => CONFIG_FOLDER,
+            Kind::History => HISTORY_FOLDER,
+            Kind::Journal => JOURNAL_FOLDER,
+        }
+    }
+
+    fn path(kind: Kind, key: &str) -> String {
+        format!("{}/{key}.yaml", Self::folder(kind))
+    }
+
+    fn content(&self, path: &str) -> Result<Option<String>> {
+        if let Some(change) = self.changes.borrow().get(path) {
+            return Ok(change.as_ref().map(|file| file.yaml.clone()));
+        }
+        let file = self.root.join(path);
+        if file.exists() {
+            Ok(Some(fs::read_to_string(file)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn load(&self, kind: Kind, path: &str) -> Result<Option<Entity>> {
+        let Some(yaml) = self.content(path)? else {
+            return Ok(None);
+        };
+        let parse = || -> Result<Entity> {
+            let entity = match kind {
+                Kind::Customer => Entity::Customer(Customer::from_yaml(&yaml)?),
+                Kind::Invoice => Entity::Invoice(Invoice::from_yaml(&yaml)?),
+                Kind::Business => Entity::Business(Business::from_yaml(&yaml)?),
+                Kind::Config => Entity::Config(Config::from_yaml(&yaml)?),
+                Kind::History => Entity::History(serde_yml::from_str(&yaml)?),
+                Kind::Journal => Entity::Journal(serde_yml::from_str(&yaml)?),
+            };
+            Ok(entity)
+        };
+        parse()
+            .map(Some)
+            .map_err(|error| anyhow!("{path}: {error}"))
+    }
+
+    /// Keys of all records of a kind including the changes of this transaction
+    fn keys(&self, kind: Kind) -> Result<BTreeSet<String>> {
+        let folder = Self::folder(kind);
+        let mut keys = list_folder(&self.root.join(folder))?;
+        for (path, change) in self.changes.borrow().iter() {
+            let Some(key) = path
+                .strip_prefix(&format!("{folder}/"))
+                .and_then(|name| name.strip_suffix(".yaml"))
+            else {
+                continue;
+            };
+            match change {
+                Some(_) => keys.insert(key.to_owned()),
+                None => keys.remove(key),
+            };
+        }
+        Ok(keys)
+    }
+
+    /// Fails if the file was changed outside facture since it was last written
+    fn check_conflict(&self, path: &str) -> Result<()> {
+        if self.changes.borrow().contains_key(path) {
+            return Ok(());
+        }
+        let file = self.root.join(path);
+        if !file.exists() {
+            return Ok(());
+        }
+        let current = hash(&fs::read_to_string(file)?);
+        match self.index.records.get(path) {
+            Some(entry) if entry.hash == current => Ok(()),
+            _ => bail!(
+                "{path} was changed outside facture. Review the change and run `facture db reindex` to accept it"
+            ),
+        }
+    }
+
+    fn check_unique_id(&self, entity: &Entity, key: &str) -> Result<()> {
+        let id = match entity {
+            Entity::Customer(customer) => &customer.id,
+            Entity::Invoice(invoice) => &invoice.id,
+            Entity::Business(_) | Entity::Config(_) | Entity::History(_) | Entity::Journal(_) => {
+                return Ok(())
+            }
+        };
+        let path = Self::path(entity.kind(), key);
+        if self
+            .find_path(entity.kind(), id)?
+            .is_some_and(|other| other != path)
+        {
+            bail!("The id is already used by another record");
+        }
+        Ok(())
+    }
+
+    /// Finds the file of a record by its id. Files written by this transaction or added outside
+    /// facture are not part of the index and are read instead.
+    fn find_path(&self, kind: Kind, id: &str) -> Result<Option<String>> {
+        let candidates: BTreeSet<String> = self
+            .keys(kind)?
+            .into_iter()
+            .map(|key| Self::path(kind, &key))
+            .filter(|path| {
+                let changed = self.changes.borrow().contains_key(path);
+                match self.index.records.get(path) {
+                    Some(entry) if !changed => entry.id.as_deref() == Some(id),
+                    _ => true,
+                }
+            })
+            .collect();
+        for path in candidates {
+            let Some(yaml) = self.content(&path)? else {
+                continue;
+            };
+            let IdOnly { id: found } =
+                serde_yml::from_str(&yaml).map_err(|error| anyhow!("{path}: {error}"))?;
+            if found.as_deref() == Some(id) {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+
+    fn stage(&self, path: String, entity: Option<Entity>) -> Result<()> {
+        if self.read_only {
+            bail!("Can not write in a read-only transaction");
+        }
+        let change = match entity {
+            Some(entity) => {
+                let (id, yaml) = match entity {
+                    Entity::Customer(customer) => (Some(customer.id.clone()), customer.to_yaml()?),
+                    Entity::Invoice(invoice) => (Some(invoice.id.clone()), invoice.to_yaml()?),
+                    Entity::Business(business) => (None, business.to_yaml()?),
+                    Entity::Config(config) => (None, config.to_yaml()?),
+                    Entity::History(entry) => (None, serde_yml::to_string(&entry)?),
+                    Entity::Journal(entry) => (None, serde_yml::to_string(&entry)?),
+                };
+                Some(StagedFile { id, yaml })
+            }
+            None => None,
+        };
+        self.changes.borrow_mut().insert(path, change);
+        Ok(())
+    }
+
+    /// Writes every changed file next to its destination and lists them in the pending commit
+    fn prepare(self) -> Result<PendingCommit> {
+        let mut commit = PendingCommit {
+            index: self.index,
+            ..PendingCommit::default()
+        };
+        for (path, change) in self.changes.into_inner() {
+            match change {
+                Some(StagedFile { id, yaml }) => {
+                    fs::write(temporary_path(&self.root.join(&path)), &yaml)?;
+                    let hash = hash(&yaml);
+                    commit
+                        .index
+                        .records
+                        .insert(path.clone(), IndexEntry { id, hash });
+                    commit.written.push(path);
+                }
+                None => {
+                    commit.index.records.remove(&path);
+                    commit.removed.push(path);
+                }
+            }
+        }
+        write_file(
+            &self.root.join(COMMIT_NAME),
+            &serde_yml::to_string(&commit)?,
+        )?;
+        Ok(commit)
+    }
+
+    /// Writes all changes to disk together with the updated index
+    fn commit(self) -> Result<()> {
+        let root = self.root;
+        self.prepare()?.apply(root)
+    }
+}
+
+impl BackendTransaction for YamlTransaction<'_> {
+    fn create(&self, entity: Entity) -> Result<()> {
+        let key = entity.key().to_owned();
+        let path = Self::path(entity.kind(), &key);
+        if self.content(&path)?.is_some() {
+            bail!("The id is already used by another record");
+        }
+        self.check_unique_id(&entity, &key)?;
+        self.stage(path, Some(entity))
+    }
+
+    fn update(&self, key: &str, entity: Entity) -> Result<()> {
+        let path = Self::path(entity.kind(), key);
+        if self.content(&path)?.is_none() {
+            bail!("{key} not found");
+        }
+        self.check_conflict(&path)?;
+        self.check_unique_id(&entity, key)?;
+        self.stage(path, Some(entity))
+    }
+
+    fn read(&self, kind: Kind, key: &str) -> Result<Option<Entity>> {
+        self.load(kind, &Self::path(kind, key))
+    }
+
+    fn read_all(&self, kind: Kind) -> Result<Vec<Entity>> {
+        let mut entities = Vec::new();
+        for key in self.keys(kind)? {
+            entities.extend(self.load(kind, &Self::path(kind, &key))?);
+        }
+        Ok(entities)
+    }
+
+    fn find_by_id(&self, kind: Kind, id: &str) -> Result<Option<Entity>> {
+        match kind {
+            Kind::Customer | Kind::Invoice => match self.find_path(kind, id)? {
+                Some(path) => self.load(kind, &path),
+                None => Ok(None),
+            },
+            Kind::Business | Kind::Config | Kind::History | Kind::Journal => {
+                bail!("A {kind} has no id")
+            }
+        }
+    }
+
+    fn delete(&self, kind: Kind, key: &str) -> Result<()> {
+        let path = Self::path(kind, key);
+        if self.content(&path)?.is_none() {
+            bail!("{key} not found");
+        }
+        self.check_conflict(&path)?;
+        self.stage(path, None)
+    }
+}
+
+/// Returns the file stems of all YAML files in a folder
+fn list_folder(folder: &Path) -> Result<BTreeSet<String>> {
+    let mut keys = BTreeSet::new();
+    if !folder.is_dir() {
+        return Ok(keys);
+    }
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "yaml")
+        {
+            if let Some(stem) = path.file_stem() {
+                keys.insert(stem.to_string_lossy().to_string());
+            }
+        }
+    }
+    Ok(keys)
+}
+
+/// Writes the file next to its destination first so a crash never leaves half a file behind
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    let temporary = temporary_path(path);
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    PathBuf::from(temporary)
+}
+
+fn remove_temporary_files(folder: &Path) -> Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "tmp") {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open() -> (tempfile::TempDir, YamlBackend) {
+        let folder = tempfile::tempdir().unwrap();
+        let backend = YamlBackend::open(&folder.path().join("facture-data")).unwrap();
+        (folder, backend)
+    }
+
+    fn write(
+        backend: &YamlBackend,
+        f: impl Fn(&dyn BackendTransaction) -> Result<()>,
+    ) -> Result<()> {
+        backend.transaction(&mut |transaction| f(transaction))
+    }
+
+    fn stored_customer(backend: &YamlBackend, key: &str) -> Option<Customer> {
+        let mut customer = None;
+        backend
+            .read_transaction(&mut |transaction| {
+                customer = match transaction.read(Kind::Customer, key)? {
+                    Some(Entity::Customer(customer)) => Some(customer),
+                    _ => None,
+                };
+                Ok(())
+            })
+            .unwrap();
+        customer
+    }
+
+    fn find(backend: &YamlBackend, id: &str) -> Option<Entity> {
+        let mut entity = None;
+        backend
+            .read_transaction(&mut |transaction| {
+                entity = transaction.find_by_id(Kind::Customer, id)?;
+                Ok(())
+            })
+            .unwrap();
+        entity
+    }
+
+    #[test]
+    fn creates_updates_and_deletes_files() {
+        let (_folder, backend) = open();
+        let mut customer = Customer::new_with_uuid("K0001".to_owned(), None);
+        write(&backend, |transaction| {
+            transaction.create(Entity::Customer(customer.clone()))
+        })
+        .unwrap();
+        let file = backend
+            .root
+            .join(format!("customers/{}.yaml", customer.uuid));
+        assert!(file.is_file());
+        assert!(find(&backend, "K0001").is_some());
+
+        customer.id = "K0002".to_owned();
+        write(&backend, |transaction| {
+            transaction.update(&customer.uuid, Entity::Customer(customer.clone()))
+        })
+        .unwrap();
+        assert_eq!(
+            stored_customer(&backend, &customer.uuid).unwrap().id,
+            "K0002"
+        );
+        assert!(find(&backend, "K0001").is_none());
+        assert!(find(&backend, "K0002").is_some());
+
+        let duplicate = Customer::new_with_uuid("K0002".to_owned(), None);
+        let result = write(&backend, |transaction| {
+            transaction.create(Entity::Customer(duplicate.clone()))
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "The id is already used by another record"
+        );
+
+        write(&backend, |transaction| {
+            transaction.delete(Kind::Customer, &customer.uuid)
+        })
+        .unwrap();
+        assert!(!file.exists());
+        assert!(stored_customer(&backend, &customer.uuid).is_none());
+        assert!(backend.load_index().unwrap().records.is_empty());
+    }
+
+    #[test]
+    fn refuses_to_overwrite_files_changed_outside_facture() {
+        let (_folder, backend) = open();
+        let customer = Customer::new_with_uuid("K0001".to_owned(), None);
+        write(&backend, |transaction| {
+            transaction.create(Entity::Customer(customer.clone()))
+        })
+        .unwrap();
+        let path = format!("customers/{}.yaml", customer.uuid);
+        let file = backend.root.join(&path);
+        let edited = fs::read_to_string(&file)
+            .unwrap()
+            .replace("organization: ''", "organization: ACME");
+        fs::write(&file, edited).unwrap();
+
+        let result = write(&backend, |transaction| {
+            transaction.update(&customer.uuid, Entity::Customer(customer.clone()))
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!(
+                "{path} was changed outside facture. Review the change and run `facture db reindex` to accept it"
+            )
+        );
+        assert_eq!(backend.reindex().unwrap(), [format!("Changed {path}")]);
+        assert_eq!(
+            stored_customer(&backend, &customer.uuid)
+                .unwrap()
+                .organization,
+            "ACME"
+        );
+        write(&backend, |transaction| {
+            transaction.update(&customer.uuid, Entity::Customer(customer.clone()))
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn completes_interrupted_commits_on_open() {
+        let (folder, backend) = open();
+        let kept = Customer::new_with_uuid("K0001".to_owned(), None);
+        write(&backend, |transaction| {
+            transaction.create(Entity::Customer(kept.clone()))
+        })
+        .unwrap();
+
+        // Stop after the files were listed, before anything was moved into place
+        let added = Customer::new_with_uuid("K0002".to_owned(), None);
+        let transaction = YamlTransaction {
+            root: &backend.root,
+            index: backend.load_index().unwrap(),
+            read_only: false,
+            changes: RefCell::default(),
+        };
+        transaction.delete(Kind::Customer, &kept.uuid).unwrap();
+        transaction.create(Entity::Customer(added.clone())).unwrap();
+        transaction.prepare().unwrap();
+        assert!(stored_customer(&backend, &kept.uuid).is_some());
+        assert!(stored_customer(&backend, &added.uuid).is_none());
+
+        let backend = YamlBackend::open(&folder.path().join("facture-data")).unwrap();
+        assert!(!backend.root.join(COMMIT_NAME).exists());
+        assert!(stored_customer(&backend, &kept.uuid).is_none());
+        assert_eq!(stored_customer(&backend, &added.uuid).unwrap().id, "K0002");
+        assert!(backend.reindex().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,413 @@
+use super::{
+    backend::Kind,
+    models::{Business, Config, Customer, HistoryEntry, Invoice, JournalEntry, Upgrade},
+    FactureDatabase, YamlAble,
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::{Local, NaiveDateTime};
+use native_model::Model;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::Read,
+    path::Path,
+};
+
+/// Version of the archive layout. Bump it whenever files are added, moved or renamed
+const ARCHIVE_FORMAT: u32 = 1;
+const MANIFEST_NAME: &str = "manifest.yaml";
+const CONFIG_FOLDER: &str = "config";
+const BUSINESS_FOLDER: &str = "business";
+const CUSTOMERS_FOLDER: &str = "customers";
+const INVOICES_FOLDER: &str = "invoices";
+const HISTORY_FOLDER: &str = "history";
+const JOURNAL_FOLDER: &str = "journal";
+const PDFS_FOLDER: &str = "pdfs";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Manifest {
+    pub format: u32,
+    pub facture_version: String,
+    pub created: NaiveDateTime,
+    pub models: ModelVersions,
+}
+
+/// Model versions the records of an archive were written with
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ModelVersions {
+    pub customer: u32,
+    pub invoice: u32,
+    pub business: u32,
+    pub config: u32,
+    pub history: u32,
+    pub journal: u32,
+}
+
+impl Default for ModelVersions {
+    fn default() -> Self {
+        Self {
+            customer: Customer::native_model_version(),
+            invoice: Invoice::native_model_version(),
+            business: Business::native_model_version(),
+            config: Config::native_model_version(),
+            history: HistoryEntry::native_model_version(),
+            journal: JournalEntry::native_model_version(),
+        }
+    }
+}
+
+impl ModelVersions {
+    pub fn of(&self, kind: Kind) -> u32 {
+        match kind {
+            Kind::Customer => self.customer,
+            Kind::Invoice => self.invoice,
+            Kind::Business => self.business,
+            Kind::Config => self.config,
+            Kind::History => self.history,
+            Kind::Journal => self.journal,
+        }
+    }
+}
+
+/// A portable copy of all records and rendered invoices.
+///
+/// It is stored as an uncompressed tar archive of YAML files so it can be inspected with standard
+/// tools.
+pub struct Backup {
+    pub manifest: Manifest,
+    /// Config and business of every profile
+    pub configs: Vec<Config>,
+    pub businesses: Vec<Business>,
+    pub customers: Vec<Customer>,
+    pub invoices: Vec<Invoice>,
+    pub history: Vec<HistoryEntry>,
+    /// The operations `facture undo` and `facture redo` apply to
+    pub journal: Vec<JournalEntry>,
+    /// File names and contents of the rendered pdfs
+    pub pdfs: Vec<(String, Vec<u8>)>,
+}
+
+impl Backup {
+    pub fn from_database(database: &FactureDatabase, pdf_folder: &Path) -> Result<Self> {
+        let mut pdfs = Vec::new();
+        if pdf_folder.is_dir() {
+            for entry in fs::read_dir(pdf_folder)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|extension| extension == "pdf") {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    pdfs.push((name.to_string(), fs::read(&path)?));
+                }
+            }
+        }
+
+        let backup = Self {
+            manifest: Manifest {
+                format: ARCHIVE_FORMAT,
+                facture_version: env!("CARGO_PKG_VERSION").to_owned(),
+                created: Local::now().naive_local(),
+                models: ModelVersions::default(),
+            },
+            configs: database.read_all()?,
+            businesses: database.read_all()?,
+            customers: database.read_all()?,
+            invoices: database.read_all()?,
+            history: database.read_all()?,
+            journal: database.read_all()?,
+            pdfs,
+        };
+        Ok(backup)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut archive = tar::Builder::new(File::create(path)?);
+        let mut append = |name: &str, content: &[u8]| -> Result<()> {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(self.manifest.created.and_utc().timestamp().max(0) as u64);
+            header.set_cksum();
+            archive.append_data(&mut header, name, content)?;
+            Ok(())
+        };
+
+        append(
+            MANIFEST_NAME,
+            serde_yml::to_string(&self.manifest)?.as_bytes(),
+        )?;
+        for config in &self.configs {
+            let name = format!("{CONFIG_FOLDER}/{}.yaml", config.profile);
+            append(&name, config.to_yaml()?.as_bytes())?;
+        }
+        for business in &self.businesses {
+            let name = format!("{BUSINESS_FOLDER}/{}.yaml", business.profile);
+            append(&name, business.to_yaml()?.as_bytes())?;
+        }
+        for customer in &self.customers {
+            let name = format!("{CUSTOMERS_FOLDER}/{}.yaml", customer.uuid);
+            append(&name, customer.to_yaml()?.as_bytes())?;
+        }
+        for invoice in &self.invoices {
+            let name = format!("{INVOICES_FOLDER}/{}.yaml", invoice.uuid);
+            append(&name, invoice.to_yaml()?.as_bytes())?;
+        }
+        for entry in &self.history {
+            let name = format!("{HISTORY_FOLDER}/{}.yaml", entry.uuid);
+            append(&name, serde_yml::to_string(entry)?.as_bytes())?;
+        }
+        for entry in &self.journal {
+            let name = format!("{JOURNAL_FOLDER}/{}.yaml", entry.uuid);
+            append(&name, serde_yml::to_string(entry)?.as_bytes())?;
+        }
+        for (name, content) in &self.pdfs {
+            append(&format!("{PDFS_FOLDER}/{name}"), content)?;
+        }
+
+        archive.into_inner()?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut archive = tar::Archive::new(File::open(path)?);
+        let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = entry.path()?.to_string_lossy().to_string();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            files.insert(name, content);
+        }
+
+        let text = |name: &str, content: &[u8]| {
+            String::from_utf8(content.to_vec()).map_err(|_| anyhow!("{name} is not valid UTF-8"))
+        };
+        let manifest = files
+            .get(MANIFEST_NAME)
+            .ok_or_else(|| anyhow!("Archive has no {MANIFEST_NAME}"))?;
+        let manifest: Manifest = serde_yml::from_str(&text(MANIFEST_NAME, manifest)?)?;
+        if manifest.format > ARCHIVE_FORMAT {
+            bail!(
+                "Archive format {} is newer than the supported format {ARCHIVE_FORMAT}. Please update facture",
+                manifest.format
+            );
+        }
+
+        let mut backup = Self {
+            manifest,
+            configs: Vec::new(),
+            businesses: Vec::new(),
+            customers: Vec::new(),
+            invoices: Vec::new(),
+            history: Vec::new(),
+            journal: Vec::new(),
+            pdfs: Vec::new(),
+        };
+        let versions = &backup.manifest.models;
+        let mut names: Vec<_> = files.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            let content = &files[&name];
+            let load_error = |error: anyhow::Error| anyhow!("{name}: {error}");
+            match name.split_once('/') {
+                None if name == MANIFEST_NAME => {}
+                Some((CONFIG_FOLDER, _)) => {
+                    let config = Config::upgrade(versions.config, &text(&name, content)?);
+                    backup.configs.push(config.map_err(load_error)?);
+                }
+                Some((BUSINESS_FOLDER, _)) => {
+                    let business = Business::upgrade(versions.business, &text(&name, content)?);
+                    backup.businesses.push(business.map_err(load_error)?);
+                }
+                Some((CUSTOMERS_FOLDER, _)) => {
+                    let customer = Customer::upgrade(versions.customer, &text(&name, content)?);
+                    backup.customers.push(customer.map_err(load_error)?);
+                }
+                Some((INVOICES_FOLDER, _)) => {
+                    let invoice = Invoice::upgrade(versions.invoice, &text(&name, content)?);
+                    backup.invoices.push(invoice.map_err(load_error)?);
+                }
+                Some((HISTORY_FOLDER, _)) => {
+                    let entry = HistoryEntry::upgrade(versions.history, &text(&name, content)?);
+                    backup.history.push(entry.map_err(load_error)?);
+                }
+                Some((JOURNAL_FOLDER, _)) => {
+                    let entry = JournalEntry::upgrade(versions.journal, &text(&name, content)?);
+                    backup.journal.push(entry.map_err(load_error)?);
+                }
+                Some((PDFS_FOLDER, pdf)) if !pdf.contains(['/', '\\']) && pdf != ".." => {
+                    backup.pdfs.push((pdf.to_owned(), content.clone()))
+                }
+                _ => bail!("Unexpected file {name} in archive"),
+            }
+        }
+        Ok(backup)
+    }
+
+    /// Checks that every reference between customers and invoices points to an existing record
+    pub fn check_references(&self) -> Result<()> {
+        let customers: HashSet<&str> = self.customers.iter().map(|c| c.uuid.as_str()).collect();
+        let invoices: HashSet<&str> = self.invoices.iter().map(|i| i.uuid.as_str()).collect();
+        let mut errors = Vec::new();
+
+        for invoice in &self.invoices {
+            if !customers.contains(invoice.customer.as_str()) {
+                errors.push(format!(
+                    "Invoice {} references the missing customer {}",
+                    invoice.id, invoice.customer
+                ));
+            }
+        }
+        for customer in &self.customers {
+            for invoice in &customer.invoices {
+                if !invoices.contains(invoice.as_str()) {
+                    errors.push(format!(
+                        "Customer {} references the missing invoice {invoice}",
+                        customer.id
+                    ));
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!("Archive has broken references:\n{}", errors.join("\n"));
+        }
+        Ok(())
+    }
+
+    /// Inserts all records, their history and the journal into the database and writes the pdfs to the given
+    /// folder. Existing pdfs are kept.
+    pub fn restore(self, database: &FactureDatabase, pdf_folder: &Path) -> Result<()> {
+        database.transaction(|transaction| {
+            for config in self.configs {
+                transaction.copy(config)?;
+            }
+            for business in self.businesses {
+                transaction.copy(business)?;
+            }
+            for customer in self.customers {
+                transaction.copy(customer)?;
+            }
+            for invoice in self.invoices {
+                transaction.copy(invoice)?;
+            }
+            for entry in self.history {
+                transaction.copy(entry)?;
+            }
+            for entry in self.journal {
+                transaction.copy(entry)?;
+            }
+            Ok(())
+        })?;
+
+        if !self.pdfs.is_empty() {
+            fs::create_dir_all(pdf_folder)?;
+        }
+        for (name, content) in self.pdfs {
+            let path = pdf_folder.join(&name);
+            if path.exists() {
+                eprintln!("{} already exists... skipping!", path.display());
+                continue;
+            }
+            fs::write(path, content)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::BackendKind,
+        database::{journal::Direction, testing},
+    };
+    use std::time::Duration;
+
+    fn yaml<T: YamlAble>(records: &[T]) -> Vec<String> {
+        let mut yaml: Vec<String> = records.iter().map(|r| r.to_yaml().unwrap()).collect();
+        yaml.sort();
+        yaml
+    }
+
+    #[test]
+    fn restores_records_journal_and_pdfs() {
+        let (folder, database) = testing::database(BackendKind::Native);
+        let mut customer = Customer::new_with_uuid("K0001".to_owned(), None);
+        let invoice = Invoice::new_with_uuid("R1".to_owned(), database.profile());
+        customer.add_invoice(&invoice.uuid);
+        database
+            .transaction(|transaction| {
+                transaction.create(Invoice {
+                    customer: customer.uuid.clone(),
+                    ..invoice.clone()
+                })?;
+                transaction.create(customer.clone())
+            })
+            .unwrap();
+        let pdfs = folder.path().join("pdfs");
+        fs::create_dir(&pdfs).unwrap();
+        fs::write(pdfs.join("R1.pdf"), "pdf of R1").unwrap();
+
+        let archive = folder.path().join("backup.tar");
+        Backup::from_database(&database, &pdfs)
+            .unwrap()
+            .write(&archive)
+            .unwrap();
+        let backup = Backup::read(&archive).unwrap();
+        assert_eq!(backup.manifest.format, ARCHIVE_FORMAT);
+        backup.check_references().unwrap();
+
+        for kind in [BackendKind::Native, BackendKind::Sqlite, BackendKind::Yaml] {
+            let target = tempfile::tempdir().unwrap();
+            let restored =
+                FactureDatabase::open(kind, target.path().join(kind.file_name()), Duration::ZERO)
+                    .unwrap();
+            let restored_pdfs = target.path().join("pdfs");
+            Backup::read(&archive)
+                .unwrap()
+                .restore(&restored, &restored_pdfs)
+                .unwrap();
+
+            assert_eq!(
+                yaml(&restored.read_all::<Customer>().unwrap()),
+                yaml(&database.read_all::<Customer>().unwrap())
+            );
+            assert_eq!(
+                yaml(&restored.read_all::<Invoice>().unwrap()),
+                yaml(&database.read_all::<Invoice>().unwrap())
+            );
+            assert_eq!(
+                yaml(&restored.read_all::<Config>().unwrap()),
+                yaml(&database.read_all::<Config>().unwrap())
+            );
+            assert_eq!(
+                restored.read_all::<HistoryEntry>().unwrap().len(),
+                database.read_all::<HistoryEntry>().unwrap().len()
+            );
+            assert_eq!(
+                fs::read(restored_pdfs.join("R1.pdf")).unwrap(),
+                b"pdf of R1"
+            );
+
+            // The journal is restored with the records, so undo removes the invoice again
+            let entry = restored
+                .next_journal_entry(Direction::Undo)
+                .unwrap()
+                .unwrap();
+            restored
+                .apply_journal_entry(&entry.uuid, Direction::Undo)
+                .unwrap();
+            assert!(restored.read_all::<Invoice>().unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn rejects_archives_of_newer_formats() {
+        let (folder, database) = testing::database(BackendKind::Native);
+        let archive = folder.path().join("backup.tar");
+        let mut backup = Backup::from_database(&database, folder.path()).unwrap();
+        backup.manifest.format = ARCHIVE_FORMAT + 1;
+        backup.write(&archive).unwrap();
+        assert!(Backup::read(&archive).is_err());
+    }
+}
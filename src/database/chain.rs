@@ -0,0 +1,383 @@
+use super::{
+    models::{HistoryEntry, Invoice, Issuance, Operation},
+    Transaction,
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::{Local, NaiveDateTime};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fmt::Display, fs, path::Path};
+
+/// A break in the hash chain of the issued invoices
+pub enum Break {
+    /// The invoice was changed after it was issued
+    ChangedInvoice {
+        invoice: String,
+    },
+    /// The invoice does not reference the hash of the invoice issued before it
+    BrokenLink {
+        invoice: String,
+    },
+    /// Issued invoices are missing between this invoice and the one before
+    MissingInvoices {
+        profile: String,
+        invoice: String,
+        expected: u64,
+        found: u64,
+    },
+    MissingPdf {
+        invoice: String,
+    },
+    /// The pdf differs from the one that was issued
+    ChangedPdf {
+        invoice: String,
+    },
+}
+
+impl Display for Break {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Break::ChangedInvoice { invoice } => {
+                write!(f, "Invoice {invoice} was changed after it was issued")
+            }
+            Break::BrokenLink { invoice } => write!(
+                f,
+                "Invoice {invoice} does not reference the hash of the invoice issued before it"
+            ),
+            Break::MissingInvoices {
+                profile,
+                invoice,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Invoice {invoice} is number {found} of profile {profile} but number {expected} was expected. Issued invoices are missing"
+            ),
+            Break::MissingPdf { invoice } => write!(f, "The pdf of invoice {invoice} is missing"),
+            Break::ChangedPdf { invoice } => {
+                write!(f, "The pdf of invoice {invoice} was changed after it was issued")
+            }
+        }
+    }
+}
+
+/// Everything the hash of an issued invoice covers. The fields that may still change after the
+/// invoice was issued, its payment date and trash timestamp, are left out
+#[derive(Serialize)]
+struct Canonical<'a> {
+    invoice: &'a Invoice,
+    issued_at: &'a NaiveDateTime,
+    sequence: u64,
+    previous_hash: Option<&'a str>,
+    pdf_hash: &'a str,
+}
+
+/// Hashes the JSON serialization of the invoice without its issuance, payment date and trash
+/// timestamp together with the issuance fields
+fn chain_hash(invoice: &Invoice, issuance: &Issuance) -> Result<String> {
+    let invoice = Invoice {
+        issuance: None,
+        payment_date: None,
+        deleted_at: None,
+        ..invoice.clone()
+    };
+    let canonical = Canonical {
+        invoice: &invoice,
+        issued_at: &issuance.issued_at,
+        sequence: issuance.sequence,
+        previous_hash: issuance.previous_hash.as_deref(),
+        pdf_hash: &issuance.pdf_hash,
+    };
+    Ok(sha256(&serde_json::to_vec(&canonical)?))
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Issues the invoice as the next link of the chain of its profile and records the hash of its
+/// rendered pdf
+pub fn issue(transaction: &Transaction, uuid: &str, pdf: &[u8]) -> Result<Invoice> {
+    let mut invoice: Invoice = transaction.read(uuid)?;
+    if invoice.issuance.is_some() {
+        bail!("Invoice {} was already issued", invoice.id);
+    }
+    let last = transaction
+        .read_all::<Invoice>()?
+        .into_iter()
+        .filter(|other| other.profile == invoice.profile)
+        .filter_map(|other| other.issuance)
+        .max_by_key(|issuance| issuance.sequence);
+    let mut issuance = Issuance {
+        issued_at: Local::now().naive_local(),
+        sequence: last.as_ref().map_or(1, |last| last.sequence + 1),
+        previous_hash: last.map(|last| last.hash),
+        pdf_hash: sha256(pdf),
+        hash: String::new(),
+    };
+    issuance.hash = chain_hash(&invoice, &issuance)?;
+    invoice.issuance = Some(issuance);
+    transaction.update(uuid, invoice.clone())?;
+    Ok(invoice)
+}
+
+/// The link an issued invoice left in the chain of its profile when it was purged from the
+/// trash after its retention period
+struct Tombstone {
+    hash: String,
+    previous_hash: Option<String>,
+}
+
+/// Collects the tombstones of all purged invoices by their profile and sequence from the
+/// history entries of their deletion
+fn tombstones(transaction: &Transaction) -> Result<BTreeMap<(String, u64), Tombstone>> {
+    let mut tombstones = BTreeMap::new();
+    for entry in transaction.read_all::<HistoryEntry>()? {
+        if entry.record_type != "invoice" || entry.operation != Operation::Delete {
+            continue;
+        }
+        let before = |field: &str| {
+            entry
+                .changes
+                .iter()
+                .find(|change| change.field == field)
+                .and_then(|change| change.before.clone())
+        };
+        let (Some(profile), Some(sequence), Some(hash)) = (
+            before("profile"),
+            before("issuance.sequence"),
+            before("issuance.hash"),
+        ) else {
+            continue;
+        };
+        if let Ok(sequence) = sequence.parse() {
+            let previous_hash = before("issuance.previous_hash");
+            tombstones.insert(
+                (profile, sequence),
+                Tombstone {
+                    hash,
+                    previous_hash,
+                },
+            );
+        }
+    }
+    Ok(tombstones)
+}
+
+/// Recomputes the chain of every profile and compares the pdfs in the folder with their hashes.
+/// Invoices that were purged after their retention period may be missing from the chain, as
+/// long as their tombstones still link their neighbours.
+/// Returns the number of verified invoices and all breaks.
+pub fn verify(transaction: &Transaction, pdf_folder: &Path) -> Result<(usize, Vec<Break>)> {
+    let tombstones = tombstones(transaction)?;
+    let mut chains: BTreeMap<String, Vec<(Invoice, Issuance)>> = BTreeMap::new();
+    for mut invoice in transaction.read_all::<Invoice>()? {
+        if let Some(issuance) = invoice.issuance.take() {
+            chains
+                .entry(invoice.profile.clone())
+                .or_default()
+                .push((invoice, issuance));
+        }
+    }
+
+    let mut verified = 0;
+    let mut breaks = Vec::new();
+    for (profile, mut chain) in chains {
+        chain.sort_by_key(|(_, issuance)| issuance.sequence);
+        let mut previous: Option<&Issuance> = None;
+        for (invoice, issuance) in &chain {
+            verified += 1;
+            let mut expected = previous.map_or(1, |previous| previous.sequence + 1);
+            let mut linked = previous.map(|previous| previous.hash.as_str());
+            // Purged invoices are skipped along their tombstones
+            while expected < issuance.sequence {
+                let Some(tombstone) = tombstones.get(&(profile.clone(), expected)) else {
+                    break;
+                };
+                if tombstone.previous_hash.as_deref() != linked {
+                    break;
+                }
+                linked = Some(&tombstone.hash);
+                expected += 1;
+            }
+            if issuance.sequence != expected {
+                breaks.push(Break::MissingInvoices {
+                    profile: profile.clone(),
+                    invoice: invoice.id.clone(),
+                    expected,
+                    found: issuance.sequence,
+                });
+            }
+            if issuance.previous_hash.as_deref() != linked {
+                breaks.push(Break::BrokenLink {
+                    invoice: invoice.id.clone(),
+                });
+            }
+            if chain_hash(invoice, issuance)? != issuance.hash {
+                breaks.push(Break::ChangedInvoice {
+                    invoice: invoice.id.clone(),
+                });
+            }
+            let pdf = pdf_folder.join(format!("{}.pdf", invoice.id));
+            if pdf.exists() {
+                let content =
+                    fs::read(&pdf).map_err(|error| anyhow!("{}: {error}", pdf.display()))?;
+                if sha256(&content) != issuance.pdf_hash {
+                    breaks.push(Break::ChangedPdf {
+                        invoice: invoice.id.clone(),
+                    });
+                }
+            } else {
+                breaks.push(Break::MissingPdf {
+                    invoice: invoice.id.clone(),
+                });
+            }
+            previous = Some(issuance);
+        }
+    }
+    Ok((verified, breaks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::BackendKind,
+        database::{
+            backend::{Kind, Record},
+            FactureDatabase,
+        },
+    };
+    use chrono::NaiveDate;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Opens a new database in a temporary folder that also holds the pdfs
+    fn database() -> (TempDir, FactureDatabase) {
+        let folder = tempfile::tempdir().unwrap();
+        let database = FactureDatabase::open(
+            BackendKind::Native,
+            folder.path().join("facture.db"),
+            Duration::ZERO,
+        )
+        .unwrap();
+        (folder, database)
+    }
+
+    /// Creates an invoice, issues it and writes its pdf. Returns its uuid
+    fn issue_invoice(database: &FactureDatabase, folder: &Path, id: &str) -> String {
+        issue_invoice_of(database, folder, id, Local::now().date_naive())
+    }
+
+    fn issue_invoice_of(
+        database: &FactureDatabase,
+        folder: &Path,
+        id: &str,
+        issuing_date: NaiveDate,
+    ) -> String {
+        let invoice = Invoice {
+            issuing_date,
+            ..Invoice::new_with_uuid(id.to_owned(), database.profile())
+        };
+        let uuid = invoice.uuid.clone();
+        database.create(invoice).unwrap();
+        let pdf = format!("pdf of {id}");
+        database
+            .transaction(|transaction| issue(transaction, &uuid, pdf.as_bytes()))
+            .unwrap();
+        fs::write(folder.join(format!("{id}.pdf")), pdf).unwrap();
+        uuid
+    }
+
+    fn verify_all(database: &FactureDatabase, folder: &Path) -> (usize, Vec<String>) {
+        let (verified, breaks) = database
+            .read_transaction(|transaction| verify(transaction, folder))
+            .unwrap();
+        (verified, breaks.iter().map(Break::to_string).collect())
+    }
+
+    #[test]
+    fn issued_invoice_can_be_marked_paid() {
+        let (folder, database) = database();
+        issue_invoice(&database, folder.path(), "R1");
+        let uuid = issue_invoice(&database, folder.path(), "R2");
+
+        let mut invoice: Invoice = database.read(&uuid).unwrap();
+        invoice.payment_date = NaiveDate::from_ymd_opt(2025, 3, 1);
+        database.update(&uuid, invoice).unwrap();
+
+        assert_eq!(verify_all(&database, folder.path()), (2, Vec::new()));
+    }
+
+    #[test]
+    fn issued_invoice_can_not_be_changed() {
+        let (folder, database) = database();
+        let uuid = issue_invoice(&database, folder.path(), "R1");
+
+        let mut invoice: Invoice = database.read(&uuid).unwrap();
+        invoice.due_days += 1;
+        assert!(database.update(&uuid, invoice).is_err());
+        assert_eq!(verify_all(&database, folder.path()), (1, Vec::new()));
+    }
+
+    #[test]
+    fn chain_stays_valid_after_a_purge() {
+        let (folder, database) = database();
+        let issuing_date = NaiveDate::from_ymd_opt(2010, 5, 1).unwrap();
+        issue_invoice_of(&database, folder.path(), "R1", issuing_date);
+        let uuid = issue_invoice_of(&database, folder.path(), "R2", issuing_date);
+        issue_invoice_of(&database, folder.path(), "R3", issuing_date);
+
+        database.delete::<Invoice>(&uuid).unwrap();
+        fs::remove_file(folder.path().join("R2.pdf")).unwrap();
+
+        assert_eq!(verify_all(&database, folder.path()), (2, Vec::new()));
+    }
+
+    #[test]
+    fn forged_purges_do_not_excuse_a_gap() {
+        let (folder, database) = database();
+        issue_invoice(&database, folder.path(), "R1");
+        let uuid = issue_invoice(&database, folder.path(), "R2");
+        issue_invoice(&database, folder.path(), "R3");
+
+        // Removed behind the back of facture with a made up history entry
+        database
+            .transaction(|transaction| {
+                let invoice: Invoice = transaction.read(&uuid)?;
+                let forged = Invoice {
+                    issuance: invoice.issuance.clone().map(|issuance| Issuance {
+                        hash: "forged".to_owned(),
+                        ..issuance
+                    }),
+                    ..invoice.clone()
+                };
+                transaction.inner.delete(Kind::Invoice, &uuid)?;
+                transaction.copy(HistoryEntry::new(
+                    "invoice".to_owned(),
+                    uuid.clone(),
+                    Some(invoice.id),
+                    Some(&forged.into_entity().to_json()?),
+                    None,
+                ))
+            })
+            .unwrap();
+        fs::remove_file(folder.path().join("R2.pdf")).unwrap();
+
+        let (verified, breaks) = verify_all(&database, folder.path());
+        assert_eq!(verified, 2);
+        assert_eq!(
+            breaks,
+            ["Invoice R3 does not reference the hash of the invoice issued before it"]
+        );
+    }
+
+    #[test]
+    fn issued_invoice_can_not_be_deleted_during_retention() {
+        let (folder, database) = database();
+        let uuid = issue_invoice(&database, folder.path(), "R1");
+
+        assert!(database.delete::<Invoice>(&uuid).is_err());
+        assert_eq!(verify_all(&database, folder.path()), (1, Vec::new()));
+    }
+}
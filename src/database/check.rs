@@ -0,0 +1,376 @@
+use super::{
+    models::{Config, Customer, Invoice},
+    Transaction,
+};
+use crate::commands::render_id_template;
+use anyhow::Result;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+/// Placeholder for the counter when matching ids against their template
+const COUNTER_MARKER: &str = "\u{0}";
+
+/// An inconsistency between the stored records
+pub enum Issue {
+    /// The customer of an invoice does not exist
+    MissingCustomer {
+        invoice: String,
+        customer: String,
+    },
+    /// An invoice is not listed in the invoices of its customer
+    UnlistedInvoice {
+        invoice: String,
+        invoice_uuid: String,
+        customer: String,
+        customer_uuid: String,
+    },
+    /// A customer lists an invoice that does not exist or belongs to another customer
+    ForeignInvoice {
+        customer: String,
+        customer_uuid: String,
+        invoice_uuid: String,
+    },
+    /// A customer lists the same invoice multiple times
+    DuplicateInvoiceEntry {
+        customer: String,
+        customer_uuid: String,
+        invoice_uuid: String,
+    },
+    DuplicateCustomerId(String),
+    DuplicateInvoiceId(String),
+    /// The counter of a profile would generate an id that already exists
+    CustomerCounterTooLow {
+        profile: String,
+        counter: usize,
+        next: usize,
+    },
+    InvoiceCounterTooLow {
+        profile: String,
+        counter: usize,
+        next: usize,
+    },
+}
+
+impl Issue {
+    /// Counter issues do not affect the stored records
+    pub fn is_counter(&self) -> bool {
+        matches!(
+            self,
+            Issue::CustomerCounterTooLow { .. } | Issue::InvoiceCounterTooLow { .. }
+        )
+    }
+
+    /// Whether the issue can be repaired without losing or guessing data
+    pub fn is_repairable(&self) -> bool {
+        !matches!(
+            self,
+            Issue::MissingCustomer { .. }
+                | Issue::DuplicateCustomerId(_)
+                | Issue::DuplicateInvoiceId(_)
+        )
+    }
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::MissingCustomer { invoice, customer } => {
+                write!(f, "Invoice {invoice} references the missing customer {customer}")
+            }
+            Issue::UnlistedInvoice {
+                invoice, customer, ..
+            } => write!(
+                f,
+                "Invoice {invoice} is missing from the invoices of customer {customer}"
+            ),
+            Issue::ForeignInvoice {
+                customer,
+                invoice_uuid,
+                ..
+            } => write!(
+                f,
+                "Customer {customer} lists the invoice {invoice_uuid} which does not belong to it"
+            ),
+            Issue::DuplicateInvoiceEntry {
+                customer,
+                invoice_uuid,
+                ..
+            } => write!(
+                f,
+                "Customer {customer} lists the invoice {invoice_uuid} multiple times"
+            ),
+            Issue::DuplicateCustomerId(id) => write!(f, "Multiple customers have the id {id}"),
+            Issue::DuplicateInvoiceId(id) => write!(f, "Multiple invoices have the id {id}"),
+            Issue::CustomerCounterTooLow {
+                profile,
+                counter,
+                next,
+            } => write!(
+                f,
+                "Customer counter {counter} of profile {profile} is lower than the existing ids (expected at least {next})"
+            ),
+            Issue::InvoiceCounterTooLow {
+                profile,
+                counter,
+                next,
+            } => write!(
+                f,
+                "Invoice counter {counter} of profile {profile} is lower than the existing ids (expected at least {next})"
+            ),
+        }
+    }
+}
+
+/// Scans all records for broken references, duplicate ids and outdated counters
+pub fn check(transaction: &Transaction) -> Result<Vec<Issue>> {
+    let customers: Vec<Customer> = transaction.read_all()?;
+    let invoices: Vec<Invoice> = transaction.read_all()?;
+    let customers_by_uuid: HashMap<&str, &Customer> = customers
+        .iter()
+        .map(|customer| (customer.uuid.as_str(), customer))
+        .collect();
+    let invoices_by_uuid: HashMap<&str, &Invoice> = invoices
+        .iter()
+        .map(|invoice| (invoice.uuid.as_str(), invoice))
+        .collect();
+    let mut issues = Vec::new();
+
+    for invoice in &invoices {
+        match customers_by_uuid.get(invoice.customer.as_str()) {
+            None => issues.push(Issue::MissingCustomer {
+                invoice: invoice.id.clone(),
+                customer: invoice.customer.clone(),
+            }),
+            Some(customer) if !customer.invoices.contains(&invoice.uuid) => {
+                issues.push(Issue::UnlistedInvoice {
+                    invoice: invoice.id.clone(),
+                    invoice_uuid: invoice.uuid.clone(),
+                    customer: customer.id.clone(),
+                    customer_uuid: customer.uuid.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    for customer in &customers {
+        let mut listed = HashSet::new();
+        for invoice_uuid in &customer.invoices {
+            let belongs_to_customer = invoices_by_uuid
+                .get(invoice_uuid.as_str())
+                .is_some_and(|invoice| invoice.customer == customer.uuid);
+            if !belongs_to_customer {
+                issues.push(Issue::ForeignInvoice {
+                    customer: customer.id.clone(),
+                    customer_uuid: customer.uuid.clone(),
+                    invoice_uuid: invoice_uuid.clone(),
+                });
+            } else if !listed.insert(invoice_uuid) {
+                issues.push(Issue::DuplicateInvoiceEntry {
+                    customer: customer.id.clone(),
+                    customer_uuid: customer.uuid.clone(),
+                    invoice_uuid: invoice_uuid.clone(),
+                });
+            }
+        }
+    }
+
+    let customer_ids: Vec<&str> = customers.iter().map(|c| c.id.as_str()).collect();
+    let invoice_ids: Vec<&str> = invoices.iter().map(|i| i.id.as_str()).collect();
+    issues.extend(duplicates(&customer_ids).map(Issue::DuplicateCustomerId));
+    issues.extend(duplicates(&invoice_ids).map(Issue::DuplicateInvoiceId));
+
+    // Ids are unique across profiles, so every counter is checked against all ids
+    for config in transaction.read_all::<Config>()? {
+        let next = next_counter(&config.customer_template, &customer_ids)?;
+        if config.customer_counter < next {
+            issues.push(Issue::CustomerCounterTooLow {
+                profile: config.profile.clone(),
+                counter: config.customer_counter,
+                next,
+            });
+        }
+        let next = next_counter(&config.invoice_template, &invoice_ids)?;
+        if config.invoice_counter < next {
+            issues.push(Issue::InvoiceCounterTooLow {
+                profile: config.profile.clone(),
+                counter: config.invoice_counter,
+                next,
+            });
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Repairs all repairable issues. Returns the number of repaired issues
+pub fn repair(transaction: &Transaction, issues: &[Issue]) -> Result<usize> {
+    let mut repaired = 0;
+    for issue in issues.iter().filter(|issue| issue.is_repairable()) {
+        match issue {
+            Issue::UnlistedInvoice {
+                invoice_uuid,
+                customer_uuid,
+                ..
+            } => {
+                let mut customer: Customer = transaction.read(customer_uuid)?;
+                customer.add_invoice(invoice_uuid);
+                transaction.update(customer_uuid, customer)?;
+            }
+            Issue::ForeignInvoice {
+                customer_uuid,
+                invoice_uuid,
+                ..
+            } => {
+                let mut customer: Customer = transaction.read(customer_uuid)?;
+                customer.remove_invoice(invoice_uuid);
+                transaction.update(customer_uuid, customer)?;
+            }
+            Issue::DuplicateInvoiceEntry {
+                customer_uuid,
+                invoice_uuid,
+                ..
+            } => {
+                let mut customer: Customer = transaction.read(customer_uuid)?;
+                if !customer.invoices.contains(invoice_uuid) {
+                    // Already repaired by an earlier issue of the same invoice
+                    continue;
+                }
+                customer.remove_invoice(invoice_uuid);
+                customer.add_invoice(invoice_uuid);
+                transaction.update(customer_uuid, customer)?;
+            }
+            Issue::CustomerCounterTooLow { profile, next, .. } => {
+                let mut config: Config = transaction.read(profile)?;
+                config.customer_counter = *next;
+                transaction.update(profile, config)?;
+            }
+            Issue::InvoiceCounterTooLow { profile, next, .. } => {
+                let mut config: Config = transaction.read(profile)?;
+                config.invoice_counter = *next;
+                transaction.update(profile, config)?;
+            }
+            Issue::MissingCustomer { .. }
+            | Issue::DuplicateCustomerId(_)
+            | Issue::DuplicateInvoiceId(_) => continue,
+        }
+        repaired += 1;
+    }
+    Ok(repaired)
+}
+
+pub(super) fn duplicates(ids: &[&str]) -> impl Iterator<Item = String> {
+    let mut seen = HashSet::new();
+    let mut duplicates = HashSet::new();
+    for id in ids {
+        if !seen.insert(*id) {
+            duplicates.insert(id.to_string());
+        }
+    }
+    let mut duplicates: Vec<String> = duplicates.into_iter().collect();
+    duplicates.sort();
+    duplicates.into_iter()
+}
+
+/// Returns the lowest counter that does not collide with the ids generated from the template.
+///
+/// Only ids matching the template for today's date are considered, e.g. invoices of the current
+/// year for `R{{ year }}-{{ counter }}`.
+fn next_counter(template: &str, ids: &[&str]) -> Result<usize> {
+    let pattern = render_id_template(template, COUNTER_MARKER)?;
+    let Some((prefix, suffix)) = pattern.split_once(COUNTER_MARKER) else {
+        return Ok(0);
+    };
+    let next = ids
+        .iter()
+        .filter_map(|id| id.strip_prefix(prefix)?.strip_suffix(suffix))
+        .filter_map(|counter| counter.parse::<usize>().ok())
+        .max()
+        .map_or(0, |counter| counter + 1);
+    Ok(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::BackendKind,
+        commands::next_customer_id,
+        database::{models::DEFAULT_PROFILE, testing},
+    };
+
+    #[test]
+    fn repair_fixes_references_and_counters() {
+        for kind in [BackendKind::Native, BackendKind::Sqlite, BackendKind::Yaml] {
+            let (_folder, database) = testing::database(kind);
+            let config = database.config().unwrap();
+            let mut customer = Customer::new_with_uuid(
+                next_customer_id(&Config {
+                    customer_counter: 7,
+                    ..config
+                })
+                .unwrap(),
+                None,
+            );
+            let listed = Invoice {
+                customer: customer.uuid.clone(),
+                ..Invoice::new_with_uuid("R1".to_owned(), DEFAULT_PROFILE)
+            };
+            let unlisted = Invoice {
+                customer: customer.uuid.clone(),
+                ..Invoice::new_with_uuid("R2".to_owned(), DEFAULT_PROFILE)
+            };
+            customer.add_invoice(&listed.uuid);
+            customer.add_invoice(&listed.uuid);
+            customer.add_invoice("missing");
+            database
+                .transaction(|transaction| {
+                    transaction.create(customer.clone())?;
+                    transaction.create(listed.clone())?;
+                    transaction.create(unlisted.clone())
+                })
+                .unwrap();
+
+            let issues = database.read_transaction(check).unwrap();
+            let found: Vec<String> = issues.iter().map(Issue::to_string).collect();
+            assert_eq!(
+                found,
+                [
+                    format!(
+                        "Invoice R2 is missing from the invoices of customer {}",
+                        customer.id
+                    ),
+                    format!(
+                        "Customer {} lists the invoice {} multiple times",
+                        customer.id, listed.uuid
+                    ),
+                    format!(
+                        "Customer {} lists the invoice missing which does not belong to it",
+                        customer.id
+                    ),
+                    "Customer counter 1 of profile default is lower than the existing ids (expected at least 8)"
+                        .to_owned(),
+                ],
+                "{kind}"
+            );
+
+            let repaired = database
+                .transaction(|transaction| repair(transaction, &issues))
+                .unwrap();
+            assert_eq!(repaired, 4, "{kind}");
+            assert!(
+                database.read_transaction(check).unwrap().is_empty(),
+                "{kind}"
+            );
+            let customer: Customer = database.read(&customer.uuid).unwrap();
+            assert_eq!(
+                customer.invoices,
+                [unlisted.uuid.clone(), listed.uuid.clone()],
+                "{kind}"
+            );
+            assert_eq!(database.config().unwrap().customer_counter, 8, "{kind}");
+        }
+    }
+}
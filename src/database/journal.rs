@@ -0,0 +1,163 @@
+use super::{
+    backend::{Entity, Record},
+    models::{JournalChange, JournalEntry},
+    Transaction,
+};
+use anyhow::{bail, Result};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Undo,
+    Redo,
+}
+
+/// Returns the last entry that was not undone or the first undone entry to redo
+pub fn next(transaction: &Transaction, direction: Direction) -> Result<Option<JournalEntry>> {
+    let mut entries: Vec<JournalEntry> = transaction.read_all()?;
+    entries.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+    let entry = match direction {
+        Direction::Undo => entries.into_iter().rev().find(|entry| !entry.undone),
+        Direction::Redo => entries.into_iter().find(|entry| entry.undone),
+    };
+    Ok(entry)
+}
+
+/// Parses a recorded state and upgrades it to the current model version
+fn state(change: &JournalChange, json: Option<&str>) -> Result<Option<Value>> {
+    json.map(|json| Entity::upgrade(change.kind, change.version, json)?.to_json())
+        .transpose()
+}
+
+/// Writes the state of every change from before the entry when undoing or from after it when
+/// redoing. Fails if a record was changed since without the journal, like by a migration
+pub fn apply(transaction: &Transaction, uuid: &str, direction: Direction) -> Result<()> {
+    let mut entry: JournalEntry = transaction.read(uuid)?;
+    let changes: Vec<&JournalChange> = match direction {
+        Direction::Undo => entry.changes.iter().rev().collect(),
+        Direction::Redo => entry.changes.iter().collect(),
+    };
+    for change in changes {
+        let (expected, target) = match direction {
+            Direction::Undo => (change.after.as_deref(), change.before.as_deref()),
+            Direction::Redo => (change.before.as_deref(), change.after.as_deref()),
+        };
+        let current = transaction
+            .inner
+            .read(change.kind, &change.key)?
+            .map(|entity| entity.to_json())
+            .transpose()?;
+        if current != state(change, expected)? {
+            bail!(
+                "The {} {} was changed since and can not be restored",
+                change.kind,
+                change.id.as_deref().unwrap_or(&change.key)
+            );
+        }
+        let target = target
+            .map(|json| Entity::upgrade(change.kind, change.version, json))
+            .transpose()?;
+        match (expected, target) {
+            (None, Some(entity)) => transaction.create_entity(entity)?,
+            (Some(_), Some(entity)) => transaction.update_entity(&change.key, entity)?,
+            (Some(_), None) => transaction.delete_entity(change.kind, &change.key)?,
+            (None, None) => bail!("A journal change needs a record"),
+        }
+    }
+    entry.undone = direction == Direction::Undo;
+    transaction.inner.update(uuid, entry.into_entity())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::BackendKind,
+        database::{
+            models::{Config, DEFAULT_PROFILE},
+            testing, FactureDatabase, JOURNAL_LIMIT,
+        },
+    };
+
+    fn counter(database: &FactureDatabase) -> usize {
+        database.config().unwrap().invoice_counter
+    }
+
+    fn step(database: &FactureDatabase, direction: Direction) -> bool {
+        let Some(entry) = database.next_journal_entry(direction).unwrap() else {
+            return false;
+        };
+        database
+            .apply_journal_entry(&entry.uuid, direction)
+            .unwrap();
+        true
+    }
+
+    fn increment(database: &FactureDatabase) {
+        let mut config = database.config().unwrap();
+        config.invoice_counter += 1;
+        database.update(DEFAULT_PROFILE, config).unwrap();
+    }
+
+    #[test]
+    fn undoes_and_redoes_up_to_the_journal_limit() {
+        for kind in [BackendKind::Native, BackendKind::Sqlite, BackendKind::Yaml] {
+            let (_folder, database) = testing::database(kind);
+            for _ in 0..JOURNAL_LIMIT + 5 {
+                increment(&database);
+            }
+            assert_eq!(counter(&database), JOURNAL_LIMIT + 6, "{kind}");
+            assert_eq!(
+                database.read_all::<JournalEntry>().unwrap().len(),
+                JOURNAL_LIMIT,
+                "{kind}"
+            );
+
+            let mut undone = 0;
+            while step(&database, Direction::Undo) {
+                undone += 1;
+            }
+            assert_eq!(undone, JOURNAL_LIMIT, "{kind}");
+            assert_eq!(counter(&database), 6, "{kind}");
+
+            for _ in 0..3 {
+                assert!(step(&database, Direction::Redo), "{kind}");
+            }
+            assert_eq!(counter(&database), 9, "{kind}");
+
+            // A new write drops the entries that could still be redone
+            increment(&database);
+            assert!(!step(&database, Direction::Redo), "{kind}");
+            assert!(step(&database, Direction::Undo), "{kind}");
+            assert_eq!(counter(&database), 9, "{kind}");
+        }
+    }
+
+    #[test]
+    fn refuses_to_undo_records_changed_outside_the_journal() {
+        let (_folder, database) = testing::database(BackendKind::Native);
+        increment(&database);
+        let entry = database
+            .next_journal_entry(Direction::Undo)
+            .unwrap()
+            .unwrap();
+        database
+            .transaction(|transaction| {
+                let mut config: Config = transaction.read(DEFAULT_PROFILE)?;
+                config.invoice_counter = 10;
+                transaction
+                    .inner
+                    .update(DEFAULT_PROFILE, config.into_entity())
+            })
+            .unwrap();
+
+        let error = database
+            .apply_journal_entry(&entry.uuid, Direction::Undo)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The config default was changed since and can not be restored"
+        );
+        assert_eq!(counter(&database), 10);
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::{bail, Result};
+use fs2::FileExt;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+/// How often a busy lock is tried again while waiting
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An exclusive advisory lock on a database, so only one facture process reads and writes it at
+/// a time. The lock is released when facture exits, even if it crashes.
+///
+/// The lock is taken on `<database>.lock` next to the database, which works for the folder of
+/// the yaml backend too. The file keeps the pid of the process holding the lock.
+pub struct DatabaseLock {
+    _file: File,
+}
+
+impl DatabaseLock {
+    /// Takes the lock or waits up to `wait` for the process holding it to finish
+    pub fn acquire(database: &Path, wait: Duration) -> Result<Self> {
+        let path = lock_path(database);
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let start = Instant::now();
+        let mut waiting = false;
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => break,
+                Err(error) if error.kind() == fs2::lock_contended_error().kind() => {}
+                Err(error) => bail!("Failed to lock {}: {error}", path.display()),
+            }
+            let holder = match fs::read_to_string(&path)?.trim() {
+                "" => "another facture process".to_owned(),
+                pid => format!("another facture process (pid {pid})"),
+            };
+            if start.elapsed() >= wait {
+                if wait.is_zero() {
+                    bail!(
+                        "Database {} is in use by {holder}. Use --wait to wait for it",
+                        database.display()
+                    );
+                }
+                bail!(
+                    "Database {} is still in use by {holder} after {}s",
+                    database.display(),
+                    wait.as_secs()
+                );
+            }
+            if !waiting {
+                eprintln!("Waiting for {holder} to release the database...");
+                waiting = true;
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+        file.set_len(0)?;
+        write!(file, "{}", process::id())?;
+        Ok(Self { _file: file })
+    }
+}
+
+fn lock_path(database: &Path) -> PathBuf {
+    let mut path = database.as_os_str().to_owned();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_the_lock_to_be_released() {
+        let folder = tempfile::tempdir().unwrap();
+        let database = folder.path().join("facture.db");
+        let lock = DatabaseLock::acquire(&database, Duration::ZERO).unwrap();
+        assert_eq!(
+            fs::read_to_string(lock_path(&database)).unwrap(),
+            process::id().to_string()
+        );
+
+        let error = DatabaseLock::acquire(&database, Duration::ZERO)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Database {} is in use by another facture process (pid {}). \
+                 Use --wait to wait for it",
+                database.display(),
+                process::id()
+            )
+        );
+
+        let waiting = thread::spawn({
+            let database = database.clone();
+            move || DatabaseLock::acquire(&database, Duration::from_secs(10)).map(|_| ())
+        });
+        thread::sleep(RETRY_INTERVAL * 3);
+        assert!(!waiting.is_finished());
+        drop(lock);
+        waiting.join().unwrap().unwrap();
+    }
+}
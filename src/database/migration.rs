@@ -0,0 +1,203 @@
+use super::{
+    check::duplicates,
+    models::{v1, Business, Config, Customer, Identifiable, Invoice},
+};
+use anyhow::Result;
+use native_db::{
+    transaction::{RTransaction, RwTransaction},
+    Database, ToInput,
+};
+use std::{collections::HashSet, fmt::Display};
+
+/// Records of a model that are still stored with an older model version
+pub struct Migration {
+    pub model: &'static str,
+    pub from: u32,
+    pub to: u32,
+    /// Number of records to migrate. Schema migrations of SQLite do not count records
+    pub records: Option<u64>,
+}
+
+impl Display for Migration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.records {
+            Some(records) => write!(
+                f,
+                "{}: {records} records from version {} to {}",
+                self.model, self.from, self.to
+            ),
+            None => write!(f, "{}: version {} to {}", self.model, self.from, self.to),
+        }
+    }
+}
+
+// Every older model version that is defined in `backend::native::MODELS` has to be listed in
+// `pending` and `migrate`.
+
+/// Returns all migrations that are needed to bring the database to the latest model versions
+pub fn pending(database: &Database) -> Result<Vec<Migration>> {
+    let r = database.r_transaction()?;
+    let migrations = [
+        pending_model::<v1::Customer, Customer>(&r, "customer")?,
+        pending_model::<v1::Invoice, Invoice>(&r, "invoice")?,
+        pending_model::<v1::Business, Business>(&r, "business")?,
+        pending_model::<v1::Config, Config>(&r, "config")?,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    Ok(migrations)
+}
+
+/// Returns the ids that more than one customer or invoice of any model version uses, like
+/// `invoice R2024-001`
+pub fn duplicate_ids(database: &Database) -> Result<Vec<String>> {
+    let r = database.r_transaction()?;
+    let customers = [
+        ids::<v1::Customer, Customer>(&r)?,
+        ids::<Customer, Customer>(&r)?,
+    ]
+    .concat();
+    let invoices = [
+        ids::<v1::Invoice, Invoice>(&r)?,
+        ids::<Invoice, Invoice>(&r)?,
+    ]
+    .concat();
+    let customers: Vec<&str> = customers.iter().map(String::as_str).collect();
+    let invoices: Vec<&str> = invoices.iter().map(String::as_str).collect();
+    Ok(duplicates(&customers)
+        .map(|id| format!("customer {id}"))
+        .chain(duplicates(&invoices).map(|id| format!("invoice {id}")))
+        .collect())
+}
+
+/// Returns the ids of all records of the model version
+fn ids<Old: ToInput, New: Identifiable>(r: &RTransaction) -> Result<Vec<String>> {
+    let records: Vec<Old> = r.scan().primary()?.all()?.collect::<Result<_, _>>()?;
+    records
+        .iter()
+        .map(|record| {
+            let (upgraded, _) = native_model::decode::<New>(native_model::encode(record)?)?;
+            Ok(upgraded.id().to_owned())
+        })
+        .collect()
+}
+
+/// Moves all records to the latest model versions in a single transaction
+pub fn migrate(database: &Database) -> Result<()> {
+    let rw = database.rw_transaction()?;
+    migrate_model::<v1::Customer, Customer>(&rw)?;
+    migrate_model::<v1::Invoice, Invoice>(&rw)?;
+    migrate_singleton::<v1::Business, Business>(&rw)?;
+    migrate_singleton::<v1::Config, Config>(&rw)?;
+    rw.commit()?;
+    Ok(())
+}
+
+fn pending_model<Old: ToInput, New: ToInput>(
+    r: &RTransaction,
+    model: &'static str,
+) -> Result<Option<Migration>> {
+    let records = r.len().primary::<Old>()?;
+    let migration = (records > 0).then(|| Migration {
+        model,
+        from: Old::native_model_version(),
+        to: New::native_model_version(),
+        records: Some(records),
+    });
+    Ok(migration)
+}
+
+/// Upgrades every record of the old model version through the `from` chain of native_model.
+///
+/// `RwTransaction::migrate` is not used because native_db marks models of unrelated ids as legacy
+/// when multiple versions are defined.
+///
+/// Older versions did not enforce unique ids, so duplicates get a numbered suffix. Migrating
+/// them has to be asked for explicitly, see [`duplicate_ids`].
+fn migrate_model<Old: ToInput, New: Identifiable>(rw: &RwTransaction) -> Result<()> {
+    let records: Vec<Old> = rw.scan().primary()?.all()?.collect::<Result<_, _>>()?;
+    let existing: Vec<New> = rw.scan().primary()?.all()?.collect::<Result<_, _>>()?;
+    let mut ids: HashSet<String> = existing
+        .iter()
+        .map(|record| record.id().to_owned())
+        .collect();
+    for record in records {
+        let (mut upgraded, _) = native_model::decode::<New>(native_model::encode(&record)?)?;
+        if ids.contains(upgraded.id()) {
+            let id = (2..)
+                .map(|number| format!("{}-{number}", upgraded.id()))
+                .find(|id| !ids.contains(id))
+                .unwrap_or_default();
+            eprintln!("Duplicate id {} was renamed to {id}", upgraded.id());
+            upgraded.set_id(id);
+        }
+        ids.insert(upgraded.id().to_owned());
+        rw.remove(record)?;
+        rw.insert(upgraded)?;
+    }
+    Ok(())
+}
+
+/// Upgrades the business or config, which have no id that could collide
+fn migrate_singleton<Old: ToInput, New: ToInput>(rw: &RwTransaction) -> Result<()> {
+    let records: Vec<Old> = rw.scan().primary()?.all()?.collect::<Result<_, _>>()?;
+    for record in records {
+        let (upgraded, _) = native_model::decode::<New>(native_model::encode(&record)?)?;
+        rw.remove(record)?;
+        rw.insert(upgraded)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cli::BackendKind,
+        database::{backend::native::MODELS, FactureDatabase},
+    };
+    use native_db::Builder;
+    use std::{fs, path::Path, time::Duration};
+
+    /// Creates a database of the first model version with two customers sharing an id
+    fn old_database(path: &Path) {
+        let database = Builder::new().create(&MODELS, path).unwrap();
+        let rw = database.rw_transaction().unwrap();
+        for uuid in ["a", "b"] {
+            rw.insert(v1::Customer {
+                uuid: uuid.to_owned(),
+                id: "K0001".to_owned(),
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        rw.commit().unwrap();
+    }
+
+    #[test]
+    fn duplicate_ids_are_only_renamed_when_asked_for() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("facture.db");
+        old_database(&path);
+
+        let Err(error) = FactureDatabase::open(BackendKind::Native, &path, Duration::ZERO) else {
+            panic!("Opening migrated the duplicate ids");
+        };
+        assert!(error.to_string().contains("customer K0001"), "{error}");
+        // Nothing was migrated, so there is no backup either
+        assert_eq!(fs::read_dir(folder.path()).unwrap().count(), 2);
+
+        let database =
+            FactureDatabase::open_unmigrated(BackendKind::Native, &path, Duration::ZERO).unwrap();
+        database.migrate(true).unwrap();
+        let mut ids: Vec<String> = database
+            .read_all::<Customer>()
+            .unwrap()
+            .into_iter()
+            .map(|customer| customer.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["K0001", "K0001-2"]);
+    }
+}
@@ -1,22 +1,30 @@
+pub mod backend;
+pub mod backup;
+pub mod chain;
+pub mod check;
+pub mod journal;
+pub mod lock;
+pub mod migration;
 pub mod models;
+pub mod settings;
 
-use anyhow::{anyhow, Result};
-use models::v1;
-use native_db::*;
+use crate::cli::BackendKind;
+use anyhow::{anyhow, bail, Result};
+use backend::{Backend, BackendTransaction, Entity, Kind, Record};
+use chrono::Local;
+use lock::DatabaseLock;
+use migration::Migration;
+use models::{
+    Business, Config, HistoryEntry, Identifiable, Invoice, JournalChange, JournalEntry, Scoped,
+    Trashable, DEFAULT_PROFILE,
+};
 use serde::{de::DeserializeOwned, Serialize};
-use std::{path::Path, sync::LazyLock};
-
-pub const DATABASE_PATH: &str = "./facture.db";
-
-pub static MODELS: LazyLock<Models> = LazyLock::new(|| {
-    let mut models = Models::new();
-    // It's a good practice to define the models by specifying the version
-    models.define::<v1::Customer>().unwrap();
-    models.define::<v1::Invoice>().unwrap();
-    models.define::<v1::Business>().unwrap();
-    models.define::<v1::Config>().unwrap();
-    models
-});
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 pub trait YamlAble: Serialize + DeserializeOwned {
     fn to_yaml(&self) -> Result<String> {
@@ -30,65 +38,692 @@ pub trait YamlAble: Serialize + DeserializeOwned {
     }
 }
 
-pub struct FactureDatabase<'a> {
-    _builder: Builder,
-    database: Database<'a>,
+/// Number of transactions that can be undone
+const JOURNAL_LIMIT: usize = 50;
+
+pub struct FactureDatabase {
+    backend: Box<dyn Backend>,
+    path: PathBuf,
+    /// Business profile new records are created in
+    profile: String,
+    /// Held as long as the database is open. Declared last so the backend is closed first
+    _lock: DatabaseLock,
 }
 
-impl<'a> FactureDatabase<'a> {
-    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
-        let builder = Builder::new();
-        let database = builder.create(&MODELS, path.as_ref())?;
+impl FactureDatabase {
+    /// Opens the database and migrates it to the latest model versions if needed. Waits up to
+    /// `wait` if another facture process is using the database.
+    pub fn open(kind: BackendKind, path: impl AsRef<Path>, wait: Duration) -> Result<Self> {
+        let database = Self::open_unmigrated(kind, path, wait)?;
+        let migrations = database.pending_migrations()?;
+        if !migrations.is_empty() {
+            eprintln!("Database uses old model versions. Migrating...");
+            for migration in &migrations {
+                eprintln!("  {migration}");
+            }
+            database.migrate(false)?;
+        }
+        Ok(database)
+    }
+
+    /// Opens the database without migrating it
+    pub fn open_unmigrated(
+        kind: BackendKind,
+        path: impl AsRef<Path>,
+        wait: Duration,
+    ) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let lock = DatabaseLock::acquire(path.as_ref(), wait)?;
         let database = Self {
-            _builder: builder,
-            database,
+            backend: kind.open(path.as_ref())?,
+            path: path.as_ref().to_path_buf(),
+            profile: DEFAULT_PROFILE.to_owned(),
+            _lock: lock,
         };
         Ok(database)
     }
 
-    pub fn create<T: ToInput + Clone>(&self, item: T) -> Result<()> {
-        let rw = self.database.rw_transaction()?;
-        rw.insert(item)?;
-        rw.commit()?;
-        Ok(())
+    /// Selects the business profile that is used instead of [`DEFAULT_PROFILE`]
+    pub fn with_profile(mut self, profile: &str) -> Self {
+        self.profile = profile.to_owned();
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    pub fn pending_migrations(&self) -> Result<Vec<Migration>> {
+        self.backend.pending_migrations()
+    }
+
+    /// Copies the database file next to itself and migrates all records to the latest model
+    /// versions. Returns the path of the copy.
+    ///
+    /// Fails if older versions left ids that are used by more than one record, unless
+    /// `rename_duplicates` allows to rename them with a numbered suffix like `R2024-001-2`.
+    pub fn migrate(&self, rename_duplicates: bool) -> Result<PathBuf> {
+        let duplicates = self.backend.duplicate_ids()?;
+        if !duplicates.is_empty() && !rename_duplicates {
+            let list: Vec<String> = duplicates.iter().map(|id| format!("  {id}")).collect();
+            bail!(
+                "Ids are used by more than one record, which newer versions do not allow:\n{}\n\
+                Give them unique ids with the facture version that created the database, or run \
+                `facture db migrate --rename-duplicates` to rename them with a numbered suffix",
+                list.join("\n")
+            );
+        }
+        let timestamp = Local::now().format("%Y%m%d-%H%M%S");
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(format!(".{timestamp}.bak"));
+        let backup = PathBuf::from(backup);
+        copy_all(&self.path, &backup)?;
+        eprintln!("Backed up database to {}", backup.display());
+
+        self.backend.migrate()?;
+        Ok(backup)
+    }
+
+    pub fn reindex(&self) -> Result<Vec<String>> {
+        self.backend.reindex()
+    }
+
+    /// Runs all reads and writes of the closure in a single transaction. Nothing is written if
+    /// the closure fails.
+    ///
+    /// The writes are recorded as one [`JournalEntry`] so `facture undo` can revert them together.
+    pub fn transaction<R>(&self, f: impl FnOnce(&Transaction) -> Result<R>) -> Result<R> {
+        self.run(Access::Write, f)
+    }
+
+    /// Runs the closure in a transaction that can only read, so nothing is written even if the
+    /// closure succeeds
+    pub fn read_transaction<R>(&self, f: impl FnOnce(&Transaction) -> Result<R>) -> Result<R> {
+        self.run(Access::Read, f)
+    }
+
+    fn run<R>(&self, access: Access, f: impl FnOnce(&Transaction) -> Result<R>) -> Result<R> {
+        let mut f = Some(f);
+        let mut result = None;
+        let mut body = |inner: &dyn BackendTransaction| {
+            let f = f
+                .take()
+                .ok_or_else(|| anyhow!("Transaction closure called twice"))?;
+            let transaction = Transaction {
+                inner,
+                profile: &self.profile,
+                journal: RefCell::default(),
+            };
+            result = Some(f(&transaction)?);
+            let changes = transaction.journal.take();
+            if access == Access::Write && !changes.is_empty() {
+                write_journal(inner, changes)?;
+            }
+            Ok(())
+        };
+        if access == Access::Read {
+            self.backend.read_transaction(&mut body)?;
+        } else {
+            self.backend.transaction(&mut body)?;
+        }
+        result.ok_or_else(|| anyhow!("Transaction closure was not called"))
+    }
+
+    /// Returns the journal entry the next undo or redo applies
+    pub fn next_journal_entry(
+        &self,
+        direction: journal::Direction,
+    ) -> Result<Option<JournalEntry>> {
+        self.run(Access::Read, |transaction| {
+            journal::next(transaction, direction)
+        })
+    }
+
+    /// Undoes or redoes the journal entry
+    pub fn apply_journal_entry(&self, uuid: &str, direction: journal::Direction) -> Result<()> {
+        self.run(Access::Replay, |transaction| {
+            journal::apply(transaction, uuid, direction)
+        })
+    }
+
+    pub fn create<T: Record>(&self, item: T) -> Result<()> {
+        self.transaction(|transaction| transaction.create(item))
+    }
+
+    pub fn update<T: Record>(&self, uuid: &str, item: T) -> Result<()> {
+        self.transaction(|transaction| transaction.update(uuid, item))
+    }
+
+    pub fn exists<T: Record>(&self, uuid: &str) -> Result<bool> {
+        self.read_transaction(|transaction| transaction.exists::<T>(uuid))
+    }
+
+    pub fn read<T: Record>(&self, uuid: &str) -> Result<T> {
+        self.read_transaction(|transaction| transaction.read(uuid))
+    }
+
+    pub fn read_all<T: Record>(&self) -> Result<Vec<T>> {
+        self.read_transaction(|transaction| transaction.read_all())
+    }
+
+    /// Reads all records of the selected profile including the shared ones. Records in the
+    /// trash are left out
+    pub fn read_visible<T: Record + Scoped + Trashable>(&self) -> Result<Vec<T>> {
+        self.read_transaction(|transaction| transaction.read_visible())
+    }
+
+    /// Reads the records of the selected profile that are in the trash
+    pub fn read_trashed<T: Record + Scoped + Trashable>(&self) -> Result<Vec<T>> {
+        self.read_transaction(|transaction| transaction.read_trashed())
+    }
+
+    /// Looks up a record by its human readable id
+    pub fn find_by_id<T: Identifiable>(&self, id: &str) -> Result<Option<T>> {
+        self.read_transaction(|transaction| transaction.find_by_id(id))
+    }
+
+    /// Reads a record of the selected profile by its human readable id
+    pub fn read_by_id<T: Identifiable + Scoped + Trashable>(&self, id: &str) -> Result<T> {
+        let record = self.find_by_id::<T>(id)?;
+        self.visible(record, id)
+    }
+
+    /// Reads a record of the selected profile by its uuid or its human readable id
+    pub fn read_by_key_or_id<T: Identifiable + Scoped + Trashable>(&self, key: &str) -> Result<T> {
+        let record = self.read_transaction(|transaction| {
+            if transaction.exists::<T>(key)? {
+                return transaction.read(key).map(Some);
+            }
+            transaction.find_by_id(key)
+        })?;
+        self.visible(record, key)
+    }
+
+    /// Fails if the record is missing, belongs to another profile or is in the trash
+    fn visible<T: Identifiable + Scoped + Trashable>(
+        &self,
+        record: Option<T>,
+        key: &str,
+    ) -> Result<T> {
+        let record = record
+            .filter(|record| record.is_visible_in(&self.profile))
+            .ok_or_else(|| anyhow!("{key} not found in profile {}", self.profile))?;
+        if record.is_trashed() {
+            let id = record.id();
+            bail!("{id} is in the trash. Restore it with `facture trash restore {id}`");
+        }
+        Ok(record)
+    }
+
+    pub fn config(&self) -> Result<Config> {
+        self.read_transaction(|transaction| transaction.config())
+    }
+
+    pub fn business(&self) -> Result<Business> {
+        self.read_transaction(|transaction| transaction.business())
+    }
+
+    pub fn delete<T: Record>(&self, key: &str) -> Result<()> {
+        self.transaction(|transaction| transaction.delete::<T>(key))
+    }
+}
+
+/// How [`FactureDatabase::run`] accesses the backend
+#[derive(Clone, Copy, PartialEq)]
+enum Access {
+    /// Only reads and commits nothing
+    Read,
+    /// Writes and records the writes in the journal
+    Write,
+    /// Writes without recording them. Used to undo and redo journal entries
+    Replay,
+}
+
+/// A transaction created by [`FactureDatabase::transaction`] or
+/// [`FactureDatabase::read_transaction`]. Reads see the writes made earlier in the same
+/// transaction.
+///
+/// Every create, update and delete appends a [`HistoryEntry`] in the same transaction.
+pub struct Transaction<'a> {
+    inner: &'a dyn BackendTransaction,
+    profile: &'a str,
+    /// The writes of this transaction, collected for the journal
+    journal: RefCell<Vec<JournalChange>>,
+}
+
+impl Transaction<'_> {
+    pub fn profile(&self) -> &str {
+        self.profile
+    }
+
+    pub fn create<T: Record>(&self, item: T) -> Result<()> {
+        self.create_entity(item.into_entity())
+    }
+
+    fn create_entity(&self, entity: Entity) -> Result<()> {
+        check_mutable(entity.kind())?;
+        let entry = history_entry(None, Some(&entity))?;
+        self.journal
+            .borrow_mut()
+            .push(journal_change(None, Some(&entity))?);
+        self.inner.create(entity)?;
+        self.inner.create(entry.into_entity())
     }
 
-    pub fn update<T: ToInput>(&self, uuid: &str, item: T) -> Result<()> {
-        let rw = self.database.rw_transaction()?;
-        let old: T = self.read(uuid)?;
+    /// Creates a record as is without writing a history entry. Used to copy records and their
+    /// history between databases
+    pub fn copy<T: Record>(&self, item: T) -> Result<()> {
+        self.inner.create(item.into_entity())
+    }
+
+    pub fn update<T: Record>(&self, uuid: &str, item: T) -> Result<()> {
+        self.update_entity(uuid, item.into_entity())
+    }
 
-        rw.update(old, item)?;
-        rw.commit()?;
-        Ok(())
+    fn update_entity(&self, uuid: &str, entity: Entity) -> Result<()> {
+        check_mutable(entity.kind())?;
+        if entity.key() != uuid {
+            bail!("The key {uuid} of a {} can not be changed", entity.kind());
+        }
+        let before = self
+            .inner
+            .read(entity.kind(), uuid)?
+            .ok_or_else(|| anyhow!("{uuid} not found"))?;
+        check_issued(&before, Some(&entity))?;
+        let entry = history_entry(Some(&before), Some(&entity))?;
+        // Writing an unchanged record is not worth an entry
+        if !entry.changes.is_empty() {
+            self.journal
+                .borrow_mut()
+                .push(journal_change(Some(&before), Some(&entity))?);
+        }
+        self.inner.update(uuid, entity)?;
+        if entry.changes.is_empty() {
+            return Ok(());
+        }
+        self.inner.create(entry.into_entity())
     }
 
-    pub fn exists<T: ToInput>(&self, uuid: &str) -> Result<bool> {
-        let r = self.database.r_transaction()?;
-        let result: Option<T> = r.get().primary(uuid)?;
-        Ok(result.is_some())
+    pub fn exists<T: Record>(&self, uuid: &str) -> Result<bool> {
+        self.inner.exists(T::KIND, uuid)
     }
 
-    pub fn read<T: ToInput>(&self, uuid: &str) -> Result<T> {
-        let r = self.database.r_transaction()?;
-        let result: T = r
-            .get()
-            .primary(uuid)?
+    pub fn read<T: Record>(&self, uuid: &str) -> Result<T> {
+        let entity = self
+            .inner
+            .read(T::KIND, uuid)?
             .ok_or_else(|| anyhow!("{uuid} not found"))?;
+        T::from_entity(entity)
+    }
+
+    pub fn read_all<T: Record>(&self) -> Result<Vec<T>> {
+        self.inner
+            .read_all(T::KIND)?
+            .into_iter()
+            .map(T::from_entity)
+            .collect()
+    }
+
+    pub fn read_visible<T: Record + Scoped + Trashable>(&self) -> Result<Vec<T>> {
+        let records = self.read_all::<T>()?;
+        Ok(records
+            .into_iter()
+            .filter(|record| record.is_visible_in(self.profile) && !record.is_trashed())
+            .collect())
+    }
+
+    pub fn read_trashed<T: Record + Scoped + Trashable>(&self) -> Result<Vec<T>> {
+        let records = self.read_all::<T>()?;
+        Ok(records
+            .into_iter()
+            .filter(|record| record.is_visible_in(self.profile) && record.is_trashed())
+            .collect())
+    }
+
+    /// Reads the config of the selected profile
+    pub fn config(&self) -> Result<Config> {
+        self.read_profile_record()
+    }
+
+    /// Reads the business of the selected profile
+    pub fn business(&self) -> Result<Business> {
+        self.read_profile_record()
+    }
+
+    fn read_profile_record<T: Record>(&self) -> Result<T> {
+        match self.inner.read(T::KIND, self.profile)? {
+            Some(entity) => T::from_entity(entity),
+            None => bail!(
+                "Profile {0} does not exist. Run `facture init --profile {0}` to create it",
+                self.profile
+            ),
+        }
+    }
+
+    pub fn find_by_id<T: Identifiable>(&self, id: &str) -> Result<Option<T>> {
+        self.inner
+            .find_by_id(T::KIND, id)?
+            .map(T::from_entity)
+            .transpose()
+    }
+
+    pub fn delete<T: Record>(&self, key: &str) -> Result<()> {
+        self.delete_entity(T::KIND, key)
+    }
+
+    fn delete_entity(&self, kind: Kind, key: &str) -> Result<()> {
+        check_mutable(kind)?;
+        let before = self
+            .inner
+            .read(kind, key)?
+            .ok_or_else(|| anyhow!("{key} not found"))?;
+        check_issued(&before, None)?;
+        let entry = history_entry(Some(&before), None)?;
+        self.journal
+            .borrow_mut()
+            .push(journal_change(Some(&before), None)?);
+        self.inner.delete(kind, key)?;
+        self.inner.create(entry.into_entity())
+    }
+}
 
-        Ok(result)
+fn check_mutable(kind: Kind) -> Result<()> {
+    match kind {
+        Kind::History => bail!("The history can not be changed"),
+        Kind::Journal => bail!("The journal can not be changed"),
+        _ => Ok(()),
+    }
+}
+
+/// Appends the writes of a transaction to the journal. Entries that were undone can not be
+/// redone after a new write, and only the last [`JOURNAL_LIMIT`] entries are kept
+fn write_journal(inner: &dyn BackendTransaction, changes: Vec<JournalChange>) -> Result<()> {
+    let mut entries: Vec<JournalEntry> = inner
+        .read_all(Kind::Journal)?
+        .into_iter()
+        .map(JournalEntry::from_entity)
+        .collect::<Result<_>>()?;
+    entries.sort_by(|a, b| a.uuid.cmp(&b.uuid));
+    let (undone, mut kept): (Vec<_>, Vec<_>) = entries.into_iter().partition(|entry| entry.undone);
+    for entry in undone {
+        inner.delete(Kind::Journal, &entry.uuid)?;
+    }
+    while kept.len() >= JOURNAL_LIMIT {
+        let entry = kept.remove(0);
+        inner.delete(Kind::Journal, &entry.uuid)?;
     }
+    inner.create(JournalEntry::new(changes).into_entity())
+}
+
+/// Issued invoices are part of the hash chain and stay as they were issued. They can only be
+/// marked as paid, moved to the trash and deleted once their retention period ended
+fn check_issued(before: &Entity, after: Option<&Entity>) -> Result<()> {
+    let Entity::Invoice(invoice) = before else {
+        return Ok(());
+    };
+    let Some(retained_until) = invoice.retained_until() else {
+        return Ok(());
+    };
+    match after {
+        Some(Entity::Invoice(after)) => {
+            let unchanged = Invoice {
+                deleted_at: invoice.deleted_at,
+                payment_date: invoice.payment_date,
+                ..after.clone()
+            };
+            if unchanged.into_entity().to_json()? != before.to_json()? {
+                bail!("Invoice {} was issued and can not be changed", invoice.id);
+            }
+        }
+        Some(_) => {}
+        None => {
+            if retained_until >= Local::now().date_naive() {
+                bail!(
+                    "Invoice {} was issued and has to be kept until {retained_until}",
+                    invoice.id
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn history_entry(before: Option<&Entity>, after: Option<&Entity>) -> Result<HistoryEntry> {
+    let Some(entity) = after.or(before) else {
+        bail!("A history entry needs a record");
+    };
+    let entry = HistoryEntry::new(
+        entity.kind().to_string(),
+        entity.key().to_owned(),
+        entity.id().map(str::to_owned),
+        before.map(Entity::to_json).transpose()?.as_ref(),
+        after.map(Entity::to_json).transpose()?.as_ref(),
+    );
+    Ok(entry)
+}
+
+fn journal_change(before: Option<&Entity>, after: Option<&Entity>) -> Result<JournalChange> {
+    let Some(entity) = after.or(before) else {
+        bail!("A journal change needs a record");
+    };
+    let json = |entity: &Entity| -> Result<String> { Ok(entity.to_json()?.to_string()) };
+    Ok(JournalChange {
+        kind: entity.kind(),
+        key: entity.key().to_owned(),
+        id: entity.id().map(str::to_owned),
+        version: entity.kind().version(),
+        before: before.map(json).transpose()?,
+        after: after.map(json).transpose()?,
+    })
+}
+
+/// Copies a database file or the folder of the yaml backend
+fn copy_all(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+/// Removes a database file or the folder of the yaml backend
+pub fn remove(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Opens a new database in a temporary folder with the config and business of the default
+    /// profile. The folder is removed when it is dropped
+    pub fn database(kind: BackendKind) -> (TempDir, FactureDatabase) {
+        let folder = tempfile::tempdir().unwrap();
+        let database =
+            FactureDatabase::open(kind, folder.path().join(kind.file_name()), Duration::ZERO)
+                .unwrap();
+        database.create(Config::new(DEFAULT_PROFILE)).unwrap();
+        database.create(Business::new(DEFAULT_PROFILE)).unwrap();
+        (folder, database)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use models::{v1::Change, Customer, Operation};
+
+    const KINDS: [BackendKind; 3] = [BackendKind::Native, BackendKind::Sqlite, BackendKind::Yaml];
+
+    #[test]
+    fn failed_transactions_write_nothing() {
+        for kind in KINDS {
+            let (_folder, database) = testing::database(kind);
+            let customer = Customer::new_with_uuid("K0001".to_owned(), None);
+            let result = database.transaction::<()>(|transaction| {
+                transaction.create(customer.clone())?;
+                let mut config = transaction.config()?;
+                config.customer_counter += 1;
+                transaction.update(DEFAULT_PROFILE, config)?;
+                bail!("Interrupted")
+            });
+            assert_eq!(result.unwrap_err().to_string(), "Interrupted", "{kind}");
+            assert!(
+                !database.exists::<Customer>(&customer.uuid).unwrap(),
+                "{kind}"
+            );
+            assert_eq!(database.config().unwrap().customer_counter, 1, "{kind}");
+            let history = database.read_all::<HistoryEntry>().unwrap();
+            assert_eq!(history.len(), 2, "{kind}");
+            assert_eq!(
+                database.read_all::<JournalEntry>().unwrap().len(),
+                2,
+                "{kind}"
+            );
+        }
+    }
+
+    #[test]
+    fn read_transactions_can_not_write() {
+        for kind in KINDS {
+            let (folder, database) = testing::database(kind);
+            let before = fs_snapshot(folder.path());
+            let customer = Customer::new_with_uuid("K0001".to_owned(), None);
+            let result =
+                database.read_transaction(|transaction| transaction.create(customer.clone()));
+            assert!(result.is_err(), "{kind}");
+            database.read_all::<Customer>().unwrap();
+            database.config().unwrap();
+            assert_eq!(fs_snapshot(folder.path()), before, "{kind}");
+            assert!(
+                !database.exists::<Customer>(&customer.uuid).unwrap(),
+                "{kind}"
+            );
+            // The connection accepts writes again afterwards
+            database.create(customer).unwrap();
+        }
+    }
+
+    #[test]
+    fn writes_record_a_history_entry_with_the_changed_fields() {
+        for kind in KINDS {
+            let (_folder, database) = testing::database(kind);
+            let mut customer = Customer::new_with_uuid("K0001".to_owned(), None);
+            database.create(customer.clone()).unwrap();
+            customer.contact.email = Some("jane@acme.com".to_owned());
+            database.update(&customer.uuid, customer.clone()).unwrap();
+            // Writing an unchanged record is not recorded
+            database.update(&customer.uuid, customer.clone()).unwrap();
+            database.delete::<Customer>(&customer.uuid).unwrap();
+
+            let entries: Vec<HistoryEntry> = database
+                .read_all::<HistoryEntry>()
+                .unwrap()
+                .into_iter()
+                .filter(|entry| entry.record == customer.uuid)
+                .collect();
+            let operations: Vec<Operation> = entries.iter().map(|entry| entry.operation).collect();
+            assert_eq!(
+                operations,
+                [Operation::Create, Operation::Update, Operation::Delete],
+                "{kind}"
+            );
+            assert!(entries.iter().all(|entry| entry.record_type == "customer"
+                && entry.record_id.as_deref() == Some("K0001")));
+            let created: Vec<String> = entries[0].changes.iter().map(Change::to_string).collect();
+            assert!(created.contains(&"id: - -> K0001".to_owned()), "{kind}");
+            assert_eq!(
+                entries[1].changes,
+                [Change {
+                    field: "contact.email".to_owned(),
+                    before: None,
+                    after: Some("jane@acme.com".to_owned()),
+                }],
+                "{kind}"
+            );
+            assert!(entries[2]
+                .changes
+                .iter()
+                .all(|change| change.after.is_none()));
+        }
+    }
+
+    #[test]
+    fn records_are_scoped_to_their_profile() {
+        let (_folder, database) = testing::database(BackendKind::Native);
+        let own = Customer::new_with_uuid("K0001".to_owned(), Some(DEFAULT_PROFILE.to_owned()));
+        let shared = Customer::new_with_uuid("K0002".to_owned(), None);
+        let other = Customer::new_with_uuid("other-K0001".to_owned(), Some("other".to_owned()));
+        let trashed = Customer {
+            deleted_at: Some(Local::now().naive_local()),
+            ..Customer::new_with_uuid("K0003".to_owned(), None)
+        };
+        for customer in [&own, &shared, &other, &trashed] {
+            database.create(customer.clone()).unwrap();
+        }
+
+        let ids = |customers: Vec<Customer>| -> Vec<String> {
+            customers.into_iter().map(|customer| customer.id).collect()
+        };
+        assert_eq!(ids(database.read_visible().unwrap()), ["K0001", "K0002"]);
+        assert_eq!(ids(database.read_trashed().unwrap()), ["K0003"]);
+        for key in [&own.uuid, &own.id, &shared.uuid, &shared.id] {
+            database.read_by_key_or_id::<Customer>(key).unwrap();
+        }
+        for key in [&other.uuid, &other.id] {
+            assert_eq!(
+                database
+                    .read_by_key_or_id::<Customer>(key)
+                    .unwrap_err()
+                    .to_string(),
+                format!("{key} not found in profile default")
+            );
+        }
+        assert_eq!(
+            database
+                .read_by_key_or_id::<Customer>(&trashed.uuid)
+                .unwrap_err()
+                .to_string(),
+            "K0003 is in the trash. Restore it with `facture trash restore K0003`"
+        );
 
-    pub fn read_all<T: ToInput>(&self) -> Result<Vec<T>> {
-        let r = self.database.r_transaction()?;
-        let result = r.scan().primary()?.all()?.filter_map(Result::ok).collect();
-        Ok(result)
+        let database = database.with_profile("other");
+        assert_eq!(
+            ids(database.read_visible().unwrap()),
+            ["K0002", "other-K0001"]
+        );
+        assert!(database.read_by_id::<Customer>(&own.id).is_err());
     }
 
-    pub fn delete<T: ToInput>(&self, key: &str) -> Result<()> {
-        let rw = self.database.rw_transaction()?;
-        let item: T = self.read(key)?;
-        rw.remove(item)?;
-        rw.commit()?;
-        Ok(())
+    /// Content of every file below the folder, except the lock file
+    fn fs_snapshot(folder: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(folder).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(fs_snapshot(&path));
+            } else if path.extension().is_none_or(|extension| extension != "lock") {
+                files.push((path.clone(), fs::read(path).unwrap()));
+            }
+        }
+        files.sort();
+        files
     }
 }
@@ -1,12 +1,13 @@
-use super::{Address, Contact};
-
+use super::{Address, Contact, Upgrade, DEFAULT_PROFILE};
+use anyhow::{bail, Result};
 use native_db::{native_db, ToKey};
 use native_model::{native_model, Model};
 use serde::{Deserialize, Serialize};
 
-pub const PRIMARY_KEY: &str = "BUSINESS";
+/// Key of the single business before profiles were introduced
+const PRIMARY_KEY: &str = "BUSINESS";
 
-pub type Business = v1::Business;
+pub type Business = v2::Business;
 
 pub mod v1 {
     use super::*;
@@ -32,8 +33,100 @@ pub mod v1 {
     }
 }
 
-impl Business {
+pub mod v2 {
+    use super::*;
+
+    #[native_db]
+    #[native_model(id = 3, version = 2, from = v1::Business)]
+    #[derive(Serialize, Deserialize, Debug, Default, Clone)]
+    pub struct Business {
+        #[primary_key]
+        pub profile: String,
+        pub name: String,
+        pub vat_id: String,
+        pub tax_number: String,
+        pub vat: f32,
+        pub small_business: bool,
+        pub contact: Contact,
+        pub address: Address,
+        pub payment: v1::Payment,
+    }
+}
+
+impl Upgrade for v1::Business {}
+
+impl Upgrade for v2::Business {
+    fn upgrade(version: u32, yaml: &str) -> Result<Self> {
+        match version {
+            2 => Ok(serde_yml::from_str(yaml)?),
+            _ => v1::Business::upgrade(version, yaml).map(Self::from),
+        }
+    }
+}
+
+impl v1::Business {
     fn primary_key(&self) -> String {
         PRIMARY_KEY.to_owned()
     }
 }
+
+impl From<v1::Business> for v2::Business {
+    fn from(business: v1::Business) -> Self {
+        Self {
+            profile: DEFAULT_PROFILE.to_owned(),
+            name: business.name,
+            vat_id: business.vat_id,
+            tax_number: business.tax_number,
+            vat: business.vat,
+            small_business: business.small_business,
+            contact: business.contact,
+            address: business.address,
+            payment: business.payment,
+        }
+    }
+}
+
+impl From<v2::Business> for v1::Business {
+    fn from(business: v2::Business) -> Self {
+        Self {
+            name: business.name,
+            vat_id: business.vat_id,
+            tax_number: business.tax_number,
+            vat: business.vat,
+            small_business: business.small_business,
+            contact: business.contact,
+            address: business.address,
+            payment: business.payment,
+        }
+    }
+}
+
+impl Business {
+    pub fn new(profile: &str) -> Self {
+        Self {
+            profile: profile.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Rejects a VAT that is not a fraction, like 19 given for 19 %
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.vat) {
+            bail!(
+                "vat {} is not a fraction between 0 and 1, like 0.19 for 19 %",
+                self.vat
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds the VAT to a net amount. The VAT is a fraction like 0.19, as the invoice template
+    /// takes it. Small businesses charge no VAT
+    pub fn gross(&self, net: f32) -> f32 {
+        if self.small_business {
+            net
+        } else {
+            net * (1.0 + self.vat)
+        }
+    }
+}
@@ -1,14 +1,16 @@
+use super::{Upgrade, DEFAULT_PROFILE};
 use crate::{commands::CRUD, database::YamlAble};
-
+use anyhow::Result;
 use native_db::{native_db, ToKey};
 use native_model::{native_model, Model};
 use serde::{Deserialize, Serialize};
 
-pub const PRIMARY_KEY: &str = "CONFIG";
+/// Key of the single config before profiles were introduced
+const PRIMARY_KEY: &str = "CONFIG";
 const CUSTOMER_TEMPLATE_DEFAULT: &str = "K{{ counter }}";
 const INVOICE_TEMPLATE_DEFAULT: &str = "R{{ year }}-{{ counter }}";
 
-pub type Config = v1::Config;
+pub type Config = v2::Config;
 
 pub mod v1 {
     use super::*;
@@ -24,22 +26,79 @@ pub mod v1 {
     }
 }
 
+pub mod v2 {
+    use super::*;
+
+    #[native_db]
+    #[native_model(id = 4, version = 2, from = v1::Config)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct Config {
+        #[primary_key]
+        pub profile: String,
+        pub customer_template: String,
+        pub customer_counter: usize,
+        pub invoice_template: String,
+        pub invoice_counter: usize,
+    }
+}
+
+impl Upgrade for v1::Config {}
+
+impl Upgrade for v2::Config {
+    fn upgrade(version: u32, yaml: &str) -> Result<Self> {
+        match version {
+            2 => Ok(serde_yml::from_str(yaml)?),
+            _ => v1::Config::upgrade(version, yaml).map(Self::from),
+        }
+    }
+}
+
+impl v1::Config {
+    fn primary_key(&self) -> String {
+        PRIMARY_KEY.to_owned()
+    }
+}
+
 impl YamlAble for Config {}
 impl CRUD for Config {}
 
-impl Default for Config {
-    fn default() -> Self {
+impl From<v1::Config> for v2::Config {
+    fn from(config: v1::Config) -> Self {
         Self {
-            customer_template: CUSTOMER_TEMPLATE_DEFAULT.to_owned(),
-            customer_counter: 1,
-            invoice_template: INVOICE_TEMPLATE_DEFAULT.to_owned(),
-            invoice_counter: 1,
+            profile: DEFAULT_PROFILE.to_owned(),
+            customer_template: config.customer_template,
+            customer_counter: config.customer_counter,
+            invoice_template: config.invoice_template,
+            invoice_counter: config.invoice_counter,
+        }
+    }
+}
+
+impl From<v2::Config> for v1::Config {
+    fn from(config: v2::Config) -> Self {
+        Self {
+            customer_template: config.customer_template,
+            customer_counter: config.customer_counter,
+            invoice_template: config.invoice_template,
+            invoice_counter: config.invoice_counter,
         }
     }
 }
 
 impl Config {
-    fn primary_key(&self) -> String {
-        PRIMARY_KEY.to_owned()
+    /// Ids are unique across all profiles, so the templates of other profiles than the default
+    /// one start with the profile name like `shop-K{{ counter }}`
+    pub fn new(profile: &str) -> Self {
+        let prefix = match profile {
+            DEFAULT_PROFILE => String::new(),
+            profile => format!("{profile}-"),
+        };
+        Self {
+            profile: profile.to_owned(),
+            customer_template: format!("{prefix}{CUSTOMER_TEMPLATE_DEFAULT}"),
+            customer_counter: 1,
+            invoice_template: format!("{prefix}{INVOICE_TEMPLATE_DEFAULT}"),
+            invoice_counter: 1,
+        }
     }
 }
@@ -1,9 +1,11 @@
-use super::{uuid_v7, Address, Contact};
+use super::{uuid_v7, Address, Contact, Identifiable, Scoped, Trashable, Upgrade, DEFAULT_PROFILE};
+use anyhow::Result;
+use chrono::NaiveDateTime;
 use native_db::{native_db, ToKey};
 use native_model::{native_model, Model};
 use serde::{Deserialize, Serialize};
 
-pub type Customer = v1::Customer;
+pub type Customer = v2::Customer;
 
 pub mod v1 {
     use super::*;
@@ -23,15 +25,119 @@ pub mod v1 {
     }
 }
 
+pub mod v2 {
+    use super::*;
+
+    #[native_db]
+    #[native_model(id = 1, version = 2, from = v1::Customer)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+    pub struct Customer {
+        #[primary_key]
+        pub uuid: String,
+        #[secondary_key(unique)]
+        pub id: String,
+        /// Profile the customer belongs to. `None` shares the customer between all profiles
+        pub profile: Option<String>,
+        pub organization: String,
+        pub vat_id: String,
+        pub contact: Contact,
+        pub address: Address,
+        pub invoices: Vec<String>,
+        /// Set while the customer is in the trash
+        pub deleted_at: Option<NaiveDateTime>,
+    }
+}
+
+impl Upgrade for v1::Customer {}
+
+impl Upgrade for v2::Customer {
+    fn upgrade(version: u32, yaml: &str) -> Result<Self> {
+        match version {
+            2 => Ok(serde_yml::from_str(yaml)?),
+            _ => v1::Customer::upgrade(version, yaml).map(Self::from),
+        }
+    }
+}
+
+impl Identifiable for v2::Customer {
+    type IdKey = v2::CustomerKey;
+
+    fn id_key() -> Self::IdKey {
+        v2::CustomerKey::id
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+}
+
+impl From<v1::Customer> for v2::Customer {
+    fn from(customer: v1::Customer) -> Self {
+        Self {
+            uuid: customer.uuid,
+            id: customer.id,
+            profile: Some(DEFAULT_PROFILE.to_owned()),
+            organization: customer.organization,
+            vat_id: customer.vat_id,
+            contact: customer.contact,
+            address: customer.address,
+            invoices: customer.invoices,
+            deleted_at: None,
+        }
+    }
+}
+
+impl From<v2::Customer> for v1::Customer {
+    fn from(customer: v2::Customer) -> Self {
+        Self {
+            uuid: customer.uuid,
+            id: customer.id,
+            organization: customer.organization,
+            vat_id: customer.vat_id,
+            contact: customer.contact,
+            address: customer.address,
+            invoices: customer.invoices,
+        }
+    }
+}
+
+impl Scoped for v2::Customer {
+    fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+}
+
+impl Trashable for v2::Customer {
+    fn deleted_at(&self) -> Option<NaiveDateTime> {
+        self.deleted_at
+    }
+}
+
 impl Customer {
-    pub fn new_with_uuid(id: String) -> Self {
+    pub fn new_with_uuid(id: String, profile: Option<String>) -> Self {
         Self {
             uuid: uuid_v7(),
             id,
+            profile,
             ..Default::default()
         }
     }
 
+    /// Returns the organization or the name of the contact for private customers
+    pub fn display_name(&self) -> String {
+        if self.organization.is_empty() {
+            format!("{} {}", self.contact.name, self.contact.surname)
+                .trim()
+                .to_owned()
+        } else {
+            self.organization.clone()
+        }
+    }
+
     pub fn add_invoice(&mut self, invoice_id: &str) {
         self.invoices.push(invoice_id.to_owned());
     }
@@ -41,7 +147,7 @@ impl Customer {
             .invoices
             .clone()
             .into_iter()
-            .filter(|current_invoice_id| *current_invoice_id == invoice_id)
+            .filter(|current_invoice_id| *current_invoice_id != invoice_id)
             .collect();
         self.invoices = new_invoices;
     }
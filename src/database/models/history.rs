@@ -0,0 +1,152 @@
+use super::{uuid_v7, Upgrade};
+use chrono::{Local, NaiveDateTime};
+use native_db::{native_db, ToKey};
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+pub type HistoryEntry = v1::HistoryEntry;
+pub type Operation = v1::Operation;
+pub type Change = v1::Change;
+
+pub mod v1 {
+    use super::*;
+
+    /// An immutable record of a single write to the database
+    #[native_db]
+    #[native_model(id = 5, version = 1)]
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct HistoryEntry {
+        /// A v7 uuid, so the entries are ordered by time
+        #[primary_key]
+        pub uuid: String,
+        pub timestamp: NaiveDateTime,
+        /// Type of the changed record like `customer`
+        pub record_type: String,
+        /// Key of the changed record. The uuid of customers and invoices, the profile otherwise
+        #[secondary_key]
+        pub record: String,
+        /// Human readable id of the record at the time of the change
+        pub record_id: Option<String>,
+        pub operation: Operation,
+        pub changes: Vec<Change>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+    #[serde(rename_all = "lowercase")]
+    pub enum Operation {
+        Create,
+        Update,
+        Delete,
+    }
+
+    /// A changed field. Nested fields are joined with dots like `contact.email`, list entries
+    /// use their index like `items.0.price`
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct Change {
+        pub field: String,
+        pub before: Option<String>,
+        pub after: Option<String>,
+    }
+}
+
+impl Upgrade for v1::HistoryEntry {}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::Create => f.write_str("create"),
+            Operation::Update => f.write_str("update"),
+            Operation::Delete => f.write_str("delete"),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let before = self.before.as_deref().unwrap_or("-");
+        let after = self.after.as_deref().unwrap_or("-");
+        write!(f, "{}: {before} -> {after}", self.field)
+    }
+}
+
+impl HistoryEntry {
+    /// Creates an entry with the diff between the serialized record before and after the write
+    pub fn new(
+        record_type: String,
+        record: String,
+        record_id: Option<String>,
+        before: Option<&Value>,
+        after: Option<&Value>,
+    ) -> Self {
+        let operation = match (before, after) {
+            (None, _) => Operation::Create,
+            (Some(_), Some(_)) => Operation::Update,
+            (Some(_), None) => Operation::Delete,
+        };
+        Self {
+            uuid: uuid_v7(),
+            timestamp: Local::now().naive_local(),
+            record_type,
+            record,
+            record_id,
+            operation,
+            changes: diff(before, after),
+        }
+    }
+}
+
+fn diff(before: Option<&Value>, after: Option<&Value>) -> Vec<Change> {
+    let mut old = BTreeMap::new();
+    let mut new = BTreeMap::new();
+    if let Some(before) = before {
+        flatten(String::new(), before, &mut old);
+    }
+    if let Some(after) = after {
+        flatten(String::new(), after, &mut new);
+    }
+    let fields: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    fields
+        .into_iter()
+        .map(|field| Change {
+            field: field.clone(),
+            before: old.get(field).cloned(),
+            after: new.get(field).cloned(),
+        })
+        .filter(|change| change.before != change.after)
+        .collect()
+}
+
+fn flatten(prefix: String, value: &Value, fields: &mut BTreeMap<String, String>) {
+    let join = |key: &str| {
+        if prefix.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{prefix}.{key}")
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(join(key), value, fields);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                flatten(join(&index.to_string()), value, fields);
+            }
+        }
+        // Unset optional fields are treated like missing fields
+        Value::Null => {}
+        Value::String(text) => {
+            fields.insert(prefix, text.clone());
+        }
+        value => {
+            fields.insert(prefix, value.to_string());
+        }
+    }
+}
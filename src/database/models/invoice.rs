@@ -1,10 +1,13 @@
-use super::uuid_v7;
-use chrono::{Local, NaiveDate};
+use super::{uuid_v7, Identifiable, Scoped, Trashable, Upgrade, DEFAULT_PROFILE};
+use anyhow::Result;
+use chrono::{Datelike, Days, Local, NaiveDate, NaiveDateTime};
 use native_db::{native_db, ToKey};
 use native_model::{native_model, Model};
 use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
 
-pub type Invoice = v1::Invoice;
+pub type Invoice = v2::Invoice;
+pub type Issuance = v2::Issuance;
 pub type Item = v1::Item;
 
 pub mod v1 {
@@ -31,12 +34,150 @@ pub mod v1 {
     }
 }
 
+pub mod v2 {
+    use super::*;
+
+    #[native_db]
+    #[native_model(id = 2, version = 2, from = v1::Invoice)]
+    #[derive(Serialize, Deserialize, Debug, Default, Clone)]
+    pub struct Invoice {
+        #[primary_key]
+        pub uuid: String,
+        #[secondary_key(unique)]
+        pub id: String,
+        /// Profile of the business that issues the invoice
+        pub profile: String,
+        pub issuing_date: NaiveDate,
+        pub delivery_date: NaiveDate,
+        pub due_days: u32,
+        pub payment_date: Option<NaiveDate>,
+        pub customer: String,
+        pub items: Vec<v1::Item>,
+        /// Set once the invoice is issued. Issued invoices can not be changed anymore
+        pub issuance: Option<Issuance>,
+        /// Set while the invoice is in the trash
+        pub deleted_at: Option<NaiveDateTime>,
+    }
+
+    /// Links an issued invoice into the hash chain of its profile
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    pub struct Issuance {
+        pub issued_at: NaiveDateTime,
+        /// Position in the chain of the profile, starting at 1
+        pub sequence: u64,
+        /// Hash of the invoice issued before. `None` for the first invoice of a profile
+        pub previous_hash: Option<String>,
+        /// SHA-256 of the rendered pdf
+        pub pdf_hash: String,
+        /// SHA-256 over the canonical serialization of the invoice and all fields above
+        pub hash: String,
+    }
+}
+
+impl Upgrade for v1::Invoice {}
+
+impl Upgrade for v2::Invoice {
+    fn upgrade(version: u32, yaml: &str) -> Result<Self> {
+        match version {
+            2 => Ok(serde_yml::from_str(yaml)?),
+            _ => v1::Invoice::upgrade(version, yaml).map(Self::from),
+        }
+    }
+}
+
+impl Identifiable for v2::Invoice {
+    type IdKey = v2::InvoiceKey;
+
+    fn id_key() -> Self::IdKey {
+        v2::InvoiceKey::id
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn set_id(&mut self, id: String) {
+        self.id = id;
+    }
+}
+
+impl From<v1::Invoice> for v2::Invoice {
+    fn from(invoice: v1::Invoice) -> Self {
+        Self {
+            uuid: invoice.uuid,
+            id: invoice.id,
+            profile: DEFAULT_PROFILE.to_owned(),
+            issuing_date: invoice.issuing_date,
+            delivery_date: invoice.delivery_date,
+            due_days: invoice.due_days,
+            payment_date: None,
+            customer: invoice.customer,
+            items: invoice.items,
+            issuance: None,
+            deleted_at: None,
+        }
+    }
+}
+
+impl From<v2::Invoice> for v1::Invoice {
+    fn from(invoice: v2::Invoice) -> Self {
+        Self {
+            uuid: invoice.uuid,
+            id: invoice.id,
+            issuing_date: invoice.issuing_date,
+            delivery_date: invoice.delivery_date,
+            due_days: invoice.due_days,
+            customer: invoice.customer,
+            items: invoice.items,
+        }
+    }
+}
+
+impl Scoped for v2::Invoice {
+    fn profile(&self) -> Option<&str> {
+        Some(&self.profile)
+    }
+}
+
+impl Trashable for v2::Invoice {
+    fn deleted_at(&self) -> Option<NaiveDateTime> {
+        self.deleted_at
+    }
+}
+
+/// Where an invoice stands with its payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaymentStatus {
+    /// Not issued yet
+    Draft,
+    /// Issued and waiting for the payment
+    Open,
+    /// Issued and not paid past the due date
+    Overdue,
+    Paid,
+}
+
+impl Display for PaymentStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Draft => "draft",
+            Self::Open => "open",
+            Self::Overdue => "overdue",
+            Self::Paid => "paid",
+        })
+    }
+}
+
+/// Issued invoices have to be kept this many years after the end of the year they are dated in
+pub const RETENTION_YEARS: i32 = 10;
+
 impl Invoice {
-    pub fn new_with_uuid(id: String) -> Self {
+    pub fn new_with_uuid(id: String, profile: &str) -> Self {
         let date = Local::now().date_naive();
         Self {
             uuid: uuid_v7(),
             id,
+            profile: profile.to_owned(),
             issuing_date: date,
             delivery_date: date,
             due_days: 30,
@@ -44,4 +185,40 @@ impl Invoice {
             ..Default::default()
         }
     }
+
+    /// Sum of all items without taxes. Items without a quantity count once
+    pub fn amount(&self) -> f32 {
+        self.items
+            .iter()
+            .map(|item| item.price * item.quantity.unwrap_or(1) as f32)
+            .sum()
+    }
+
+    /// Returns the day the payment is due
+    pub fn due_date(&self) -> NaiveDate {
+        self.issuing_date + Days::new(self.due_days.into())
+    }
+
+    pub fn payment_status(&self, today: NaiveDate) -> PaymentStatus {
+        match (self.payment_date, &self.issuance) {
+            (Some(_), _) => PaymentStatus::Paid,
+            // Drafts were never sent, so they can not be overdue
+            (None, None) => PaymentStatus::Draft,
+            (None, Some(_)) if self.due_date() < today => PaymentStatus::Overdue,
+            (None, Some(_)) => PaymentStatus::Open,
+        }
+    }
+
+    /// Returns how many days an unpaid invoice is past its due date
+    pub fn days_overdue(&self, today: NaiveDate) -> Option<i64> {
+        (self.payment_status(today) == PaymentStatus::Overdue)
+            .then(|| (today - self.due_date()).num_days())
+    }
+
+    /// Returns the last day an issued invoice has to be kept. Unissued invoices can be deleted
+    /// at any time
+    pub fn retained_until(&self) -> Option<NaiveDate> {
+        self.issuance.as_ref()?;
+        NaiveDate::from_ymd_opt(self.issuing_date.year() + RETENTION_YEARS, 12, 31)
+    }
 }
@@ -0,0 +1,53 @@
+use super::{uuid_v7, Upgrade};
+use crate::database::backend::Kind;
+use chrono::{Local, NaiveDateTime};
+use native_db::{native_db, ToKey};
+use native_model::{native_model, Model};
+use serde::{Deserialize, Serialize};
+
+pub type JournalEntry = v1::JournalEntry;
+pub type JournalChange = v1::JournalChange;
+
+pub mod v1 {
+    use super::*;
+
+    /// The writes of one transaction, kept so the transaction can be undone and redone
+    #[native_db]
+    #[native_model(id = 6, version = 1)]
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct JournalEntry {
+        /// A v7 uuid, so the entries are ordered by time
+        #[primary_key]
+        pub uuid: String,
+        pub timestamp: NaiveDateTime,
+        /// Set while the entry is undone. Undone entries are dropped by the next write
+        pub undone: bool,
+        pub changes: Vec<JournalChange>,
+    }
+
+    /// A record before and after a write. The states are stored as JSON together with the model
+    /// version they were written with, so they can be upgraded when they are restored
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    pub struct JournalChange {
+        pub kind: Kind,
+        pub key: String,
+        /// Human readable id of the record at the time of the write
+        pub id: Option<String>,
+        pub version: u32,
+        pub before: Option<String>,
+        pub after: Option<String>,
+    }
+}
+
+impl Upgrade for v1::JournalEntry {}
+
+impl JournalEntry {
+    pub fn new(changes: Vec<JournalChange>) -> Self {
+        Self {
+            uuid: uuid_v7(),
+            timestamp: Local::now().naive_local(),
+            undone: false,
+            changes,
+        }
+    }
+}
@@ -1,24 +1,45 @@
 mod business;
 mod config;
 mod customer;
+mod history;
 mod invoice;
+mod journal;
 
-pub use business::{Business, PRIMARY_KEY as BUSINESS_PRIMARY_KEY};
-pub use config::{Config, PRIMARY_KEY as CONFIG_PRIMARY_KEY};
+pub use business::Business;
+pub use config::Config;
 pub use customer::Customer;
-pub use invoice::Invoice;
+pub use history::{HistoryEntry, Operation};
 pub use invoice::Item;
+pub use invoice::{Invoice, Issuance, PaymentStatus};
+pub use journal::{JournalChange, JournalEntry};
 
-use serde::{Deserialize, Serialize};
+use super::backend::Record;
+use anyhow::{bail, Result};
+use chrono::NaiveDateTime;
+use native_db::{db_type::KeyOptions, db_type::ToKeyDefinition, ToInput};
+use native_model::Model;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use uuid::Uuid;
 
 pub mod v1 {
     pub use super::business::v1::*;
     pub use super::config::v1::*;
     pub use super::customer::v1::*;
+    pub use super::history::v1::*;
     pub use super::invoice::v1::*;
+    pub use super::journal::v1::*;
 }
 
+pub mod v2 {
+    pub use super::business::v2::*;
+    pub use super::config::v2::*;
+    pub use super::customer::v2::*;
+    pub use super::invoice::v2::*;
+}
+
+/// Profile used when no profile is selected and by databases created before profiles existed
+pub const DEFAULT_PROFILE: &str = "default";
+
 #[derive(Serialize, Default, Deserialize, Debug, Clone)]
 pub struct Address {
     pub country: String,
@@ -36,6 +57,50 @@ pub struct Contact {
     pub phone: Option<String>,
 }
 
+/// Loads a record that was serialized as YAML with the given model version and upgrades it to
+/// this model version
+pub trait Upgrade: Model + DeserializeOwned {
+    fn upgrade(version: u32, yaml: &str) -> Result<Self> {
+        if version != Self::native_model_version() {
+            bail!(
+                "Unsupported version {version} of model {}",
+                Self::native_model_id()
+            );
+        }
+        Ok(serde_yml::from_str(yaml)?)
+    }
+}
+
+/// A record with a unique, human readable id like `K0001` or `R2025-001`
+pub(crate) trait Identifiable: Record + ToInput {
+    type IdKey: ToKeyDefinition<KeyOptions>;
+
+    /// The unique secondary key of the id
+    fn id_key() -> Self::IdKey;
+    fn id(&self) -> &str;
+    fn set_id(&mut self, id: String);
+}
+
+/// A record that belongs to a business profile
+pub trait Scoped {
+    /// Returns `None` if the record is shared between all profiles
+    fn profile(&self) -> Option<&str>;
+
+    fn is_visible_in(&self, profile: &str) -> bool {
+        self.profile().is_none_or(|own| own == profile)
+    }
+}
+
+/// A record that is moved to the trash instead of being deleted right away
+pub trait Trashable {
+    /// Returns when the record was moved to the trash
+    fn deleted_at(&self) -> Option<NaiveDateTime>;
+
+    fn is_trashed(&self) -> bool {
+        self.deleted_at().is_some()
+    }
+}
+
 fn uuid_v7() -> String {
     Uuid::now_v7().to_string()
 }
@@ -0,0 +1,113 @@
+use super::{models::DEFAULT_PROFILE, YamlAble};
+use crate::cli::BackendKind;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+pub const SETTINGS_NAME: &str = "facture.yaml";
+const APPLICATION_NAME: &str = "facture";
+const INVOICE_FOLDER_NAME: &str = "invoices";
+
+/// Settings that are needed before the database can be opened. They are read from
+/// `./facture.yaml` or `$XDG_CONFIG_HOME/facture/facture.yaml`.
+///
+/// ```yaml
+/// backend: sqlite
+/// path: /home/me/business/facture.sqlite
+/// invoice_folder: /home/me/business/invoices
+/// profile: consulting
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Settings {
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// Path of the database
+    pub path: Option<PathBuf>,
+    /// Folder the invoices are rendered to
+    pub invoice_folder: Option<PathBuf>,
+    /// Business profile used if none is selected with `--profile`
+    pub profile: Option<String>,
+}
+
+impl YamlAble for Settings {}
+
+impl Settings {
+    pub fn load() -> Result<Self> {
+        let path = settings_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let yaml = fs::read_to_string(&path)?;
+        Self::from_yaml(&yaml).map_err(|error| anyhow!("{}: {error}", path.display()))
+    }
+
+    /// Resolves the database path in this order:
+    ///
+    /// 1. `--database` or `FACTURE_DB`
+    /// 2. `path` of the settings
+    /// 3. A database in the current directory like `./facture.db`
+    /// 4. `$XDG_DATA_HOME/facture/facture.db`
+    pub fn database_path(&self, explicit: Option<&Path>) -> PathBuf {
+        resolve(explicit, self.path.as_deref(), self.backend.file_name())
+    }
+
+    /// Resolves the invoice folder from `--invoice-folder` or `FACTURE_INVOICE_FOLDER`, then
+    /// `invoice_folder` of the settings and falls back to `invoices` next to the database
+    pub fn invoice_folder(&self, explicit: Option<&Path>, database_path: &Path) -> PathBuf {
+        if let Some(folder) = explicit.or(self.invoice_folder.as_deref()) {
+            return folder.to_path_buf();
+        }
+        database_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join(INVOICE_FOLDER_NAME)
+    }
+
+    /// Resolves the business profile from `--profile` or `FACTURE_PROFILE`, then the settings
+    /// and falls back to [`DEFAULT_PROFILE`]
+    pub fn profile<'a>(&'a self, explicit: Option<&'a str>) -> &'a str {
+        explicit
+            .or(self.profile.as_deref())
+            .unwrap_or(DEFAULT_PROFILE)
+    }
+}
+
+fn resolve(explicit: Option<&Path>, configured: Option<&Path>, name: &str) -> PathBuf {
+    if let Some(path) = explicit.or(configured) {
+        return path.to_path_buf();
+    }
+    let local = Path::new(".").join(name);
+    if local.exists() {
+        return local;
+    }
+    data_dir().join(name)
+}
+
+/// Returns `./facture.yaml` if it exists and `$XDG_CONFIG_HOME/facture/facture.yaml` otherwise
+pub fn settings_path() -> PathBuf {
+    let local = Path::new(".").join(SETTINGS_NAME);
+    if local.exists() {
+        return local;
+    }
+    xdg_dir("XDG_CONFIG_HOME", ".config").join(SETTINGS_NAME)
+}
+
+fn data_dir() -> PathBuf {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Follows the XDG base directory specification and falls back to the current directory if
+/// there is no home directory
+fn xdg_dir(variable: &str, fallback: &str) -> PathBuf {
+    let base = env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)));
+    match base {
+        Some(base) => base.join(APPLICATION_NAME),
+        None => PathBuf::from("."),
+    }
+}
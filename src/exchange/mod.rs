@@ -0,0 +1,392 @@
+pub mod vcard;
+
+use crate::{
+    cli::ExchangeFormat,
+    database::models::{Address, Contact, Customer, Invoice, Item},
+};
+use anyhow::{anyhow, bail, Result};
+use chrono::NaiveDate;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+/// A record that can be exported to and imported from the exchange formats
+pub trait ExchangeRecord: Serialize + DeserializeOwned + PartialEq {
+    /// Flat representation of the record used for CSV
+    type Row: Serialize + DeserializeOwned;
+
+    fn id(&self) -> &str;
+    fn into_rows(self) -> Vec<Self::Row>;
+    fn from_rows(rows: Vec<Self::Row>) -> Vec<Self>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CustomerRecord {
+    pub id: String,
+    pub organization: String,
+    pub vat_id: String,
+    pub name: String,
+    pub surname: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub street: String,
+    pub number: String,
+    pub postal_code: String,
+    pub city: String,
+    pub country: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InvoiceRecord {
+    pub id: String,
+    pub customer: String,
+    pub issuing_date: NaiveDate,
+    pub delivery_date: NaiveDate,
+    pub due_days: u32,
+    pub items: Vec<ItemRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ItemRecord {
+    pub description: String,
+    pub price: f32,
+    pub quantity: Option<u32>,
+}
+
+/// A single CSV row of an invoice. Every item gets its own row, an invoice without items a row
+/// with empty item columns
+#[derive(Serialize, Deserialize, Debug)]
+pub struct InvoiceRow {
+    pub id: String,
+    pub customer: String,
+    pub issuing_date: NaiveDate,
+    pub delivery_date: NaiveDate,
+    pub due_days: u32,
+    pub description: Option<String>,
+    pub price: Option<f32>,
+    pub quantity: Option<u32>,
+}
+
+impl ExchangeRecord for CustomerRecord {
+    type Row = Self;
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn into_rows(self) -> Vec<Self::Row> {
+        vec![self]
+    }
+
+    fn from_rows(rows: Vec<Self::Row>) -> Vec<Self> {
+        rows
+    }
+}
+
+impl ExchangeRecord for InvoiceRecord {
+    type Row = InvoiceRow;
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn into_rows(self) -> Vec<Self::Row> {
+        let row = |item: Option<ItemRecord>| InvoiceRow {
+            id: self.id.clone(),
+            customer: self.customer.clone(),
+            issuing_date: self.issuing_date,
+            delivery_date: self.delivery_date,
+            due_days: self.due_days,
+            description: item.as_ref().map(|item| item.description.clone()),
+            price: item.as_ref().map(|item| item.price),
+            quantity: item.and_then(|item| item.quantity),
+        };
+        if self.items.is_empty() {
+            return vec![row(None)];
+        }
+        self.items.iter().cloned().map(Some).map(row).collect()
+    }
+
+    fn from_rows(rows: Vec<Self::Row>) -> Vec<Self> {
+        let mut records: Vec<Self> = Vec::new();
+        for row in rows {
+            let item = row.price.map(|price| ItemRecord {
+                description: row.description.unwrap_or_default(),
+                price,
+                quantity: row.quantity,
+            });
+            match records.iter_mut().find(|record| record.id == row.id) {
+                Some(record) => record.items.extend(item),
+                None => records.push(Self {
+                    id: row.id,
+                    customer: row.customer,
+                    issuing_date: row.issuing_date,
+                    delivery_date: row.delivery_date,
+                    due_days: row.due_days,
+                    items: item.into_iter().collect(),
+                }),
+            }
+        }
+        records
+    }
+}
+
+impl From<Customer> for CustomerRecord {
+    fn from(customer: Customer) -> Self {
+        Self {
+            id: customer.id,
+            organization: customer.organization,
+            vat_id: customer.vat_id,
+            name: customer.contact.name,
+            surname: customer.contact.surname,
+            email: customer.contact.email,
+            phone: customer.contact.phone,
+            street: customer.address.street,
+            number: customer.address.number,
+            postal_code: customer.address.postal_code,
+            city: customer.address.city,
+            country: customer.address.country,
+        }
+    }
+}
+
+impl CustomerRecord {
+    /// Applies the record onto a customer while keeping its uuid and invoices
+    pub fn apply(self, customer: &mut Customer) {
+        customer.id = self.id;
+        customer.organization = self.organization;
+        customer.vat_id = self.vat_id;
+        customer.contact = Contact {
+            name: self.name,
+            surname: self.surname,
+            email: self.email,
+            phone: self.phone,
+        };
+        customer.address = Address {
+            country: self.country,
+            city: self.city,
+            postal_code: self.postal_code,
+            street: self.street,
+            number: self.number,
+        };
+    }
+}
+
+impl InvoiceRecord {
+    pub fn new(invoice: Invoice, customer_id: &str) -> Self {
+        Self {
+            id: invoice.id,
+            customer: customer_id.to_owned(),
+            issuing_date: invoice.issuing_date,
+            delivery_date: invoice.delivery_date,
+            due_days: invoice.due_days,
+            items: invoice.items.into_iter().map(ItemRecord::from).collect(),
+        }
+    }
+
+    /// Applies the record onto an invoice while keeping its uuid. The customer has to be resolved
+    /// by the caller.
+    pub fn apply(self, invoice: &mut Invoice, customer_uuid: &str) {
+        invoice.id = self.id;
+        invoice.customer = customer_uuid.to_owned();
+        invoice.issuing_date = self.issuing_date;
+        invoice.delivery_date = self.delivery_date;
+        invoice.due_days = self.due_days;
+        invoice.items = self.items.into_iter().map(Item::from).collect();
+    }
+}
+
+impl From<Item> for ItemRecord {
+    fn from(item: Item) -> Self {
+        Self {
+            description: item.description,
+            price: item.price,
+            quantity: item.quantity,
+        }
+    }
+}
+
+impl From<ItemRecord> for Item {
+    fn from(item: ItemRecord) -> Self {
+        Self {
+            description: item.description,
+            price: item.price,
+            quantity: item.quantity,
+        }
+    }
+}
+
+/// Returns the explicit format or derives it from the extension of the path
+pub fn resolve_format(
+    format: Option<ExchangeFormat>,
+    path: Option<&Path>,
+) -> Result<ExchangeFormat> {
+    if let Some(format) = format {
+        return Ok(format);
+    }
+    let extension = path
+        .and_then(|path| path.extension())
+        .and_then(|extension| extension.to_str())
+        .ok_or_else(|| anyhow!("Unable to derive the format. Please specify it with --format"))?;
+    match extension.to_lowercase().as_str() {
+        "csv" => Ok(ExchangeFormat::Csv),
+        "json" => Ok(ExchangeFormat::Json),
+        "jsonl" | "ndjson" => Ok(ExchangeFormat::Jsonl),
+        _ => bail!("Unknown file extension .{extension}. Please specify the format with --format"),
+    }
+}
+
+pub fn write<R: ExchangeRecord>(
+    records: Vec<R>,
+    format: ExchangeFormat,
+    mut writer: impl Write,
+) -> Result<()> {
+    match format {
+        ExchangeFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for row in records.into_iter().flat_map(R::into_rows) {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+        ExchangeFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &records)?;
+            writeln!(writer)?;
+        }
+        ExchangeFormat::Jsonl => {
+            for record in records {
+                serde_json::to_writer(&mut writer, &record)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn read<R: ExchangeRecord>(format: ExchangeFormat, reader: impl Read) -> Result<Vec<R>> {
+    let records = match format {
+        ExchangeFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(reader);
+            let rows = reader
+                .deserialize()
+                .collect::<Result<Vec<R::Row>, csv::Error>>()?;
+            R::from_rows(rows)
+        }
+        ExchangeFormat::Json => serde_json::from_reader(reader)?,
+        ExchangeFormat::Jsonl => {
+            let mut records = Vec::new();
+            for (number, line) in BufReader::new(reader).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let record = serde_json::from_str(&line)
+                    .map_err(|error| anyhow!("Line {}: {error}", number + 1))?;
+                records.push(record);
+            }
+            records
+        }
+    };
+
+    let mut ids: Vec<&str> = records.iter().map(R::id).collect();
+    ids.sort_unstable();
+    if let Some(duplicate) = ids.windows(2).find(|ids| ids[0] == ids[1]) {
+        bail!("The id {} occurs multiple times", duplicate[0]);
+    }
+    Ok(records)
+}
+
+/// Returns the names of all top level fields that differ between both records
+pub fn changed_fields<R: Serialize>(old: &R, new: &R) -> Result<Vec<String>> {
+    let old = serde_json::to_value(old)?;
+    let new = serde_json::to_value(new)?;
+    let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+        bail!("Records have to be objects");
+    };
+    let fields = new
+        .iter()
+        .filter(|(field, value)| old.get(*field) != Some(value))
+        .map(|(field, _)| field.to_owned())
+        .collect();
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invoice(id: &str, items: Vec<ItemRecord>) -> InvoiceRecord {
+        let date = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        InvoiceRecord {
+            id: id.to_owned(),
+            customer: "K0001".to_owned(),
+            issuing_date: date,
+            delivery_date: date,
+            due_days: 14,
+            items,
+        }
+    }
+
+    fn round_trip(records: Vec<InvoiceRecord>, format: ExchangeFormat) -> Vec<InvoiceRecord> {
+        let mut content = Vec::new();
+        write(records, format, &mut content).unwrap();
+        read(format, content.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn invoices_survive_a_round_trip_in_every_format() {
+        let item = |description: &str, price, quantity| ItemRecord {
+            description: description.to_owned(),
+            price,
+            quantity,
+        };
+        let records = vec![
+            invoice(
+                "R1",
+                vec![
+                    item("Design, layout", 120.0, None),
+                    item("Hosting", 9.5, Some(12)),
+                ],
+            ),
+            invoice("R2", Vec::new()),
+        ];
+        for format in [
+            ExchangeFormat::Csv,
+            ExchangeFormat::Json,
+            ExchangeFormat::Jsonl,
+        ] {
+            assert_eq!(round_trip(records.clone(), format), records);
+        }
+    }
+
+    #[test]
+    fn invoice_without_items_gets_a_row_with_empty_item_columns() {
+        let mut content = Vec::new();
+        write(
+            vec![invoice("R1", Vec::new())],
+            ExchangeFormat::Csv,
+            &mut content,
+        )
+        .unwrap();
+        let content = String::from_utf8(content).unwrap();
+        assert_eq!(
+            content.lines().nth(1),
+            Some("R1,K0001,2025-03-01,2025-03-01,14,,,")
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_ids() {
+        let mut content = Vec::new();
+        write(
+            vec![invoice("R1", Vec::new()), invoice("R1", Vec::new())],
+            ExchangeFormat::Jsonl,
+            &mut content,
+        )
+        .unwrap();
+        let error = read::<InvoiceRecord>(ExchangeFormat::Jsonl, content.as_slice()).unwrap_err();
+        assert_eq!(error.to_string(), "The id R1 occurs multiple times");
+    }
+}
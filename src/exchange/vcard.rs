@@ -0,0 +1,304 @@
+use crate::database::models::{Address, Contact, Customer};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::fmt::Write;
+
+const FACTURE_ID_PROPERTY: &str = "X-FACTURE-ID";
+const MAX_LINE_LENGTH: usize = 75;
+
+/// The parts of a customer that are mapped to a vCard
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct VCard {
+    /// The facture id stored in the `X-FACTURE-ID` property
+    pub id: Option<String>,
+    pub organization: String,
+    pub name: String,
+    pub surname: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Address,
+}
+
+impl From<Customer> for VCard {
+    fn from(customer: Customer) -> Self {
+        Self {
+            id: Some(customer.id),
+            organization: customer.organization,
+            name: customer.contact.name,
+            surname: customer.contact.surname,
+            email: customer.contact.email,
+            phone: customer.contact.phone,
+            address: customer.address,
+        }
+    }
+}
+
+impl VCard {
+    /// Applies the vCard onto a customer while keeping all fields that are not part of a vCard
+    pub fn apply(self, customer: &mut Customer) {
+        if let Some(id) = self.id {
+            customer.id = id;
+        }
+        customer.organization = self.organization;
+        customer.contact = Contact {
+            name: self.name,
+            surname: self.surname,
+            email: self.email,
+            phone: self.phone,
+        };
+        customer.address = self.address;
+    }
+
+    pub fn to_vcf(&self) -> String {
+        let full_name = format!("{} {}", self.name, self.surname);
+        let full_name = match full_name.trim() {
+            "" => self.organization.as_str(),
+            full_name => full_name,
+        };
+        let street = format!("{} {}", self.address.street, self.address.number);
+
+        let mut lines = vec![
+            "BEGIN:VCARD".to_owned(),
+            "VERSION:3.0".to_owned(),
+            format!("FN:{}", escape(full_name)),
+            format!("N:{};{};;;", escape(&self.surname), escape(&self.name)),
+        ];
+        if !self.organization.is_empty() {
+            lines.push(format!("ORG:{}", escape(&self.organization)));
+        }
+        if let Some(email) = &self.email {
+            lines.push(format!("EMAIL;TYPE=INTERNET:{}", escape(email)));
+        }
+        if let Some(phone) = &self.phone {
+            lines.push(format!("TEL;TYPE=WORK:{}", escape(phone)));
+        }
+        lines.push(format!(
+            "ADR;TYPE=WORK:;;{};{};;{};{}",
+            escape(street.trim()),
+            escape(&self.address.city),
+            escape(&self.address.postal_code),
+            escape(&self.address.country)
+        ));
+        if let Some(id) = &self.id {
+            lines.push(format!("{FACTURE_ID_PROPERTY}:{}", escape(id)));
+        }
+        lines.push("END:VCARD".to_owned());
+
+        lines.iter().fold(String::new(), |mut vcf, line| {
+            let _ = write!(vcf, "{}\r\n", fold(line));
+            vcf
+        })
+    }
+}
+
+/// Parses all vCards contained in the content of a .vcf file
+pub fn parse(content: &str) -> Result<Vec<VCard>> {
+    let mut cards = Vec::new();
+    let mut current: Option<VCard> = None;
+
+    for line in unfold(content) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((property, value)) = line.split_once(':') else {
+            bail!("Invalid vCard line: {line}");
+        };
+        let mut parameters = property.split(';');
+        let name = parameters.next().unwrap_or_default();
+        // Strip an optional group prefix like `item1.EMAIL`
+        let name = name.rsplit('.').next().unwrap_or_default().to_uppercase();
+
+        match (name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VCARD") => {
+                current = Some(VCard::default())
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+                cards.extend(current.take());
+            }
+            ("BEGIN" | "END", _) => bail!("Unexpected vCard line: {line}"),
+            (_, None) => bail!("vCard property outside of BEGIN:VCARD: {line}"),
+            ("ORG", Some(card)) => {
+                card.organization = components(value).into_iter().next().unwrap_or_default();
+            }
+            ("N", Some(card)) => {
+                let mut components = components(value).into_iter();
+                card.surname = components.next().unwrap_or_default();
+                card.name = components.next().unwrap_or_default();
+            }
+            ("EMAIL", Some(card)) if card.email.is_none() => card.email = Some(unescape(value)),
+            ("TEL", Some(card)) if card.phone.is_none() => card.phone = Some(unescape(value)),
+            ("ADR", Some(card)) => {
+                let components = components(value);
+                let component = |index: usize| components.get(index).cloned().unwrap_or_default();
+                let (street, number) = split_street(&component(2));
+                card.address = Address {
+                    country: component(6),
+                    city: component(3),
+                    postal_code: component(5),
+                    street,
+                    number,
+                };
+            }
+            (FACTURE_ID_PROPERTY, Some(card)) => card.id = Some(unescape(value)),
+            _ => {}
+        }
+    }
+
+    if current.is_some() {
+        bail!("vCard is missing END:VCARD");
+    }
+    Ok(cards)
+}
+
+/// Joins lines that were folded by a leading space or tab
+fn unfold(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+/// Folds a line into multiple lines of at most 75 bytes
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for character in line.chars() {
+        if length + character.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(character);
+        length += character.len_utf8();
+    }
+    folded
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut characters = value.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => match characters.next() {
+                Some('n' | 'N') => unescaped.push('\n'),
+                Some(escaped) => unescaped.push(escaped),
+                None => {}
+            },
+            character => unescaped.push(character),
+        }
+    }
+    unescaped
+}
+
+/// Splits a structured value at unescaped semicolons
+fn components(value: &str) -> Vec<String> {
+    let mut components = Vec::new();
+    let mut current = String::new();
+    let mut characters = value.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => {
+                current.push(character);
+                current.extend(characters.next());
+            }
+            ';' => components.push(unescape(&std::mem::take(&mut current))),
+            character => current.push(character),
+        }
+    }
+    components.push(unescape(&current));
+    components
+}
+
+/// Splits "Hauptstraße 12a" into the street and the house number
+fn split_street(street: &str) -> (String, String) {
+    match street.trim().rsplit_once(' ') {
+        Some((name, number)) if number.starts_with(|c: char| c.is_ascii_digit()) => {
+            (name.trim().to_owned(), number.to_owned())
+        }
+        _ => (street.trim().to_owned(), String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_folded_and_escaped_properties() {
+        let content = "BEGIN:VCARD\r\n\
+            VERSION:3.0\r\n\
+            N:Müller;Anna;;;\r\n\
+            ORG:Müller\\, Schmidt \\; Partner;Sales\r\n\
+            item1.EMAIL;TYPE=INTERNET:anna@exa\r\n\
+            \x20mple.com\r\n\
+            ADR;TYPE=WORK:;;Hauptstraße 12a;Berlin;;10115;Germany\r\n\
+            X-FACTURE-ID:K0001\r\n\
+            END:VCARD\r\n";
+        let cards = parse(content).unwrap();
+        assert_eq!(cards.len(), 1);
+        let card = &cards[0];
+        assert_eq!(card.id.as_deref(), Some("K0001"));
+        assert_eq!(card.name, "Anna");
+        assert_eq!(card.surname, "Müller");
+        assert_eq!(card.organization, "Müller, Schmidt ; Partner");
+        assert_eq!(card.email.as_deref(), Some("anna@example.com"));
+        assert_eq!(card.address.street, "Hauptstraße");
+        assert_eq!(card.address.number, "12a");
+        assert_eq!(card.address.city, "Berlin");
+        assert_eq!(card.address.postal_code, "10115");
+        assert_eq!(card.address.country, "Germany");
+    }
+
+    #[test]
+    fn rejects_unterminated_cards() {
+        assert!(parse("BEGIN:VCARD\nN:Doe;John;;;\n").is_err());
+        assert!(parse("N:Doe;John;;;\n").is_err());
+    }
+
+    #[test]
+    fn folds_long_lines_and_unfolds_them_again() {
+        let line = format!("NOTE:{}", "ä".repeat(60));
+        let folded = fold(&line);
+        assert!(folded
+            .split("\r\n")
+            .all(|part| part.len() <= MAX_LINE_LENGTH));
+        assert_eq!(unfold(&folded), vec![line]);
+    }
+
+    #[test]
+    fn writes_cards_that_parse_to_the_same_values() {
+        let card = VCard {
+            id: Some("K0002".to_owned()),
+            organization: "Acme, Inc; Branch".to_owned(),
+            name: "Jane".to_owned(),
+            surname: "Doe".to_owned(),
+            email: Some("jane@acme.test".to_owned()),
+            phone: None,
+            address: Address {
+                street: "Long Street".repeat(8),
+                number: "3".to_owned(),
+                ..Address::default()
+            },
+        };
+        let parsed = parse(&card.to_vcf()).unwrap().remove(0);
+        assert_eq!(parsed.id, card.id);
+        assert_eq!(parsed.organization, card.organization);
+        assert_eq!(parsed.name, card.name);
+        assert_eq!(parsed.surname, card.surname);
+        assert_eq!(parsed.email, card.email);
+        assert_eq!(parsed.phone, None);
+        assert_eq!(parsed.address.street, card.address.street);
+        assert_eq!(parsed.address.number, "3");
+    }
+}
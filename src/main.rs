@@ -1,28 +1,125 @@
 mod cli;
 mod commands;
+mod completion;
 mod database;
+mod exchange;
 mod template;
 mod ui;
 
 use anyhow::Result;
-use clap::Parser;
-use cli::Cli;
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
+use cli::{Cli, Commands, DatabaseCommand, OpenCommand, OpenDatabaseCommand};
 use commands::{
-    business::handle_business_command, customer::handle_customer_command, handle_config_command,
-    handle_init_command, invoice::handle_invoice_command,
+    backup::{handle_backup_command, handle_restore_command},
+    business::handle_business_command,
+    completions::handle_completions_command,
+    customer::handle_customer_command,
+    database::{handle_database_command, handle_rekey_command},
+    exchange::{handle_export_command, handle_import_command},
+    handle_config_command, handle_init_command,
+    history::handle_history_command,
+    invoice::handle_invoice_command,
+    profile::handle_profile_command,
+    search::handle_search_command,
+    trash::handle_trash_command,
+    undo::handle_undo_command,
 };
 
-use database::{FactureDatabase, DATABASE_PATH};
+use database::{journal::Direction, settings::Settings, FactureDatabase};
+use std::{path::Path, time::Duration};
+
 fn main() -> Result<()> {
+    // Answers the shell and exits while completing
+    CompleteEnv::with_factory(Cli::command).complete();
     let cli = Cli::parse();
-    let database = FactureDatabase::open(DATABASE_PATH)?;
+    if cli.yes {
+        ui::prompt::assume_yes();
+    }
+    if let Some(format) = cli.output {
+        ui::output::set_format(format);
+    }
 
     match &cli.command {
-        cli::Commands::Customer(command) => handle_customer_command(command, database)?,
-        cli::Commands::Invoice(command) => handle_invoice_command(command, database)?,
-        cli::Commands::Business(command) => handle_business_command(command, database)?,
-        cli::Commands::Config(command) => handle_config_command(command, database)?,
-        cli::Commands::Init => handle_init_command(database)?,
+        Commands::Completions { shell } => handle_completions_command(*shell),
+        // Restoring needs to create the database itself
+        Commands::Restore { archive } => {
+            let settings = Settings::load()?;
+            let database_path = settings.database_path(cli.database.as_deref());
+            handle_restore_command(
+                archive,
+                settings.backend,
+                &database_path,
+                &settings.invoice_folder(cli.invoice_folder.as_deref(), &database_path),
+            )
+        }
+        // Rekeying works on the file and unlocks it itself
+        Commands::Db(DatabaseCommand::Rekey { remove }) => {
+            let settings = Settings::load()?;
+            handle_rekey_command(
+                settings.backend,
+                &settings.database_path(cli.database.as_deref()),
+                *remove,
+                Duration::from_secs(cli.wait),
+            )
+        }
+        Commands::Db(DatabaseCommand::Open(command)) => {
+            // Migrating shows what is migrated instead of migrating on open
+            let migrate = !matches!(command, OpenDatabaseCommand::Migrate { .. });
+            let settings = Settings::load()?;
+            let database = open_database(&cli, &settings, migrate)?;
+            let invoice_folder =
+                settings.invoice_folder(cli.invoice_folder.as_deref(), database.path());
+            handle_database_command(command, database, &invoice_folder)
+        }
+        Commands::Open(command) => {
+            let settings = Settings::load()?;
+            let database = open_database(&cli, &settings, true)?;
+            let invoice_folder =
+                settings.invoice_folder(cli.invoice_folder.as_deref(), database.path());
+            run(command, database, &invoice_folder)
+        }
+    }
+}
+
+/// Opens the database and selects the profile given by the flags or the settings
+fn open_database(cli: &Cli, settings: &Settings, migrate: bool) -> Result<FactureDatabase> {
+    let database_path = settings.database_path(cli.database.as_deref());
+    let wait = Duration::from_secs(cli.wait);
+    // Opening a missing database creates an empty one, which is rarely wanted outside init
+    let init =
+        matches!(&cli.command, Commands::Open(command) if matches!(**command, OpenCommand::Init));
+    if !database_path.exists() && !init {
+        eprintln!(
+            "Warning: No database found at {}. Creating an empty one. Run `facture init` to set it up or select a database with --database",
+            database_path.display()
+        );
+    }
+    let database = if migrate {
+        FactureDatabase::open(settings.backend, &database_path, wait)?
+    } else {
+        FactureDatabase::open_unmigrated(settings.backend, &database_path, wait)?
+    };
+    Ok(database.with_profile(settings.profile(cli.profile.as_deref())))
+}
+
+fn run(command: &OpenCommand, database: FactureDatabase, invoice_folder: &Path) -> Result<()> {
+    match command {
+        OpenCommand::Customer(command) => handle_customer_command(command, database),
+        OpenCommand::Invoice(command) => handle_invoice_command(command, database, invoice_folder),
+        OpenCommand::Business(command) => handle_business_command(command, database),
+        OpenCommand::Config(command) => handle_config_command(command, database),
+        OpenCommand::Profile(command) => handle_profile_command(command, database),
+        OpenCommand::Trash(command) => handle_trash_command(command, database),
+        OpenCommand::Init => handle_init_command(database),
+        OpenCommand::Export(args) => handle_export_command(args, database),
+        OpenCommand::Import(args) => handle_import_command(args, database),
+        OpenCommand::Backup { archive } => handle_backup_command(archive, database, invoice_folder),
+        OpenCommand::History { id, since } => {
+            handle_history_command(id.as_deref(), *since, database)
+        }
+        OpenCommand::Search { term } => handle_search_command(term, database),
+        OpenCommand::Undo => handle_undo_command(Direction::Undo, database),
+        OpenCommand::Redo => handle_undo_command(Direction::Redo, database),
     }
-    Ok(())
 }
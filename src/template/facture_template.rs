@@ -157,7 +157,6 @@ impl From<Business> for template::BankAccount {
             bank: business.payment.bank,
             iban: business.payment.iban,
             bic: business.payment.bic,
-            ..Default::default()
         }
     }
 }
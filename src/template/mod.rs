@@ -4,12 +4,14 @@ use crate::database::{
     models::{Business, Customer, Invoice},
     YamlAble,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 pub use facture_template::template;
-use std::{fs, path::Path, process::Command};
-use ureq;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
-const OUTPUT_FOLDER: &str = "invoices";
 const DATA_YAML_NAME: &str = "data.yaml";
 const TEMPLATE_MAIN_NAME: &str = "main.typ";
 const TEMPLATE_LIB_NAME: &str = "template.typ";
@@ -19,21 +21,27 @@ const COMPILE_ARGUMENT: &str = "compile";
 pub struct Template<T: RenderAble> {
     invoice_id: String,
     template: T,
+    output_folder: PathBuf,
 }
 
 impl<T: RenderAble> Template<T> {
-    pub fn new(business: Business, customer: Customer, invoice: Invoice) -> Result<Self> {
-        Self::init()?;
+    pub fn new(
+        output_folder: &Path,
+        business: Business,
+        customer: Customer,
+        invoice: Invoice,
+    ) -> Result<Self> {
+        Self::init(output_folder)?;
         let template = Self {
             invoice_id: invoice.id.clone(),
             template: T::new(business, customer, invoice),
+            output_folder: output_folder.to_path_buf(),
         };
 
         Ok(template)
     }
 
-    fn init() -> Result<()> {
-        let output_folder = Path::new(OUTPUT_FOLDER);
+    fn init(output_folder: &Path) -> Result<()> {
         if !output_folder.exists() {
             fs::create_dir_all(output_folder)?;
         }
@@ -55,27 +63,30 @@ impl<T: RenderAble> Template<T> {
         Ok(())
     }
 
-    fn compile(output_folder: &Path, invoice_name: &str) -> Result<()> {
+    fn compile(output_folder: &Path, invoice_name: &str) -> Result<PathBuf> {
         let template_main = output_folder
             .join(TEMPLATE_MAIN_NAME)
             .to_string_lossy()
             .to_string();
-        let output_pdf = output_folder
-            .join(format!("{invoice_name}.pdf"))
-            .to_string_lossy()
-            .to_string();
-        Command::new(COMPILE_COMMAND)
-            .args([COMPILE_ARGUMENT, &template_main, &output_pdf])
+        let output_pdf = output_folder.join(format!("{invoice_name}.pdf"));
+        let status = Command::new(COMPILE_COMMAND)
+            .args([
+                COMPILE_ARGUMENT,
+                &template_main,
+                &output_pdf.to_string_lossy(),
+            ])
             .status()?;
-        Ok(())
+        if !status.success() {
+            bail!("{COMPILE_COMMAND} failed with {status}");
+        }
+        Ok(output_pdf)
     }
 
-    pub fn render(&self) -> Result<()> {
+    /// Renders the invoice and returns the path of the pdf
+    pub fn render(&self) -> Result<PathBuf> {
         let template_yaml = self.template.to_yaml()?;
-        let output_folder = Path::new(OUTPUT_FOLDER);
-        fs::write(&output_folder.join(DATA_YAML_NAME), template_yaml)?;
-        Self::compile(&output_folder, &self.invoice_id)?;
-        Ok(())
+        fs::write(self.output_folder.join(DATA_YAML_NAME), template_yaml)?;
+        Self::compile(&self.output_folder, &self.invoice_id)
     }
 }
 
@@ -1,13 +1,69 @@
+pub mod output;
 pub mod prompt;
 
 use comfy_table::{presets::UTF8_FULL_CONDENSED, ContentArrangement, Table};
 use std::fmt::Display;
 
-use crate::database::models::{Customer, Invoice};
+use crate::database::{
+    models::{Business, Customer, HistoryEntry, Invoice},
+    FactureDatabase,
+};
+use anyhow::Result;
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use serde::Serialize;
 
-pub trait TableAble {
+pub trait TableAble: Sized {
     fn header() -> Vec<String>;
-    fn row(self) -> Vec<String>;
+
+    /// Rows of the records in their order. Related records like the customer of an invoice are
+    /// read from the database
+    fn rows(database: &FactureDatabase, records: &[Self]) -> Result<Vec<Vec<String>>>;
+
+    /// The rows with raw values for other programs, like amounts as plain numbers and empty cells
+    /// for missing values
+    fn csv_rows(database: &FactureDatabase, records: &[Self]) -> Result<Vec<Vec<String>>>;
+
+    /// Row summing up the records below the table
+    fn footer(_database: &FactureDatabase, _records: &[Self]) -> Result<Option<Vec<String>>> {
+        Ok(None)
+    }
+
+    /// The records as printed in JSON and YAML. Its field names stay the same when the stored
+    /// records change, so scripts can rely on them
+    type Output: Serialize;
+
+    fn output(database: &FactureDatabase, records: &[Self]) -> Result<Vec<Self::Output>>;
+}
+
+/// Formats the cells of a table for reading or with raw values for CSV
+#[derive(Clone, Copy)]
+enum Cells {
+    Table,
+    Csv,
+}
+
+impl Cells {
+    fn amount(self, amount: f32) -> String {
+        match self {
+            Cells::Table => format_amount(amount),
+            Cells::Csv => format!("{amount:.2}"),
+        }
+    }
+
+    fn optional(self, value: Option<String>) -> String {
+        match self {
+            Cells::Table => value.unwrap_or_else(|| "n/a".to_owned()),
+            Cells::Csv => value.unwrap_or_default(),
+        }
+    }
+
+    fn days(self, days: Option<i64>) -> String {
+        match self {
+            Cells::Table => days.map(|days| format!("{days} days")),
+            Cells::Csv => days.map(|days| days.to_string()),
+        }
+        .unwrap_or_default()
+    }
 }
 
 impl TableAble for Customer {
@@ -23,29 +79,309 @@ impl TableAble for Customer {
         ]
     }
 
-    fn row(self) -> Vec<String> {
+    fn rows(_database: &FactureDatabase, customers: &[Self]) -> Result<Vec<Vec<String>>> {
+        Ok(customer_rows(customers, Cells::Table))
+    }
+
+    fn csv_rows(_database: &FactureDatabase, customers: &[Self]) -> Result<Vec<Vec<String>>> {
+        Ok(customer_rows(customers, Cells::Csv))
+    }
+
+    type Output = CustomerOutput;
+
+    fn output(database: &FactureDatabase, customers: &[Self]) -> Result<Vec<CustomerOutput>> {
+        let invoices: Vec<Invoice> = database.read_all()?;
+        Ok(customers
+            .iter()
+            .map(|customer| CustomerOutput {
+                id: customer.id.clone(),
+                profile: customer.profile.clone(),
+                organization: customer.organization.clone(),
+                vat_id: customer.vat_id.clone(),
+                name: customer.contact.name.clone(),
+                surname: customer.contact.surname.clone(),
+                email: customer.contact.email.clone(),
+                phone: customer.contact.phone.clone(),
+                street: customer.address.street.clone(),
+                number: customer.address.number.clone(),
+                postal_code: customer.address.postal_code.clone(),
+                city: customer.address.city.clone(),
+                country: customer.address.country.clone(),
+                invoices: customer
+                    .invoices
+                    .iter()
+                    .filter_map(|uuid| invoices.iter().find(|invoice| &invoice.uuid == uuid))
+                    .map(|invoice| invoice.id.clone())
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+/// A customer in JSON and YAML lists
+#[derive(Serialize)]
+pub struct CustomerOutput {
+    id: String,
+    /// `null` for customers shared between all profiles
+    profile: Option<String>,
+    organization: String,
+    vat_id: String,
+    name: String,
+    surname: String,
+    email: Option<String>,
+    phone: Option<String>,
+    street: String,
+    number: String,
+    postal_code: String,
+    city: String,
+    country: String,
+    /// Ids of the invoices of the customer
+    invoices: Vec<String>,
+}
+
+fn customer_rows(customers: &[Customer], cells: Cells) -> Vec<Vec<String>> {
+    customers
+        .iter()
+        .map(|customer| {
+            vec![
+                customer.id.clone(),
+                customer.organization.clone(),
+                customer.contact.name.clone(),
+                customer.contact.surname.clone(),
+                cells.optional(customer.contact.email.clone()),
+                cells.optional(customer.contact.phone.clone()),
+                customer.invoices.len().to_string(),
+            ]
+        })
+        .collect()
+}
+
+impl TableAble for Invoice {
+    fn header() -> Vec<String> {
         vec![
-            self.id,
-            self.organization,
-            self.contact.name,
-            self.contact.surname,
-            self.contact.email.unwrap_or_else(|| "n/a".to_owned()),
-            self.contact.phone.unwrap_or_else(|| "n/a".to_owned()),
-            self.invoices.len().to_string(),
+            "ID".to_owned(),
+            "Customer".to_owned(),
+            "Date".to_owned(),
+            "Net".to_owned(),
+            "Gross".to_owned(),
+            "Due".to_owned(),
+            "Overdue".to_owned(),
+            "Status".to_owned(),
         ]
     }
+
+    fn rows(database: &FactureDatabase, invoices: &[Self]) -> Result<Vec<Vec<String>>> {
+        invoice_rows(database, invoices, Cells::Table)
+    }
+
+    fn csv_rows(database: &FactureDatabase, invoices: &[Self]) -> Result<Vec<Vec<String>>> {
+        invoice_rows(database, invoices, Cells::Csv)
+    }
+
+    fn footer(database: &FactureDatabase, invoices: &[Self]) -> Result<Option<Vec<String>>> {
+        let businesses: Vec<Business> = database.read_all()?;
+        let net = invoices.iter().map(Invoice::amount).sum();
+        let gross = invoices
+            .iter()
+            .map(|invoice| gross(&businesses, invoice))
+            .sum();
+        Ok(Some(vec![
+            "Total".to_owned(),
+            count(invoices.len(), "invoice"),
+            String::new(),
+            format_amount(net),
+            format_amount(gross),
+            String::new(),
+            String::new(),
+            String::new(),
+        ]))
+    }
+
+    type Output = InvoiceOutput;
+
+    fn output(database: &FactureDatabase, invoices: &[Self]) -> Result<Vec<InvoiceOutput>> {
+        let customers: Vec<Customer> = database.read_all()?;
+        let businesses: Vec<Business> = database.read_all()?;
+        let today = Local::now().date_naive();
+        Ok(invoices
+            .iter()
+            .map(|invoice| InvoiceOutput {
+                id: invoice.id.clone(),
+                profile: invoice.profile.clone(),
+                customer: customers
+                    .iter()
+                    .find(|customer| customer.uuid == invoice.customer)
+                    .map(|customer| customer.id.clone()),
+                issuing_date: invoice.issuing_date,
+                delivery_date: invoice.delivery_date,
+                due_date: invoice.due_date(),
+                payment_date: invoice.payment_date,
+                net: invoice.amount(),
+                gross: gross(&businesses, invoice),
+                status: invoice.payment_status(today).to_string(),
+                days_overdue: invoice.days_overdue(today),
+                issued_at: invoice.issuance.as_ref().map(|issuance| issuance.issued_at),
+                items: invoice
+                    .items
+                    .iter()
+                    .map(|item| ItemOutput {
+                        description: item.description.clone(),
+                        price: item.price,
+                        quantity: item.quantity.unwrap_or(1),
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
 }
 
-impl TableAble for Invoice {
+/// An invoice in JSON and YAML lists
+#[derive(Serialize)]
+pub struct InvoiceOutput {
+    id: String,
+    profile: String,
+    /// Id of the customer, `null` if the customer was purged
+    customer: Option<String>,
+    issuing_date: NaiveDate,
+    delivery_date: NaiveDate,
+    due_date: NaiveDate,
+    payment_date: Option<NaiveDate>,
+    net: f32,
+    /// Net amount with the VAT of the business
+    gross: f32,
+    /// `draft`, `open`, `overdue` or `paid`
+    status: String,
+    days_overdue: Option<i64>,
+    /// `null` while the invoice is a draft
+    issued_at: Option<NaiveDateTime>,
+    items: Vec<ItemOutput>,
+}
+
+#[derive(Serialize)]
+pub struct ItemOutput {
+    description: String,
+    price: f32,
+    quantity: u32,
+}
+
+fn invoice_rows(
+    database: &FactureDatabase,
+    invoices: &[Invoice],
+    cells: Cells,
+) -> Result<Vec<Vec<String>>> {
+    let customers: Vec<Customer> = database.read_all()?;
+    let businesses: Vec<Business> = database.read_all()?;
+    let today = Local::now().date_naive();
+    Ok(invoices
+        .iter()
+        .map(|invoice| {
+            let customer = customers
+                .iter()
+                .find(|customer| customer.uuid == invoice.customer)
+                .map(Customer::display_name);
+            vec![
+                invoice.id.clone(),
+                cells.optional(customer),
+                invoice.issuing_date.to_string(),
+                cells.amount(invoice.amount()),
+                cells.amount(gross(&businesses, invoice)),
+                invoice.due_date().to_string(),
+                cells.days(invoice.days_overdue(today)),
+                invoice.payment_status(today).to_string(),
+            ]
+        })
+        .collect())
+}
+
+/// Returns the amount of the invoice with the VAT of the business of its profile
+fn gross(businesses: &[Business], invoice: &Invoice) -> f32 {
+    businesses
+        .iter()
+        .find(|business| business.profile == invoice.profile)
+        .map_or(invoice.amount(), |business| {
+            business.gross(invoice.amount())
+        })
+}
+
+impl TableAble for HistoryEntry {
     fn header() -> Vec<String> {
-        vec!["ID".to_owned(), "Customer".to_owned(), "Date".to_owned()]
+        vec![
+            "Time".to_owned(),
+            "Operation".to_owned(),
+            "Type".to_owned(),
+            "Record".to_owned(),
+            "Changes".to_owned(),
+        ]
     }
 
-    fn row(self) -> Vec<String> {
-        vec![self.id, self.customer, self.issuing_date.to_string()]
+    fn rows(_database: &FactureDatabase, entries: &[Self]) -> Result<Vec<Vec<String>>> {
+        Ok(entries
+            .iter()
+            .map(|entry| {
+                vec![
+                    entry.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    entry.operation.to_string(),
+                    entry.record_type.clone(),
+                    entry
+                        .record_id
+                        .clone()
+                        .unwrap_or_else(|| entry.record.clone()),
+                    entry.changes.len().to_string(),
+                ]
+            })
+            .collect())
+    }
+
+    fn csv_rows(database: &FactureDatabase, entries: &[Self]) -> Result<Vec<Vec<String>>> {
+        Self::rows(database, entries)
+    }
+
+    type Output = HistoryOutput;
+
+    fn output(_database: &FactureDatabase, entries: &[Self]) -> Result<Vec<HistoryOutput>> {
+        Ok(entries
+            .iter()
+            .map(|entry| HistoryOutput {
+                timestamp: entry.timestamp,
+                operation: entry.operation.to_string(),
+                record_type: entry.record_type.clone(),
+                record: entry
+                    .record_id
+                    .clone()
+                    .unwrap_or_else(|| entry.record.clone()),
+                changes: entry
+                    .changes
+                    .iter()
+                    .map(|change| ChangeOutput {
+                        field: change.field.clone(),
+                        before: change.before.clone(),
+                        after: change.after.clone(),
+                    })
+                    .collect(),
+            })
+            .collect())
     }
 }
 
+/// A history entry in JSON and YAML lists
+#[derive(Serialize)]
+pub struct HistoryOutput {
+    timestamp: NaiveDateTime,
+    /// `create`, `update` or `delete`
+    operation: String,
+    record_type: String,
+    /// Id of the changed record, or its key for records without an id
+    record: String,
+    changes: Vec<ChangeOutput>,
+}
+
+#[derive(Serialize)]
+pub struct ChangeOutput {
+    field: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
 impl Display for Customer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&format!(
@@ -57,8 +393,36 @@ impl Display for Customer {
 
 impl Display for Invoice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!("{}", self.id))
+        f.write_str(&self.id)
+    }
+}
+
+/// Formats a count with the noun in singular or plural, like `1 invoice` or `3 invoices`
+pub fn count(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{count} {noun}")
+    } else {
+        format!("{count} {noun}s")
+    }
+}
+
+/// Formats an amount of euros like `1.190,00 €`
+pub fn format_amount(amount: f32) -> String {
+    let formatted = format!("{:.2}", amount.abs());
+    let (units, cents) = formatted.split_once('.').unwrap_or((&formatted, "00"));
+    let mut grouped = String::new();
+    for (index, digit) in units.chars().enumerate() {
+        if index > 0 && (units.len() - index) % 3 == 0 {
+            grouped.push('.');
+        }
+        grouped.push(digit);
     }
+    let sign = if amount < 0.0 && formatted != "0.00" {
+        "-"
+    } else {
+        ""
+    };
+    format!("{sign}{grouped},{cents} €")
 }
 
 pub fn table(header: Vec<String>, rows: Vec<Vec<String>>) -> String {
@@ -74,3 +438,31 @@ pub fn table(header: Vec<String>, rows: Vec<Vec<String>>) -> String {
 
     table.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{count, format_amount};
+
+    #[test]
+    fn counts_in_singular_and_plural() {
+        assert_eq!(count(0, "invoice"), "0 invoices");
+        assert_eq!(count(1, "invoice"), "1 invoice");
+        assert_eq!(count(2, "invoice"), "2 invoices");
+    }
+
+    #[test]
+    fn groups_thousands() {
+        assert_eq!(format_amount(0.5), "0,50 €");
+        assert_eq!(format_amount(920.0), "920,00 €");
+        assert_eq!(format_amount(1190.0), "1.190,00 €");
+        assert_eq!(format_amount(123456.78), "123.456,78 €");
+        assert_eq!(format_amount(1234567.0), "1.234.567,00 €");
+    }
+
+    #[test]
+    fn signs_only_amounts_that_do_not_round_to_zero() {
+        assert_eq!(format_amount(-1500.5), "-1.500,50 €");
+        assert_eq!(format_amount(-0.004), "0,00 €");
+        assert_eq!(format_amount(-0.0), "0,00 €");
+    }
+}
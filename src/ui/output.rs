@@ -0,0 +1,154 @@
+use super::{table, TableAble};
+use crate::{cli::OutputFormat, database::FactureDatabase};
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{io, sync::OnceLock};
+
+/// Set by `--output` for everything printed as a list or record
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+pub fn set_format(format: OutputFormat) {
+    FORMAT.get_or_init(|| format);
+}
+
+/// The format of `--output` if one was given
+pub fn format() -> Option<OutputFormat> {
+    FORMAT.get().copied()
+}
+
+fn format_or(default: OutputFormat) -> OutputFormat {
+    format().unwrap_or(default)
+}
+
+/// Prints the records as a table by default. JSON and YAML contain the output of
+/// [`TableAble::output`], CSV the columns of the table with raw values and without the footer
+pub fn print_records<T: TableAble>(
+    database: &FactureDatabase,
+    records: Vec<T>,
+    empty: &str,
+) -> Result<()> {
+    match format_or(OutputFormat::Table) {
+        OutputFormat::Json => print_json(&T::output(database, &records)?),
+        OutputFormat::Yaml => print_yaml(&T::output(database, &records)?),
+        OutputFormat::Csv => print_csv(T::header(), T::csv_rows(database, &records)?),
+        OutputFormat::Table if records.is_empty() => {
+            println!("{empty}");
+            Ok(())
+        }
+        OutputFormat::Table => {
+            let mut rows = T::rows(database, &records)?;
+            rows.extend(T::footer(database, &records)?);
+            println!("{}", table(T::header(), rows));
+            Ok(())
+        }
+    }
+}
+
+/// Prints rows that are not records as a table by default. JSON and YAML get an object per row
+/// with the columns in snake case as keys
+pub fn print_rows(header: Vec<String>, rows: Vec<Vec<String>>, empty: &str) -> Result<()> {
+    let objects = || -> Vec<Map<String, Value>> {
+        let keys: Vec<String> = header.iter().map(|column| snake_case(column)).collect();
+        rows.iter()
+            .map(|row| {
+                keys.iter()
+                    .cloned()
+                    .zip(row.iter().cloned().map(Value::String))
+                    .collect()
+            })
+            .collect()
+    };
+    match format_or(OutputFormat::Table) {
+        OutputFormat::Table if rows.is_empty() => println!("{empty}"),
+        OutputFormat::Table => println!("{}", table(header, rows)),
+        OutputFormat::Json => print_json(&objects())?,
+        OutputFormat::Yaml => print_yaml(&objects())?,
+        OutputFormat::Csv => print_csv(header, rows)?,
+    }
+    Ok(())
+}
+
+/// Prints a single record as YAML by default. The table and CSV list every field, nested fields
+/// like `address.city` and `items.0.price` flattened
+pub fn print_record<T: Serialize>(record: &T) -> Result<()> {
+    match format_or(OutputFormat::Yaml) {
+        OutputFormat::Json => print_json(record),
+        OutputFormat::Yaml => print_yaml(record),
+        OutputFormat::Table => {
+            let mut fields = Vec::new();
+            flatten(String::new(), serde_json::to_value(record)?, &mut fields);
+            let rows = fields
+                .into_iter()
+                .map(|(field, value)| vec![field, value])
+                .collect();
+            println!(
+                "{}",
+                table(vec!["Field".to_owned(), "Value".to_owned()], rows)
+            );
+            Ok(())
+        }
+        OutputFormat::Csv => {
+            let mut fields = Vec::new();
+            flatten(String::new(), serde_json::to_value(record)?, &mut fields);
+            let (header, row) = fields.into_iter().unzip();
+            print_csv(header, vec![row])
+        }
+    }
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn print_yaml<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    print!("{}", serde_yml::to_string(value)?);
+    Ok(())
+}
+
+fn print_csv(header: Vec<String>, rows: Vec<Vec<String>>) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(io::stdout());
+    writer.write_record(header)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Collects the leaves of the value with their dotted path. Empty values become empty strings
+fn flatten(path: String, value: Value, fields: &mut Vec<(String, String)>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(join(&key), value, fields);
+            }
+        }
+        Value::Array(values) if values.is_empty() => fields.push((path, String::new())),
+        Value::Array(values) => {
+            for (index, value) in values.into_iter().enumerate() {
+                flatten(join(&index.to_string()), value, fields);
+            }
+        }
+        Value::Null => fields.push((path, String::new())),
+        Value::String(value) => fields.push((path, value)),
+        value => fields.push((path, value.to_string())),
+    }
+}
+
+/// Turns a column like `Kept until` into `kept_until`
+fn snake_case(column: &str) -> String {
+    column
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("_")
+}
@@ -1,10 +1,17 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use inquire::{
     ui::{RenderConfig, StyleSheet},
     validator::ValueRequiredValidator,
-    Confirm, Editor, Select, Text,
+    Confirm, Editor, MultiSelect, Password, PasswordDisplayMode, Select, Text,
+};
+use std::{
+    fmt::Display,
+    io::{self, IsTerminal},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock,
+    },
 };
-use std::{fmt::Display, sync::LazyLock};
 
 static RENDER_CONFIG: LazyLock<RenderConfig> = LazyLock::new(|| {
     RenderConfig::default_colored()
@@ -14,6 +21,25 @@ static RENDER_CONFIG: LazyLock<RenderConfig> = LazyLock::new(|| {
         ))
 });
 
+/// Set by `--yes` to answer every confirmation with yes
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+pub fn assume_yes() {
+    ASSUME_YES.store(true, Ordering::Relaxed);
+}
+
+/// Whether prompts and the editor can be used. Scripts pass ids and fields as arguments instead
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+fn require_terminal(message: &str, instead: &str) -> Result<()> {
+    if !is_interactive() {
+        bail!("{message}: no terminal to ask on. {instead}");
+    }
+    Ok(())
+}
+
 pub fn _text(message: &str, help_message: Option<&str>) -> Result<String> {
     let validator = ValueRequiredValidator::new("A value is required for this field");
     let help_message = help_message.unwrap_or_default();
@@ -45,7 +71,21 @@ pub fn _editor(message: &str, file_content: &str, file_extension: &str) -> Resul
     Ok(content)
 }
 
+/// An option that is shown and fuzzy searched by its label instead of the `Display` of its value
+pub struct Choice<T> {
+    pub label: String,
+    pub value: T,
+}
+
+impl<T> Display for Choice<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.label)
+    }
+}
+
+/// Asks for one of the options. Typing filters the options fuzzily
 pub fn select<T: Display>(message: &str, options: Vec<T>) -> Result<T> {
+    require_terminal(message, "Pass the id as an argument")?;
     let answer = Select::new(message, options)
         .with_render_config(*RENDER_CONFIG)
         .prompt()?;
@@ -53,10 +93,45 @@ pub fn select<T: Display>(message: &str, options: Vec<T>) -> Result<T> {
     Ok(answer)
 }
 
+/// Asks for any number of the options. Typing filters the options fuzzily
+pub fn multi_select<T: Display>(message: &str, options: Vec<T>) -> Result<Vec<T>> {
+    require_terminal(message, "Pass the ids as arguments")?;
+    let answer = MultiSelect::new(message, options)
+        .with_render_config(*RENDER_CONFIG)
+        .with_help_message("↑↓ to move, space to select, → to select all, type to filter")
+        .prompt()?;
+
+    Ok(answer)
+}
+
 pub fn confirm(message: &str) -> Result<bool> {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return Ok(true);
+    }
+    require_terminal(message, "Pass --yes to confirm")?;
     let answer = Confirm::new(message)
         .with_default(false)
         .with_render_config(*RENDER_CONFIG)
         .prompt()?;
     Ok(answer)
 }
+
+pub fn password(message: &str) -> Result<String> {
+    let password = Password::new(message)
+        .with_render_config(*RENDER_CONFIG)
+        .with_display_mode(PasswordDisplayMode::Hidden)
+        .without_confirmation()
+        .prompt()?;
+    Ok(password)
+}
+
+/// Asks for a password twice
+pub fn new_password(message: &str) -> Result<String> {
+    let password = Password::new(message)
+        .with_render_config(*RENDER_CONFIG)
+        .with_display_mode(PasswordDisplayMode::Hidden)
+        .with_custom_confirmation_message("Repeat the passphrase")
+        .with_custom_confirmation_error_message("The passphrases do not match")
+        .prompt()?;
+    Ok(password)
+}
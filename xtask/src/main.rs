@@ -0,0 +1,90 @@
+//! Development tasks of facture, run with `cargo xtask <task>`
+//!
+//! * `docs` writes README.md and the man pages in `man` from the command line definition
+
+#[path = "../../src/cli.rs"]
+mod cli;
+
+/// The completers of `cli` read the database, which the tasks do not have. Only the help and
+/// the man pages are generated here, which never call them
+mod completion {
+    use clap_complete::engine::CompletionCandidate;
+    use std::ffi::OsStr;
+
+    pub fn customer_ids(_current: &OsStr) -> Vec<CompletionCandidate> {
+        Vec::new()
+    }
+
+    pub fn invoice_ids(_current: &OsStr) -> Vec<CompletionCandidate> {
+        Vec::new()
+    }
+
+    pub fn record_ids(_current: &OsStr) -> Vec<CompletionCandidate> {
+        Vec::new()
+    }
+}
+
+use clap::CommandFactory;
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+/// Folder the man pages are written to, one per command like `facture-invoice-list.1`
+const MAN_FOLDER: &str = "man";
+
+fn main() -> ExitCode {
+    let task = env::args().nth(1);
+    let result = match task.as_deref() {
+        Some("docs") => docs(),
+        _ => {
+            eprintln!("Usage: cargo xtask docs");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(error) = result {
+        eprintln!("Error: {error}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Writes README.md and the man pages of every command
+fn docs() -> io::Result<()> {
+    let root = root();
+    // The command is named after the crate it is built in otherwise
+    let command = cli::Cli::command().name("facture").bin_name("facture");
+    let markdown = clap_markdown::help_markdown_command(&command);
+    fs::write(root.join("README.md"), markdown)?;
+
+    let man = root.join(MAN_FOLDER);
+    fs::create_dir_all(&man)?;
+    remove_pages(&man)?;
+    clap_mangen::generate_to(command, &man)?;
+    println!("Wrote README.md and {}", man.display());
+    Ok(())
+}
+
+/// Removes the generated pages, so pages of removed commands do not stay behind
+fn remove_pages(man: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(man)? {
+        let path = entry?.path();
+        let is_page = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("facture") && name.ends_with(".1"));
+        if is_page {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// The folder of the facture crate, wherever the task is run from
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is in the folder of facture")
+        .to_owned()
+}